@@ -0,0 +1,323 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDateTime};
+
+use serde::Serialize;
+
+use super::calls::CallLines;
+use super::lineparser::{LogLine, LogWriter, SResult, Severity};
+use super::report::{write_report, ReportOptions, TimeBucket, TIME_FORMAT};
+use super::sessions::client_ip;
+
+
+// operations of sql_execute that change the schema or the privileges
+const DDL_OPERATIONS: [&str; 7] = ["CREATE", "DROP", "ALTER", "TRUNCATE", "RENAME", "GRANT", "REVOKE"];
+
+// One row of the audit report. Each category uses the same columns,
+// so the json format can be sent as is to a SIEM.
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditRow {
+    // brute_force, failed_login, privilege_error, ddl or access
+    pub category: String,
+    pub time: String,
+    pub end: String,
+    pub count: u64,
+    pub username: String,
+    pub client: String,
+    pub dbname: String,
+    pub hostname: String,
+    pub event: String,
+    pub detail: String,
+}
+
+impl AuditRow {
+    fn new(category: &str, time: &NaiveDateTime, end: &NaiveDateTime, count: u64) -> AuditRow {
+        AuditRow {
+            category: category.to_string(),
+            time: time.format(TIME_FORMAT).to_string(),
+            end: end.format(TIME_FORMAT).to_string(),
+            count,
+            username: String::from(""),
+            client: String::from(""),
+            dbname: String::from(""),
+            hostname: String::from(""),
+            event: String::from(""),
+            detail: String::from(""),
+        }
+    }
+}
+
+// Who is on a thread, from the last stdlog call on it
+#[derive(Default, Clone)]
+struct Caller {
+    username: Option<String>,
+    client: Option<String>,
+    dbname: Option<String>,
+}
+
+// A failed login, until the stdlog of its connect call says the user and client
+struct FailedLogin {
+    hostname: String,
+    threadid: Option<i32>,
+    logtime: NaiveDateTime,
+}
+
+struct Access {
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+    count: u64,
+}
+
+// Builds the security audit from the AUTH lines and the stdlog calls:
+// failed logins per user and client IP per --bucket, with the user and client of their connect call,
+// brute force when there are
+// at least ReportOptions::brute_force_failures from one user and IP within
+// ReportOptions::brute_force_minutes, privilege errors, DDL, GRANT and REVOKE statements,
+// and which users used which databases.
+pub struct AuditWriter {
+    options: ReportOptions,
+    // (hostname, username, client) -> times of the failed logins
+    failed_logins: BTreeMap<(String, String, String), Vec<NaiveDateTime>>,
+    // other rows, kept in log order
+    events: Vec<(NaiveDateTime, AuditRow)>,
+    // (hostname, username, dbname) -> calls
+    access: BTreeMap<(String, String, String), Access>,
+    // the failed logins waiting for their connect call
+    logins: CallLines<FailedLogin>,
+    // (hostname, pid, threadid) -> the last caller
+    threads: HashMap<(String, i32, i32), Caller>,
+}
+
+impl AuditWriter {
+    pub fn new(options: ReportOptions) -> AuditWriter {
+        AuditWriter {
+            options,
+            failed_logins: BTreeMap::new(),
+            events: vec![],
+            access: BTreeMap::new(),
+            logins: CallLines::new(),
+            threads: HashMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<AuditRow> {
+        let mut rows = self.brute_force_rows();
+        rows.append(&mut self.failed_login_rows());
+
+        let mut events: Vec<&(NaiveDateTime, AuditRow)> = self.events.iter().collect();
+        events.sort_by_key(|(time, row)| (row.category != "privilege_error", *time));
+        rows.extend(events.into_iter().map(|(_, row)| row.clone()));
+
+        for ((hostname, username, dbname), access) in &self.access {
+            let mut row = AuditRow::new("access", &access.first, &access.last, access.count);
+            row.username = username.to_string();
+            row.dbname = dbname.to_string();
+            row.hostname = hostname.to_string();
+            rows.push(row);
+        }
+        rows
+    }
+
+    // failed logins of each user and client IP, per time bucket
+    fn failed_login_rows(&self) -> Vec<AuditRow> {
+        let bucket = self.options.bucket.clone().unwrap_or(TimeBucket::Hour);
+        let mut rows: Vec<AuditRow> = vec![];
+        for ((hostname, username, client), times) in &self.failed_logins {
+            let mut windows: BTreeMap<String, (NaiveDateTime, NaiveDateTime, u64)> = BTreeMap::new();
+            for time in times {
+                let window = windows.entry(bucket.format(time)).or_insert((*time, *time, 0));
+                window.0 = window.0.min(*time);
+                window.1 = window.1.max(*time);
+                window.2 += 1;
+            }
+            for (first, last, count) in windows.values() {
+                let mut row = AuditRow::new("failed_login", first, last, *count);
+                row.username = username.to_string();
+                row.client = client.to_string();
+                row.hostname = hostname.to_string();
+                rows.push(row);
+            }
+        }
+        rows.sort_by(|a, b| a.time.cmp(&b.time));
+        rows
+    }
+
+    // runs of failed logins with at least brute_force_failures within any brute_force_minutes
+    fn brute_force_rows(&self) -> Vec<AuditRow> {
+        let failures = self.options.brute_force_failures.max(1);
+        let minutes = Duration::minutes(self.options.brute_force_minutes);
+        let mut rows: Vec<AuditRow> = vec![];
+        for ((hostname, username, client), times) in &self.failed_logins {
+            let mut times = times.clone();
+            times.sort();
+            // (first, last) index of the current run
+            let mut run: Option<(usize, usize)> = None;
+            let mut runs: Vec<(usize, usize)> = vec![];
+            let mut start = 0;
+            for end in 0..times.len() {
+                while times[end] - times[start] > minutes {
+                    start += 1;
+                }
+                if end + 1 - start < failures {
+                    continue
+                }
+                run = match run {
+                    Some((first, last)) if start <= last => Some((first, end)),
+                    Some(previous) => {
+                        runs.push(previous);
+                        Some((start, end))
+                    },
+                    None => Some((start, end)),
+                };
+            }
+            runs.extend(run);
+            for (first, last) in runs {
+                let mut row = AuditRow::new("brute_force", &times[first], &times[last], (last + 1 - first) as u64);
+                row.username = username.to_string();
+                row.client = client.to_string();
+                row.hostname = hostname.to_string();
+                row.detail = format!("{} or more failed logins within {} minutes",
+                    self.options.brute_force_failures, self.options.brute_force_minutes);
+                rows.push(row);
+            }
+        }
+        rows.sort_by(|a, b| a.time.cmp(&b.time));
+        rows
+    }
+
+    fn add_failed_login(&mut self, login: &FailedLogin, username: &str, client: &str) {
+        let key = (login.hostname.to_string(), username.to_string(), client_ip(client).to_string());
+        self.failed_logins.entry(key).or_default().push(login.logtime);
+    }
+
+    fn add_event(&mut self, category: &str, log: &LogLine, caller: &Caller, detail: &str) {
+        let value = |x: &Option<String>| x.clone().unwrap_or_default();
+        let mut row = AuditRow::new(category, &log.logtime, &log.logtime, 1);
+        row.username = value(&caller.username);
+        row.client = match &caller.client {
+            None => String::from(""),
+            Some(client) => client_ip(client).to_string(),
+        };
+        row.dbname = value(&caller.dbname);
+        row.hostname = value(&log.hostname);
+        row.event = value(&log.event);
+        row.detail = detail.to_string();
+        self.events.push((log.logtime, row));
+    }
+}
+
+impl LogWriter for AuditWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let thread = log.threadid.map(|x| (hostname.clone(), log.pid, x));
+        let caller = if log.sequence.is_some() {
+            let caller = Caller {
+                username: log.username.clone(),
+                client: log.client.clone(),
+                dbname: log.dbname.clone(),
+            };
+            if let Some(thread) = thread {
+                self.threads.insert(thread, caller.clone());
+            }
+            caller
+        } else {
+            match thread.and_then(|x| self.threads.get(&x)) {
+                Some(caller) => caller.clone(),
+                None => Caller::default(),
+            }
+        };
+
+        // a failed connect has no user in its session, only in its name values
+        if log.event.as_deref() == Some("connect") && log.sequence.is_some() {
+            // the failures of the thread of the call, not of a concurrent connect
+            let logins = self.logins.take(log, |x| {
+                x.threadid.is_none() || log.threadid.is_none() || x.threadid == log.threadid
+            });
+            if let Some(login) = logins.first() {
+                let username = match log.username.as_deref() {
+                    Some(x) if !x.is_empty() => x.to_string(),
+                    _ => name_value(log, "user").unwrap_or_default(),
+                };
+                // one failure per call, which may log it more than once
+                self.add_failed_login(login, &username, log.client.as_deref().unwrap_or(""));
+            }
+        }
+        // the failed logins too old for a connect call, without a user and client
+        for login in self.logins.expire() {
+            self.add_failed_login(&login, "", "");
+        }
+
+        let event = log.event.clone().unwrap_or_default();
+        if log.sequence.is_some() {
+            if event.ends_with("_begin") {
+                return Ok(())
+            }
+            match (&log.username, &log.dbname) {
+                // a failed connect has an empty user and dbname
+                (Some(username), Some(dbname)) if !username.is_empty() && !dbname.is_empty() => {
+                    let key = (hostname, username.to_string(), dbname.to_string());
+                    let access = self.access.entry(key).or_insert(Access {
+                        first: log.logtime,
+                        last: log.logtime,
+                        count: 0,
+                    });
+                    access.first = access.first.min(log.logtime);
+                    access.last = access.last.max(log.logtime);
+                    access.count += 1;
+                },
+                _ => (),
+            }
+            if let (Some(operation), Some(query)) = (&log.operation, &log.query) {
+                if DDL_OPERATIONS.contains(&operation.as_str()) {
+                    self.add_event("ddl", log, &caller, query);
+                }
+            }
+            return Ok(())
+        }
+
+        match log.severity {
+            Severity::AUTH => (),
+            _ => return Ok(()),
+        }
+        match event.as_str() {
+            "login_failure" | "authentication_failure" => {
+                self.logins.push(log, FailedLogin { hostname, threadid: log.threadid, logtime: log.logtime });
+            },
+            "session_invalidated" => (),
+            _ => self.add_event("privilege_error", log, &caller, &log.msg),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        // the failed logins whose connect call has no stdlog
+        for login in self.logins.take_all() {
+            self.add_failed_login(&login, "", "");
+        }
+        write_report(&self.rows(), &self.options)
+    }
+}
+
+// the value of a key of the stdlog name values, like user of a failed connect
+fn name_value(log: &LogLine, key: &str) -> Option<String> {
+    log.name_values.as_ref().and_then(|x| {
+        x.chunks(2).find(|x| x.len() == 2 && x[0] == key).map(|x| x[1].to_string())
+    })
+}
@@ -0,0 +1,781 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+
+use lazy_static::lazy_static;
+
+use regex::Regex;
+
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap};
+use tui::{Frame, Terminal};
+
+use super::lineparser::{scrape_log, wrap_writer, LineFilter, LogLine, LogWriter, SResult, Severity};
+use super::redact::Redactor;
+use super::report::TIME_FORMAT;
+
+
+// most lines to take from the reader between two draws, so keys stay responsive while loading
+pub(crate) const BATCH_SIZE: usize = 10000;
+
+// most lines kept with --follow, the oldest are dropped for new ones, so it stays within memory
+const MAX_LINES: usize = 1000000;
+
+// name, width, visible by default
+const COLUMNS: [(&str, u16, bool); 18] = [
+    ("logtime", 26, true),
+    ("severity", 8, true),
+    ("verbosity", 9, false),
+    ("event", 20, true),
+    ("sequence", 8, false),
+    ("dur_ms", 7, true),
+    ("execution_time", 9, false),
+    ("total_time", 9, false),
+    ("session", 9, false),
+    ("dbname", 12, true),
+    ("username", 12, true),
+    ("client", 22, false),
+    ("fileline", 24, true),
+    ("pid", 6, false),
+    ("threadid", 8, false),
+    ("hostname", 12, false),
+    ("query", 50, true),
+    ("msg", 80, true),
+];
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: &'static str,
+    pub width: u16,
+    pub visible: bool,
+}
+
+// Value of a column as shown in the table, None if there is no such column
+pub fn column_value(log: &LogLine, name: &str) -> Option<String> {
+    let text = |x: &Option<String>| x.clone().unwrap_or_default();
+    let number = |x: Option<i32>| x.map(|x| x.to_string()).unwrap_or_default();
+    Some(match name {
+        "logtime" => log.logtime.format(TIME_FORMAT).to_string(),
+        "severity" => log.severity.to_string(),
+        "verbosity" => number(log.verbosity),
+        "event" => text(&log.event),
+        "sequence" => number(log.sequence),
+        "dur_ms" => number(log.dur_ms),
+        "execution_time" => number(log.execution_time),
+        "total_time" => number(log.total_time),
+        "session" => text(&log.session),
+        "dbname" => text(&log.dbname),
+        "username" => text(&log.username),
+        "client" => text(&log.client),
+        "fileline" => log.fileline.to_string(),
+        "pid" => log.pid.to_string(),
+        "threadid" => number(log.threadid),
+        "hostname" => text(&log.hostname),
+        "query" => text(&log.query),
+        "msg" => log.msg.to_string(),
+        _ => return None,
+    })
+}
+
+fn severity_color(log: &LogLine) -> Color {
+    // same as print_colorize
+    match log.severity {
+        Severity::FATAL => Color::Red,
+        Severity::ERROR => Color::Red,
+        Severity::WARNING => Color::Red,
+        Severity::INFO => Color::Blue,
+        Severity::DEBUG => match log.verbosity {
+            Some(2) => Color::LightGreen,
+            Some(3) => Color::LightCyan,
+            Some(4) => Color::DarkGray,
+            _ => Color::Green,
+        },
+        Severity::OTHER => Color::Cyan,
+        Severity::INPUT => Color::Gray,
+        Severity::AUTH => Color::Magenta,
+    }
+}
+
+// Puts the main clauses of a query on their own lines, and AND/OR indented.
+// Quoted strings are left as they are.
+pub fn pretty_sql(query: &str) -> String {
+    lazy_static! {
+        static ref QUOTED: Regex = Regex::new(r#"'(?:[^']|'')*'?|"(?:[^"]|"")*"?"#).unwrap();
+        static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+        static ref CLAUSE: Regex = Regex::new(
+            r"(?i)\s+\b(from|where|group\s+by|order\s+by|having|limit|offset|union(?:\s+all)?|(?:(?:left|right|full|inner|cross)\s+(?:outer\s+)?)?join|on)\b").unwrap();
+        static ref CONDITION: Regex = Regex::new(r"(?i)\s+\b(and|or)\b").unwrap();
+    }
+    let format = |text: &str| {
+        let text = WHITESPACE.replace_all(text, " ");
+        let text = CLAUSE.replace_all(&text, "\n$1");
+        CONDITION.replace_all(&text, "\n  $1").to_string()
+    };
+    let mut pretty = String::new();
+    let mut last = 0;
+    for m in QUOTED.find_iter(query) {
+        pretty.push_str(&format(&query[last..m.start()]));
+        pretty.push_str(m.as_str());
+        last = m.end();
+    }
+    pretty.push_str(&format(&query[last..]));
+    pretty.trim().to_string()
+}
+
+// text must be lowercase
+fn has_text(log: &LogLine, text: &str) -> bool {
+    if log.msg.to_lowercase().contains(text) {
+        return true
+    }
+    match &log.query {
+        None => false,
+        Some(query) => query.to_lowercase().contains(text),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum FilterOp {
+    Equal,
+    NotEqual,
+    Contains,
+    Greater,
+    Less,
+}
+
+// One term of the filter prompt, like severity=ERROR, dur_ms>100, msg~lock, or just text
+#[derive(Debug, Clone)]
+struct Filter {
+    // None matches the text in msg or query
+    column: Option<String>,
+    op: FilterOp,
+    value: String,
+}
+
+impl Filter {
+    fn parse(term: &str) -> SResult<Filter> {
+        let ops = [("!=", FilterOp::NotEqual), ("=", FilterOp::Equal), ("~", FilterOp::Contains),
+            (">", FilterOp::Greater), ("<", FilterOp::Less)];
+        let mut found: Option<(usize, &str, FilterOp)> = None;
+        for (name, op) in ops.iter() {
+            if let Some(i) = term.find(name) {
+                let earlier = match &found {
+                    None => true,
+                    Some((j, _, _)) => i < *j,
+                };
+                if earlier {
+                    found = Some((i, *name, op.clone()));
+                }
+            }
+        }
+        match found {
+            None => Ok(Filter { column: None, op: FilterOp::Contains, value: term.to_lowercase() }),
+            Some((i, name, op)) => {
+                let column = &term[..i];
+                if COLUMNS.iter().all(|(x, _, _)| *x != column) {
+                    return Err(Box::new(Error::new(ErrorKind::InvalidInput, format!("Unknown column: '{}'", column))))
+                }
+                let value = term[i + name.len()..].to_string();
+                let value = if op == FilterOp::Contains { value.to_lowercase() } else { value };
+                Ok(Filter { column: Some(column.to_string()), op, value })
+            },
+        }
+    }
+
+    fn matches(&self, log: &LogLine) -> bool {
+        let column = match &self.column {
+            None => return has_text(log, &self.value),
+            Some(x) => x,
+        };
+        let value = column_value(log, column).unwrap_or_default();
+        let number = |x: &str| x.parse::<f64>().ok();
+        match self.op {
+            FilterOp::Equal => value.eq_ignore_ascii_case(&self.value),
+            FilterOp::NotEqual => !value.eq_ignore_ascii_case(&self.value),
+            FilterOp::Contains => value.to_lowercase().contains(&self.value),
+            FilterOp::Greater => match (number(&value), number(&self.value)) {
+                (Some(a), Some(b)) => a > b,
+                _ => false,
+            },
+            FilterOp::Less => match (number(&value), number(&self.value)) {
+                (Some(a), Some(b)) => a < b,
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Mode {
+    Normal,
+    Search,
+    Filter,
+    Columns,
+}
+
+// State of the log browser, separate from drawing it
+pub struct Browser {
+    logs: VecDeque<LogLine>,
+    // number of the oldest lines dropped, the index of the first of logs
+    dropped: usize,
+    max_lines: usize,
+    // indexes of the logs that match the filter, counting the dropped ones
+    rows: VecDeque<usize>,
+    filters: Vec<Filter>,
+    filter_text: String,
+    pub columns: Vec<Column>,
+    // index into rows
+    selected: usize,
+    // first row drawn, and how many rows fit
+    offset: usize,
+    height: usize,
+    pub detail: bool,
+    detail_scroll: u16,
+    mode: Mode,
+    prompt: String,
+    search_text: String,
+    search_from: usize,
+    column_cursor: usize,
+    pub message: String,
+    follow: bool,
+}
+
+impl Browser {
+    pub fn new(follow: bool) -> Browser {
+        Browser {
+            logs: VecDeque::new(),
+            dropped: 0,
+            max_lines: usize::MAX,
+            rows: VecDeque::new(),
+            filters: vec![],
+            filter_text: String::from(""),
+            columns: COLUMNS.iter().map(|(name, width, visible)| Column {
+                name,
+                width: *width,
+                visible: *visible,
+            }).collect(),
+            selected: 0,
+            offset: 0,
+            height: 20,
+            detail: false,
+            detail_scroll: 0,
+            mode: Mode::Normal,
+            prompt: String::from(""),
+            search_text: String::from(""),
+            search_from: 0,
+            column_cursor: 0,
+            message: String::from(""),
+            follow,
+        }
+    }
+
+    // Keeps only the newest lines, all of them by default
+    pub fn with_max_lines(mut self, max_lines: usize) -> Browser {
+        self.max_lines = max_lines.max(1);
+        self
+    }
+
+    // In follow mode, if the last row is selected it stays selected, like tail -f
+    pub fn push(&mut self, log: LogLine) {
+        let tailing = self.follow && (self.rows.is_empty() || self.selected + 1 == self.rows.len());
+        if self.filters.iter().all(|x| x.matches(&log)) {
+            self.rows.push_back(self.dropped + self.logs.len());
+            if tailing {
+                self.selected = self.rows.len() - 1;
+            }
+        }
+        self.logs.push_back(log);
+        if self.logs.len() > self.max_lines {
+            self.drop_oldest();
+        }
+    }
+
+    // The selection stays on the same line, or the next one if it was the oldest
+    fn drop_oldest(&mut self) {
+        self.logs.pop_front();
+        if self.rows.front() == Some(&self.dropped) {
+            self.rows.pop_front();
+            self.selected = self.selected.saturating_sub(1);
+            self.offset = self.offset.saturating_sub(1);
+            self.search_from = self.search_from.saturating_sub(1);
+        }
+        self.dropped += 1;
+    }
+
+    fn log(&self, i: usize) -> &LogLine {
+        &self.logs[i - self.dropped]
+    }
+
+    // number of the lines dropped for newer ones
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    // number of rows that match the filter
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_log(&self) -> Option<&LogLine> {
+        self.rows.get(self.selected).map(|i| self.log(*i))
+    }
+
+    pub fn select(&mut self, row: usize) {
+        self.selected = row.min(self.rows.len().max(1) - 1);
+        self.detail_scroll = 0;
+    }
+
+    // Space separated terms that must all match, see Filter.
+    // The selection stays on the same line, or the next one that matches.
+    pub fn set_filter(&mut self, text: &str) -> SResult<()> {
+        let mut filters = vec![];
+        for term in text.split_whitespace() {
+            filters.push(Filter::parse(term)?);
+        }
+        let current = self.rows.get(self.selected).cloned();
+        self.filters = filters;
+        self.filter_text = text.trim().to_string();
+        let logs = &self.logs;
+        let filters = &self.filters;
+        self.rows = (0..logs.len()).filter(|i| filters.iter().all(|x| x.matches(&logs[*i])))
+            .map(|i| self.dropped + i)
+            .collect();
+        let row = match current {
+            None => 0,
+            Some(current) => match self.rows.binary_search(&current) {
+                Ok(i) => i,
+                Err(i) => i,
+            },
+        };
+        self.select(row);
+        Ok(())
+    }
+
+    // Selects the next row with text in msg or query, ignoring case, starting at row from,
+    // and wrapping around. Returns false if no row has it.
+    pub fn search(&mut self, text: &str, forward: bool, from: usize) -> bool {
+        let n = self.rows.len();
+        if n == 0 || text.is_empty() {
+            return false
+        }
+        let text = text.to_lowercase();
+        for i in 0..n {
+            let row = if forward { (from + i) % n } else { (from + n - i) % n };
+            if has_text(self.log(self.rows[row]), &text) {
+                self.select(row);
+                return true
+            }
+        }
+        false
+    }
+
+    // From a stdlog_begin line selects its stdlog line, or the other way.
+    // Returns false if the line is not a stdlog call, or the other one is not in the rows.
+    pub fn jump_pair(&mut self) -> bool {
+        let (pid, sequence, event) = match self.selected_log() {
+            Some(LogLine { pid, sequence: Some(sequence), event: Some(event), .. }) => (*pid, *sequence, event.to_string()),
+            _ => return false,
+        };
+        let is_pair = |log: &LogLine, target: &str| {
+            log.pid == pid && log.sequence == Some(sequence) && log.event.as_deref() == Some(target)
+        };
+        let found = match event.strip_suffix("_begin") {
+            Some(target) => (self.selected + 1..self.rows.len()).find(|i| is_pair(self.log(self.rows[*i]), target)),
+            None => {
+                let target = format!("{}_begin", event);
+                (0..self.selected).rev().find(|i| is_pair(self.log(self.rows[*i]), &target))
+            },
+        };
+        match found {
+            None => false,
+            Some(row) => {
+                self.select(row);
+                true
+            },
+        }
+    }
+
+    // The full selected line, for the detail pane
+    pub fn detail_lines(&self) -> Vec<String> {
+        let log = match self.selected_log() {
+            None => return vec![],
+            Some(x) => x,
+        };
+        let value = |name: &str| column_value(log, name).unwrap_or_default();
+        let mut lines = vec![format!("{} {} {} pid {} thread {}",
+            value("logtime"), value("severity"), log.fileline, log.pid, value("threadid"))];
+        if log.event.is_some() {
+            lines.push(format!("event {} sequence {} dur_ms {} execution_time {} total_time {}",
+                value("event"), value("sequence"), value("dur_ms"), value("execution_time"), value("total_time")));
+        }
+        if log.session.is_some() {
+            lines.push(format!("session {} dbname {} username {} client {}",
+                value("session"), value("dbname"), value("username"), value("client")));
+        }
+        if !log.msg.is_empty() {
+            lines.push(String::from(""));
+            lines.extend(log.msg.lines().map(|x| x.to_string()));
+        }
+        if let Some(name_values) = &log.name_values {
+            lines.push(String::from(""));
+            for pair in name_values.chunks(2) {
+                lines.push(format!("{} = {}", pair[0], pair.get(1).cloned().unwrap_or_default()));
+            }
+        }
+        if let Some(query) = &log.query {
+            lines.push(String::from(""));
+            lines.extend(pretty_sql(query).lines().map(|x| x.to_string()));
+        }
+        lines
+    }
+
+    // Keeps the selected row in the rows drawn
+    fn scroll(&mut self, height: usize) {
+        self.height = height.max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.height {
+            self.offset = self.selected + 1 - self.height;
+        }
+    }
+
+    // Returns true to quit
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true
+        }
+        self.message = String::from("");
+        match self.mode {
+            Mode::Normal => return self.handle_normal_key(key),
+            Mode::Search => match key.code {
+                KeyCode::Enter => {
+                    self.search_text = self.prompt.clone();
+                    self.mode = Mode::Normal;
+                },
+                KeyCode::Esc => {
+                    self.select(self.search_from);
+                    self.mode = Mode::Normal;
+                },
+                KeyCode::Backspace | KeyCode::Char(_) => {
+                    match key.code {
+                        KeyCode::Char(c) => self.prompt.push(c),
+                        _ => { self.prompt.pop(); },
+                    }
+                    let text = self.prompt.clone();
+                    if !self.search(&text, true, self.search_from) {
+                        self.message = String::from("not found");
+                    }
+                },
+                _ => (),
+            },
+            Mode::Filter => match key.code {
+                KeyCode::Enter => {
+                    let text = self.prompt.clone();
+                    if let Err(e) = self.set_filter(&text) {
+                        self.message = e.to_string();
+                    }
+                    self.mode = Mode::Normal;
+                },
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => { self.prompt.pop(); },
+                KeyCode::Char(c) => self.prompt.push(c),
+                _ => (),
+            },
+            Mode::Columns => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('c') | KeyCode::Char('q') => self.mode = Mode::Normal,
+                KeyCode::Up | KeyCode::Char('k') => self.column_cursor = self.column_cursor.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => self.column_cursor = (self.column_cursor + 1).min(self.columns.len() - 1),
+                KeyCode::Char(' ') => {
+                    let column = &mut self.columns[self.column_cursor];
+                    column.visible = !column.visible;
+                },
+                KeyCode::Char('+') | KeyCode::Right => self.columns[self.column_cursor].width += 2,
+                KeyCode::Char('-') | KeyCode::Left => {
+                    let column = &mut self.columns[self.column_cursor];
+                    column.width = column.width.saturating_sub(2).max(3);
+                },
+                _ => (),
+            },
+        }
+        false
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> bool {
+        let last = self.rows.len().max(1) - 1;
+        match key.code {
+            KeyCode::Char('q') => return true,
+            KeyCode::Esc => if self.detail {
+                self.detail = false;
+            } else {
+                return true
+            },
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.select(self.selected + self.height),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(self.height)),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(last),
+            KeyCode::Enter => {
+                self.detail = !self.detail;
+                self.detail_scroll = 0;
+            },
+            KeyCode::Char('d') => self.detail_scroll += 1,
+            KeyCode::Char('u') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            KeyCode::Char('/') => {
+                self.mode = Mode::Search;
+                self.prompt = String::from("");
+                self.search_from = self.selected;
+            },
+            KeyCode::Char('n') | KeyCode::Char('N') => {
+                let text = self.search_text.clone();
+                let found = if key.code == KeyCode::Char('n') {
+                    self.search(&text, true, self.selected + 1)
+                } else {
+                    self.search(&text, false, (self.selected + last) % (last + 1))
+                };
+                if !found {
+                    self.message = String::from("not found");
+                }
+            },
+            KeyCode::Char('f') => {
+                self.mode = Mode::Filter;
+                self.prompt = self.filter_text.clone();
+            },
+            KeyCode::Char('c') => self.mode = Mode::Columns,
+            KeyCode::Char('%') if !self.jump_pair() => {
+                self.message = String::from("no stdlog begin/end pair in the rows");
+            },
+            _ => (),
+        }
+        false
+    }
+
+    fn status(&self) -> String {
+        match self.mode {
+            Mode::Search => format!("/{}", self.prompt),
+            Mode::Filter => format!("filter: {}", self.prompt),
+            Mode::Columns => String::from("columns: space show/hide, +/- width, esc done"),
+            Mode::Normal => {
+                let mut status = format!("{}/{}", if self.is_empty() { 0 } else { self.selected() + 1 }, self.len());
+                if !self.filters.is_empty() {
+                    status.push_str(&format!(" of {} | filter: {}", self.logs.len(), self.filter_text));
+                }
+                if self.follow {
+                    status.push_str(" | following");
+                }
+                if self.dropped() > 0 {
+                    status.push_str(&format!(" | {} oldest dropped", self.dropped()));
+                }
+                if !self.message.is_empty() {
+                    status.push_str(&format!(" | {}", self.message));
+                }
+                status.push_str(" | q quit, / search, n/N next, f filter, c columns, enter detail, % begin/end");
+                status
+            },
+        }
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, browser: &mut Browser) {
+    let mut constraints = vec![Constraint::Min(3)];
+    if browser.detail {
+        constraints.push(Constraint::Percentage(40));
+    }
+    constraints.push(Constraint::Length(1));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(f.size());
+
+    // header takes one line
+    browser.scroll(chunks[0].height.saturating_sub(1) as usize);
+    let columns: Vec<&Column> = browser.columns.iter().filter(|x| x.visible).collect();
+    let widths: Vec<Constraint> = columns.iter().map(|x| Constraint::Length(x.width)).collect();
+    let header = Row::new(columns.iter().map(|x| Cell::from(x.name)))
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let end = (browser.offset + browser.height).min(browser.rows.len());
+    let rows = browser.rows.range(browser.offset..end).map(|i| {
+        let log = browser.log(*i);
+        Row::new(columns.iter().map(|column| {
+            let value = column_value(log, column.name).unwrap_or_default().replace('\n', " ");
+            let cell = Cell::from(value);
+            if column.name == "severity" {
+                cell.style(Style::default().fg(severity_color(log)))
+            } else {
+                cell
+            }
+        }))
+    });
+    let table = Table::new(rows)
+        .header(header)
+        .widths(&widths)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default();
+    if !browser.rows.is_empty() {
+        state.select(Some(browser.selected - browser.offset));
+    }
+    f.render_stateful_widget(table, chunks[0], &mut state);
+
+    if browser.detail {
+        let detail = Paragraph::new(browser.detail_lines().join("\n"))
+            .block(Block::default().borders(Borders::TOP).title("detail (d/u scroll)"))
+            .wrap(Wrap { trim: false })
+            .scroll((browser.detail_scroll, 0));
+        f.render_widget(detail, chunks[1]);
+    }
+
+    let status = Paragraph::new(browser.status()).style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(status, chunks[chunks.len() - 1]);
+
+    if browser.mode == Mode::Columns {
+        let area = f.size();
+        let height = (browser.columns.len() as u16 + 2).min(area.height);
+        let width = 36.min(area.width);
+        let popup = Rect::new((area.width - width) / 2, (area.height - height) / 2, width, height);
+        let items: Vec<ListItem> = browser.columns.iter().map(|x| ListItem::new(
+            format!("[{}] {:<16} {:>4}", if x.visible { "x" } else { " " }, x.name, x.width))).collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("columns"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default();
+        state.select(Some(browser.column_cursor));
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, &mut state);
+    }
+}
+
+// Sends lines to the screen, from the reader thread
+struct ChannelWriter {
+    sender: mpsc::SyncSender<Result<LogLine, String>>,
+}
+
+impl LogWriter for ChannelWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        // fails when the screen quit, which stops the reader
+        self.sender.send(Ok(log.clone()))?;
+        Ok(())
+    }
+}
+
+// Reads the inputs in another thread, so a screen starts right away,
+// and in follow mode new lines keep coming. An error ends the lines.
+pub(crate) fn read_logs(
+        inputs: &[String],
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        follow: bool,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        ) -> mpsc::Receiver<Result<LogLine, String>> {
+
+    // the reader waits while the screen is behind
+    let (sender, receiver) = mpsc::sync_channel(BATCH_SIZE);
+    let inputs = inputs.to_vec();
+    let filter = filter.clone();
+    let hostname = hostname.map(|x| x.to_string());
+    let templates = templates.map(|x| x.to_string());
+    let redactor = redactor.cloned();
+    thread::spawn(move || {
+        let writer = Box::new(ChannelWriter { sender: sender.clone() });
+        let result = wrap_writer(writer, templates.as_deref(), redactor.as_ref()).and_then(|mut writer| {
+            for input in &inputs {
+                scrape_log(input, &filter, hostname.as_deref(), follow, &mut writer)?;
+            }
+            writer.close()
+        });
+        if let Err(e) = result {
+            let _ = sender.send(Err(e.to_string()));
+        }
+    });
+    receiver
+}
+
+// Runs a full screen view, and restores the terminal after, even on an error
+pub(crate) fn with_terminal<F>(view: F) -> SResult<()>
+        where F: FnOnce(&mut Terminal<CrosstermBackend<io::Stdout>>) -> SResult<()> {
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let result = view(&mut terminal);
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    result
+}
+
+// Full screen log browser
+pub fn browse_logs(
+        inputs: &[String],
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        follow: bool,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        ) -> SResult<()> {
+
+    let receiver = read_logs(inputs, filter, hostname, follow, templates, redactor);
+    // without --follow the lines are all the inputs, kept to browse all of them
+    let mut browser = Browser::new(follow);
+    if follow {
+        browser = browser.with_max_lines(MAX_LINES);
+    }
+    with_terminal(|terminal| browse_loop(terminal, &mut browser, &receiver))
+}
+
+fn browse_loop<B: Backend>(
+        terminal: &mut Terminal<B>,
+        browser: &mut Browser,
+        receiver: &mpsc::Receiver<Result<LogLine, String>>,
+        ) -> SResult<()> {
+
+    loop {
+        for message in receiver.try_iter().take(BATCH_SIZE) {
+            match message {
+                Ok(log) => browser.push(log),
+                Err(e) => browser.message = e,
+            }
+        }
+        terminal.draw(|f| draw(f, browser))?;
+        if event::poll(Duration::from_millis(200))? {
+            if let Event::Key(key) = event::read()? {
+                if browser.handle_key(key) {
+                    return Ok(())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use chrono::{Duration, NaiveDateTime};
+
+use super::lineparser::LogLine;
+
+
+// seconds of log time a line waits for the stdlog of its call, longer calls are not linked
+pub const MAX_CALL_SECONDS: i64 = 3600;
+// seconds of log time between expiring what a stream keeps, rather than on every line
+const EXPIRE_SECONDS: i64 = 60;
+
+// The server of a line, so a new pid of it is a restart: its input file, or else its --hostname.
+// None if neither is known, as every server would look the same.
+pub fn server_of(log: &LogLine) -> Option<String> {
+    match (&log.logfile, &log.hostname) {
+        (Some(x), _) => Some(x.clone()),
+        (None, Some(x)) if !x.is_empty() => Some(x.clone()),
+        _ => None,
+    }
+}
+
+// Tells when to expire calls that never ended, once a minute of log time
+#[derive(Default)]
+pub struct ExpireTick {
+    latest: Option<NaiveDateTime>,
+    expired: Option<NaiveDateTime>,
+}
+
+impl ExpireTick {
+    // the latest log time, if it is time to expire
+    pub fn tick(&mut self, logtime: NaiveDateTime) -> Option<NaiveDateTime> {
+        let latest = self.latest.map_or(logtime, |x| x.max(logtime));
+        self.latest = Some(latest);
+        match self.expired {
+            Some(x) if latest - x < Duration::seconds(EXPIRE_SECONDS) => None,
+            _ => {
+                self.expired = Some(latest);
+                Some(latest)
+            },
+        }
+    }
+}
+
+// When the call of a stdlog began and ended.
+// total_time is in whole ms, so the call may have begun up to 1 ms before.
+pub fn call_window(log: &LogLine) -> (NaiveDateTime, NaiveDateTime) {
+    let ms = log.total_time.or(log.dur_ms).unwrap_or(0) as i64;
+    (log.logtime - Duration::milliseconds(ms + 1), log.logtime)
+}
+
+struct CallLine<T> {
+    hostname: Option<String>,
+    pid: i32,
+    logtime: NaiveDateTime,
+    value: T,
+}
+
+// The lines logged during a call, which only the stdlog at the end of the call says:
+// a line is of the next stdlog of its hostname and pid whose call_window has it.
+// A line without one after MAX_CALL_SECONDS expires, so --follow keeps a bounded number.
+pub struct CallLines<T> {
+    // in log order
+    lines: Vec<CallLine<T>>,
+    latest: Option<NaiveDateTime>,
+}
+
+impl<T> Default for CallLines<T> {
+    fn default() -> Self {
+        CallLines::new()
+    }
+}
+
+impl<T> CallLines<T> {
+    pub fn new() -> CallLines<T> {
+        CallLines {
+            lines: vec![],
+            latest: None,
+        }
+    }
+
+    fn seen(&mut self, logtime: NaiveDateTime) {
+        self.latest = Some(self.latest.map_or(logtime, |x| x.max(logtime)));
+    }
+
+    pub fn push(&mut self, log: &LogLine, value: T) {
+        self.seen(log.logtime);
+        self.lines.push(CallLine {
+            hostname: log.hostname.clone(),
+            pid: log.pid,
+            logtime: log.logtime,
+            value,
+        });
+    }
+
+    // the latest line of the pid of the log that matches
+    pub fn last_mut<F: Fn(&T) -> bool>(&mut self, log: &LogLine, matches: F) -> Option<&mut T> {
+        self.lines.iter_mut().rev()
+            .find(|x| x.pid == log.pid && x.hostname == log.hostname && matches(&x.value))
+            .map(|x| &mut x.value)
+    }
+
+    // Removes the lines of the call that ended with this stdlog, in log order,
+    // those the linked filter accepts, e.g. of the same queryid.
+    pub fn take<F: Fn(&T) -> bool>(&mut self, log: &LogLine, linked: F) -> Vec<T> {
+        self.seen(log.logtime);
+        let (begin, end) = call_window(log);
+        self.take_where(|x| x.pid == log.pid && x.hostname == log.hostname
+            && x.logtime >= begin && x.logtime <= end && linked(&x.value))
+    }
+
+    // Removes the lines no later stdlog can have, in log order
+    pub fn expire(&mut self) -> Vec<T> {
+        let since = match self.latest {
+            Some(x) => x - Duration::seconds(MAX_CALL_SECONDS),
+            None => return vec![],
+        };
+        self.take_where(|x| x.logtime < since)
+    }
+
+    // Removes all the lines, like at the end of the inputs
+    pub fn take_all(&mut self) -> Vec<T> {
+        self.lines.drain(..).map(|x| x.value).collect()
+    }
+
+    fn take_where<F: Fn(&CallLine<T>) -> bool>(&mut self, taken: F) -> Vec<T> {
+        let (done, lines): (Vec<CallLine<T>>, Vec<CallLine<T>>) = self.lines.drain(..).partition(taken);
+        self.lines = lines;
+        done.into_iter().map(|x| x.value).collect()
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use serde_json::{json, Value};
+
+use super::calls::{ExpireTick, MAX_CALL_SECONDS};
+use super::lineparser::{LogLine, LogWriter, SResult, Severity};
+
+
+// instant events are named by the start of msg
+const NAME_LEN: usize = 80;
+
+// with --follow, the trace is flushed at most this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn unix_micros(time: &NaiveDateTime) -> i64 {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    (*time - epoch).num_microseconds().unwrap_or(0)
+}
+
+fn short_name(msg: &str) -> String {
+    msg.chars().take(NAME_LEN).collect()
+}
+
+// a stdlog_begin without its stdlog yet
+struct Begin {
+    logtime: NaiveDateTime,
+    tid: i32,
+}
+
+// Writes the Trace Event Format read by chrome://tracing and Perfetto,
+// as a JSON array that is valid without the closing bracket, so it can be followed.
+// One process per server pid and one track per threadid.
+pub struct ChromeTraceWriter {
+    writer: Box<dyn Write>,
+    events: u64,
+    // (hostname, pid) -> pid in the trace, unique across hosts
+    processes: HashMap<(String, i32), i32>,
+    threads: HashSet<(i32, i32)>,
+    // (hostname, pid, sequence), until its stdlog or MAX_CALL_SECONDS
+    begins: HashMap<(String, i32, i32), Begin>,
+    expire: ExpireTick,
+    // (pid in the trace, device) -> bytes in slabs
+    slabs: BTreeMap<(i32, String), i64>,
+    // pid in the trace -> (allocation failures, punts to CPU)
+    failures: HashMap<i32, (u64, u64)>,
+    flushed: Instant,
+}
+
+impl ChromeTraceWriter {
+    pub fn new(writer: Box<dyn Write>) -> ChromeTraceWriter {
+        ChromeTraceWriter {
+            writer,
+            events: 0,
+            processes: HashMap::new(),
+            threads: HashSet::new(),
+            begins: HashMap::new(),
+            expire: ExpireTick::default(),
+            slabs: BTreeMap::new(),
+            failures: HashMap::new(),
+            flushed: Instant::now(),
+        }
+    }
+
+    fn emit(&mut self, event: Value) -> SResult<()> {
+        let separator = if self.events == 0 { "[\n" } else { ",\n" };
+        write!(self.writer, "{}{}", separator, event)?;
+        self.events += 1;
+        Ok(())
+    }
+
+    // the pid in the trace, named the first time it is seen
+    fn process(&mut self, log: &LogLine) -> SResult<i32> {
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let key = (hostname.clone(), log.pid);
+        if let Some(pid) = self.processes.get(&key) {
+            return Ok(*pid)
+        }
+        let mut pid = log.pid;
+        while self.processes.values().any(|x| *x == pid) {
+            pid += 1_000_000;
+        }
+        self.processes.insert(key, pid);
+        let name = if hostname.is_empty() {
+            format!("omnisci_server {}", log.pid)
+        } else {
+            format!("omnisci_server {} {}", hostname, log.pid)
+        };
+        self.emit(json!({"ph": "M", "name": "process_name", "pid": pid, "tid": 0, "args": {"name": name}}))?;
+        Ok(pid)
+    }
+
+    fn thread(&mut self, pid: i32, tid: i32) -> SResult<()> {
+        if self.threads.insert((pid, tid)) {
+            self.emit(json!({"ph": "M", "name": "thread_name", "pid": pid, "tid": tid, "args": {"name": format!("thread {}", tid)}}))?;
+            self.emit(json!({"ph": "M", "name": "thread_sort_index", "pid": pid, "tid": tid, "args": {"sort_index": tid}}))?;
+        }
+        Ok(())
+    }
+
+    fn complete(&mut self, log: &LogLine, pid: i32, tid: i32, start: NaiveDateTime, category: &str) -> SResult<()> {
+        self.thread(pid, tid)?;
+        let mut args = serde_json::Map::new();
+        for (key, value) in [
+            ("session", &log.session),
+            ("dbname", &log.dbname),
+            ("username", &log.username),
+            ("client", &log.client),
+            ("query", &log.query),
+            ("dashboardid", &log.dashboardid),
+            ("chartid", &log.chartid),
+        ] {
+            if let Some(value) = value {
+                args.insert(key.to_string(), json!(value));
+            }
+        }
+        for (key, value) in [
+            ("sequence", log.sequence),
+            ("queryid", log.queryid),
+            ("dur_ms", log.dur_ms),
+            ("execution_time", log.execution_time),
+            ("total_time", log.total_time),
+        ] {
+            if let Some(value) = value {
+                args.insert(key.to_string(), json!(value));
+            }
+        }
+        let ts = unix_micros(&start);
+        self.emit(json!({
+            "ph": "X",
+            "name": log.event.clone().unwrap_or_default(),
+            "cat": category,
+            "pid": pid,
+            "tid": tid,
+            "ts": ts,
+            "dur": (unix_micros(&log.logtime) - ts).max(0),
+            "args": args,
+        }))
+    }
+
+    // scope is t for the thread, p for the process, g for all
+    fn instant(&mut self, log: &LogLine, pid: i32, tid: i32, name: &str, category: &str, scope: &str) -> SResult<()> {
+        self.thread(pid, tid)?;
+        self.emit(json!({
+            "ph": "i",
+            "name": name,
+            "cat": category,
+            "s": scope,
+            "pid": pid,
+            "tid": tid,
+            "ts": unix_micros(&log.logtime),
+            "args": {"msg": log.msg, "fileline": log.fileline},
+        }))
+    }
+
+    fn counters(&mut self, log: &LogLine, pid: i32) -> SResult<()> {
+        let ts = unix_micros(&log.logtime);
+        let event = log.event.as_deref().unwrap_or("");
+        if event == "memory_allocation_failed" || event == "memory_punt_to_cpu" {
+            let failures = self.failures.entry(pid).or_insert((0, 0));
+            if event == "memory_allocation_failed" {
+                failures.0 += 1;
+            } else {
+                failures.1 += 1;
+            }
+            let (failed, punts) = *failures;
+            self.emit(json!({"ph": "C", "name": "gpu allocation failures", "pid": pid, "ts": ts,
+                "args": {"failed": failed, "punt_to_cpu": punts}}))?;
+        }
+        // a slab created, see memory.rs
+        if let (true, Some(allocation)) = (event == "memory_slab", &log.memory) {
+            if let Some(bytes) = allocation.bytes {
+                *self.slabs.entry((pid, allocation.device_name())).or_insert(0) += bytes;
+                let args: serde_json::Map<String, Value> = self.slabs.iter()
+                    .filter(|((x, _), _)| *x == pid)
+                    .map(|((_, device), bytes)| (device.to_string(), json!(bytes)))
+                    .collect();
+                self.emit(json!({"ph": "C", "name": "buffer pool slab bytes", "pid": pid, "ts": ts, "args": args}))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LogWriter for ChromeTraceWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let pid = self.process(log)?;
+        let tid = log.threadid.unwrap_or(0);
+        let event = log.event.clone().unwrap_or_default();
+
+        // calls whose stdlog never came
+        if let Some(latest) = self.expire.tick(log.logtime) {
+            let begun = latest - chrono::Duration::seconds(MAX_CALL_SECONDS);
+            self.begins.retain(|_, x| x.logtime >= begun);
+        }
+        if let Some(sequence) = log.sequence {
+            let key = (log.hostname.clone().unwrap_or_default(), log.pid, sequence);
+            if event.ends_with("_begin") {
+                self.begins.insert(key, Begin { logtime: log.logtime, tid });
+            } else {
+                let (start, tid) = match (self.begins.remove(&key), log.dur_ms) {
+                    (Some(begin), _) => (begin.logtime, begin.tid),
+                    (None, Some(dur_ms)) => (log.logtime - chrono::Duration::milliseconds(dur_ms as i64), tid),
+                    (None, None) => (log.logtime, tid),
+                };
+                self.complete(log, pid, tid, start, "stdlog")?;
+            }
+        } else if let (false, Some(ms)) = (event.is_empty(), log.dur_ms.or(log.total_time)) {
+            // sql_parse, read_table_metadata
+            self.complete(log, pid, tid, log.logtime - chrono::Duration::milliseconds(ms as i64), "event")?;
+        }
+
+        match log.severity {
+            Severity::FATAL => self.instant(log, pid, tid, &short_name(&log.msg), "fatal", "p")?,
+            Severity::ERROR => self.instant(log, pid, tid, &short_name(&log.msg), "error", "t")?,
+            _ => (),
+        }
+        // logged at every start of the server, the version is in msg_norm
+        if event == "version" {
+            let name = format!("OmniSci Server {}", log.msg_norm.as_deref().unwrap_or(""));
+            self.instant(log, pid, tid, &name, "start", "g")?;
+        }
+        self.counters(log, pid)?;
+        if self.flushed.elapsed() >= FLUSH_INTERVAL {
+            self.writer.flush()?;
+            self.flushed = Instant::now();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        if self.events == 0 {
+            write!(self.writer, "[")?;
+        }
+        writeln!(self.writer, "\n]")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use serde::Serialize;
+
+use super::calls::server_of;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+// One option of a startup
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigRow {
+    pub start: String,
+    pub hostname: String,
+    pub pid: i32,
+    pub version: String,
+    pub key: String,
+    pub value: String,
+}
+
+// One option that differs between two startups
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigDiffRow {
+    pub key: String,
+    // added, removed or changed
+    pub change: String,
+    pub before: String,
+    pub after: String,
+}
+
+struct Startup {
+    start: NaiveDateTime,
+    hostname: Option<String>,
+    pid: i32,
+    version: Option<String>,
+    // in log order
+    options: Vec<(String, String)>,
+}
+
+impl Startup {
+    fn value(&self, key: &str) -> Option<&str> {
+        self.options.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+// The option and its value of a startup line:
+//   CommandLineOptions.cpp:1328  Max CPU buffer pool slab size 4294967296
+//   CommandLineOptions.cpp:1334  Enable Calcite view optimize true
+//   CommandLineOptions.cpp:1070  Watchdog is set to true
+//   CommandLineOptions.cpp:1337  Allow Local Auth Fallback: disabled
+//   CommandLineOptions.cpp:1061 OmniSci started with data directory at '/omnisci-storage/data'
+//   DBHandler.cpp:338 Started in GPU mode
+// Other lines of CommandLineOptions.cpp, like warnings, are not options.
+pub fn parse_option(fileline: &str, msg: &str) -> Option<(String, String)> {
+    let msg = msg.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if !fileline.starts_with("CommandLineOptions.cpp") {
+        if msg.starts_with("Started in ") && msg.ends_with(" mode") {
+            return Some((String::from("Started in"), msg[11..].to_string()))
+        }
+        return None
+    }
+    if let Some(i) = msg.find(" is set to ") {
+        return Some((msg[..i].to_string(), msg[i + 11..].to_string()))
+    }
+    if let Some(i) = msg.find(": ") {
+        return Some((msg[..i].to_string(), msg[i + 2..].to_string()))
+    }
+    if msg.ends_with('\'') {
+        if let Some(i) = msg[..msg.len() - 1].rfind('\'') {
+            return Some((msg[..i].trim_end().to_string(), msg[i + 1..msg.len() - 1].to_string()))
+        }
+    }
+    let i = msg.rfind(' ')?;
+    let value = &msg[i + 1..];
+    if matches!(value.parse::<f64>(), Ok(x) if x.is_finite()) || value == "true" || value == "false" {
+        return Some((msg[..i].to_string(), value.to_string()))
+    }
+    None
+}
+
+enum Setting {
+    Version(String),
+    // key, value, and if of CommandLineOptions.cpp, which is logged before the version
+    Option(String, String, bool),
+}
+
+// a server's settings in log order
+type Settings = Vec<(NaiveDateTime, Setting)>;
+
+// The options each server start logs, and its version.
+// A startup of a server and pid is its options up to its version, and the options after it
+// until the next CommandLineOptions.cpp line, as a restarted container often has the same pid.
+// A server that logs no version, like with --filter, starts again when an option is logged again.
+// With ReportOptions::startups, writes the options of one startup, or the difference of two.
+pub struct ConfigWriter {
+    options: ReportOptions,
+    // (server, pid) -> its hostname and settings, split into startups at the end
+    settings: HashMap<(Option<String>, i32), (Option<String>, Settings)>,
+}
+
+impl ConfigWriter {
+    pub fn new(options: ReportOptions) -> ConfigWriter {
+        ConfigWriter {
+            options,
+            settings: HashMap::new(),
+        }
+    }
+
+    // in order of start
+    fn startups(&self) -> Vec<Startup> {
+        let mut startups: Vec<Startup> = vec![];
+        for ((_, pid), (hostname, settings)) in &self.settings {
+            let versioned = settings.iter().any(|(_, x)| matches!(x, Setting::Version(_)));
+            let first = startups.len();
+            for (logtime, setting) in settings {
+                let new = match (startups[first..].last(), setting) {
+                    (None, _) => true,
+                    (Some(x), Setting::Version(_)) => x.version.is_some(),
+                    (Some(x), Setting::Option(_, _, command_line)) if versioned => *command_line && x.version.is_some(),
+                    (Some(x), Setting::Option(key, _, _)) => x.value(key).is_some(),
+                };
+                if new {
+                    startups.push(Startup {
+                        start: *logtime,
+                        hostname: hostname.clone(),
+                        pid: *pid,
+                        version: None,
+                        options: vec![],
+                    });
+                }
+                if let Some(startup) = startups.last_mut() {
+                    match setting {
+                        Setting::Version(x) => startup.version = Some(x.to_string()),
+                        Setting::Option(key, value, _) => startup.options.push((key.to_string(), value.to_string())),
+                    }
+                }
+            }
+        }
+        startups.sort_by(|a, b| a.start.cmp(&b.start).then(a.hostname.cmp(&b.hostname)).then(a.pid.cmp(&b.pid)));
+        startups
+    }
+
+    // 1 is the first startup, -1 the last
+    fn select(startups: &[Startup], n: i64) -> SResult<&Startup> {
+        let index = if n < 0 { startups.len() as i64 + n } else { n - 1 };
+        match startups.get(index as usize) {
+            Some(x) if index >= 0 => Ok(x),
+            _ => Err(format!("No startup {}, the logs have {}", n, startups.len()).into()),
+        }
+    }
+
+    // the options of all startups, or of the startup n
+    pub fn rows(&self, n: Option<i64>) -> SResult<Vec<ConfigRow>> {
+        let all = self.startups();
+        let startups = match n {
+            None => all.iter().collect(),
+            Some(n) => vec![ConfigWriter::select(&all, n)?],
+        };
+        let mut rows = vec![];
+        for startup in startups {
+            for (key, value) in &startup.options {
+                rows.push(ConfigRow {
+                    start: startup.start.format(TIME_FORMAT).to_string(),
+                    hostname: startup.hostname.clone().unwrap_or_default(),
+                    pid: startup.pid,
+                    version: startup.version.clone().unwrap_or_default(),
+                    key: key.to_string(),
+                    value: value.to_string(),
+                });
+            }
+        }
+        Ok(rows)
+    }
+
+    // the options that differ from the startup a to the startup b
+    pub fn diff(&self, a: i64, b: i64) -> SResult<Vec<ConfigDiffRow>> {
+        let startups = self.startups();
+        let (before, after) = (ConfigWriter::select(&startups, a)?, ConfigWriter::select(&startups, b)?);
+        let mut rows = vec![];
+        let mut compare = |key: &str, a: Option<&str>, b: Option<&str>| {
+            let change = match (a, b) {
+                (None, Some(_)) => "added",
+                (Some(_), None) => "removed",
+                (Some(a), Some(b)) if a != b => "changed",
+                _ => return,
+            };
+            rows.push(ConfigDiffRow {
+                key: key.to_string(),
+                change: change.to_string(),
+                before: a.unwrap_or("").to_string(),
+                after: b.unwrap_or("").to_string(),
+            });
+        };
+        compare("version", before.version.as_deref(), after.version.as_deref());
+        for (key, value) in &before.options {
+            compare(key, Some(value), after.value(key));
+        }
+        for (key, value) in &after.options {
+            if before.value(key).is_none() {
+                compare(key, None, Some(value));
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl LogWriter for ConfigWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let setting = if let (Some("version"), Some(version)) = (log.event.as_deref(), &log.msg_norm) {
+            Setting::Version(version.to_string())
+        } else if let Some((key, value)) = parse_option(&log.fileline, &log.msg) {
+            Setting::Option(key, value, log.fileline.starts_with("CommandLineOptions.cpp"))
+        } else {
+            return Ok(())
+        };
+        let (_, settings) = self.settings.entry((server_of(log), log.pid)).or_insert((log.hostname.clone(), vec![]));
+        settings.push((log.logtime, setting));
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        match self.options.startups[..] {
+            [] => write_report(&self.rows(None)?, &self.options),
+            [n] => write_report(&self.rows(Some(n))?, &self.options),
+            [a, b] => write_report(&self.diff(a, b)?, &self.options),
+            _ => Err(format!("Expected one startup, or two to compare: {:?}", self.options.startups).into()),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::fingerprint::{fingerprint, fingerprint_hash};
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, Latency, ReportOptions};
+
+
+// One row of the digest report, for all the queries with the same fingerprint.
+#[derive(Serialize, Debug, Clone)]
+pub struct DigestRow {
+    pub fingerprint_hash: String,
+    pub count: u64,
+    pub exec_sum: i64,
+    pub exec_avg: f64,
+    pub exec_p50: u64,
+    pub exec_p95: u64,
+    pub exec_p99: u64,
+    pub exec_max: u64,
+    pub total_sum: i64,
+    pub total_avg: f64,
+    pub total_p50: u64,
+    pub total_p95: u64,
+    pub total_p99: u64,
+    pub total_max: u64,
+    pub users: String,
+    pub dbnames: String,
+    pub dashboards: String,
+    pub fingerprint: String,
+    // the slowest query seen, by total_time
+    pub sample: String,
+}
+
+struct DigestEntry {
+    fingerprint: String,
+    count: u64,
+    execution_time: Latency,
+    total_time: Latency,
+    users: BTreeSet<String>,
+    dbnames: BTreeSet<String>,
+    dashboards: BTreeSet<String>,
+    sample: String,
+    sample_time: Option<i32>,
+}
+
+impl DigestEntry {
+    fn new(fingerprint: String, sample: &str) -> DigestEntry {
+        DigestEntry {
+            fingerprint,
+            count: 0,
+            execution_time: Latency::new(),
+            total_time: Latency::new(),
+            users: BTreeSet::new(),
+            dbnames: BTreeSet::new(),
+            dashboards: BTreeSet::new(),
+            sample: sample.to_string(),
+            sample_time: None,
+        }
+    }
+
+    fn add(&mut self, log: &LogLine, query: &str) {
+        self.count += 1;
+        if let Some(ms) = log.execution_time {
+            self.execution_time.record(ms);
+        }
+        if let Some(ms) = log.total_time {
+            self.total_time.record(ms);
+            let slower = match self.sample_time {
+                None => true,
+                Some(x) => ms > x,
+            };
+            if slower {
+                self.sample = query.to_string();
+                self.sample_time = Some(ms);
+            }
+        }
+        for (set, val) in [
+            (&mut self.users, &log.username),
+            (&mut self.dbnames, &log.dbname),
+            (&mut self.dashboards, &log.dashboardid),
+        ] {
+            if let Some(x) = val {
+                set.insert(x.to_string());
+            }
+        }
+    }
+
+    fn row(&self, hash: &str) -> DigestRow {
+        let join = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<String>>().join(",");
+        DigestRow {
+            fingerprint_hash: hash.to_string(),
+            count: self.count,
+            exec_sum: self.execution_time.total(),
+            exec_avg: self.execution_time.mean(),
+            exec_p50: self.execution_time.quantile(0.50),
+            exec_p95: self.execution_time.quantile(0.95),
+            exec_p99: self.execution_time.quantile(0.99),
+            exec_max: self.execution_time.max(),
+            total_sum: self.total_time.total(),
+            total_avg: self.total_time.mean(),
+            total_p50: self.total_time.quantile(0.50),
+            total_p95: self.total_time.quantile(0.95),
+            total_p99: self.total_time.quantile(0.99),
+            total_max: self.total_time.max(),
+            users: join(&self.users),
+            dbnames: join(&self.dbnames),
+            dashboards: join(&self.dashboards),
+            fingerprint: self.fingerprint.to_string(),
+            sample: self.sample.to_string(),
+        }
+    }
+}
+
+// Groups completed sql_execute calls by query fingerprint, like pt-query-digest.
+pub struct DigestWriter {
+    options: ReportOptions,
+    entries: HashMap<String, DigestEntry>,
+}
+
+impl DigestWriter {
+    pub fn new(options: ReportOptions) -> DigestWriter {
+        DigestWriter {
+            options,
+            entries: HashMap::new(),
+        }
+    }
+
+    // sorted by the sum of total_time, so the queries costing the most are first
+    pub fn rows(&self) -> Vec<DigestRow> {
+        let mut rows: Vec<DigestRow> = self.entries.iter().map(|(hash, entry)| entry.row(hash)).collect();
+        rows.sort_by(|a, b| b.total_sum.cmp(&a.total_sum)
+            .then(b.count.cmp(&a.count))
+            .then(a.fingerprint.cmp(&b.fingerprint)));
+        rows
+    }
+}
+
+impl LogWriter for DigestWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        match (&log.event, &log.query) {
+            (Some(event), Some(query)) if event == "sql_execute" => {
+                let norm = fingerprint(query);
+                // the fingerprint column was hashed before the query was truncated, so prefer it
+                let hash = match &log.fingerprint {
+                    Some(hash) => hash.to_string(),
+                    None => fingerprint_hash(&norm),
+                };
+                self.entries.entry(hash)
+                    .or_insert_with(|| DigestEntry::new(norm, query))
+                    .add(log, query);
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
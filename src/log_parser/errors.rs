@@ -0,0 +1,199 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+
+use chrono::NaiveDateTime;
+
+use serde::{Deserialize, Serialize};
+
+use super::lineparser::{LogLine, LogWriter, SResult, Severity};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+// One row of the errors report, for all the messages that normalize the same.
+#[derive(Serialize, Debug, Clone)]
+pub struct ErrorsRow {
+    // only when there is a baseline, true if the error is not in the baseline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<bool>,
+    pub severity: String,
+    pub fileline: String,
+    pub msg_norm: String,
+    pub count: u64,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub users: String,
+    pub sessions: String,
+    pub event: String,
+    pub msg: String,
+}
+
+// The columns that identify an error, read from a previous report
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+struct ErrorKey {
+    severity: String,
+    fileline: String,
+    msg_norm: String,
+}
+
+struct ErrorsEntry {
+    severity: Severity,
+    count: u64,
+    first_seen: NaiveDateTime,
+    last_seen: NaiveDateTime,
+    users: BTreeSet<String>,
+    sessions: BTreeSet<String>,
+    event: Option<String>,
+    msg: String,
+}
+
+// Order of severities in the report, worst first
+fn severity_rank(severity: &Severity) -> Option<u8> {
+    match severity {
+        Severity::FATAL => Some(0),
+        Severity::ERROR => Some(1),
+        Severity::AUTH => Some(2),
+        Severity::WARNING => Some(3),
+        Severity::INPUT => Some(4),
+        _ => None,
+    }
+}
+
+// Reads the key columns of a previous errors report, written with --format csv or json
+fn read_baseline(path: &str) -> SResult<HashSet<ErrorKey>> {
+    let data = fs::read_to_string(path)?;
+    let mut keys = HashSet::new();
+    if data.trim_start().starts_with('{') {
+        for line in data.lines().filter(|x| !x.trim().is_empty()) {
+            keys.insert(serde_json::from_str::<ErrorKey>(line)?);
+        }
+    } else {
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        for key in reader.deserialize() {
+            keys.insert(key?);
+        }
+    }
+    Ok(keys)
+}
+
+// Groups ERROR, FATAL, WARNING, INPUT and AUTH lines by (severity, fileline, msg_norm).
+// Most error lines do not have the user or session, so those are taken from
+// the last stdlog call on the same thread.
+pub struct ErrorsWriter {
+    options: ReportOptions,
+    baseline: Option<HashSet<ErrorKey>>,
+    entries: HashMap<ErrorKey, ErrorsEntry>,
+    // (pid, threadid) -> (username, session)
+    threads: HashMap<(i32, i32), (Option<String>, Option<String>)>,
+}
+
+impl ErrorsWriter {
+    pub fn new(options: ReportOptions) -> SResult<ErrorsWriter> {
+        let baseline = match &options.baseline {
+            None => None,
+            Some(path) => Some(read_baseline(path)?),
+        };
+        Ok(ErrorsWriter {
+            options,
+            baseline,
+            entries: HashMap::new(),
+            threads: HashMap::new(),
+        })
+    }
+
+    pub fn rows(&self) -> Vec<ErrorsRow> {
+        let join = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<String>>().join(",");
+        let mut entries: Vec<(&ErrorKey, &ErrorsEntry)> = self.entries.iter().collect();
+        entries.sort_by(|(ka, a), (kb, b)| severity_rank(&a.severity).cmp(&severity_rank(&b.severity))
+            .then(b.count.cmp(&a.count))
+            .then(ka.fileline.cmp(&kb.fileline))
+            .then(ka.msg_norm.cmp(&kb.msg_norm)));
+        entries.iter().map(|(key, entry)| ErrorsRow {
+            new: self.baseline.as_ref().map(|baseline| !baseline.contains(key)),
+            severity: key.severity.to_string(),
+            fileline: key.fileline.to_string(),
+            msg_norm: key.msg_norm.to_string(),
+            count: entry.count,
+            first_seen: entry.first_seen.format(TIME_FORMAT).to_string(),
+            last_seen: entry.last_seen.format(TIME_FORMAT).to_string(),
+            users: join(&entry.users),
+            sessions: join(&entry.sessions),
+            event: entry.event.clone().unwrap_or_default(),
+            msg: entry.msg.to_string(),
+        }).collect()
+    }
+}
+
+impl LogWriter for ErrorsWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let thread = log.threadid.map(|x| (log.pid, x));
+        if log.session.is_some() {
+            if let Some(thread) = thread {
+                self.threads.insert(thread, (log.username.clone(), log.session.clone()));
+            }
+        }
+
+        if severity_rank(&log.severity).is_none() {
+            return Ok(())
+        }
+        // connect and disconnect calls are AUTH, but they are not errors
+        if let (Severity::AUTH, Some(_)) = (&log.severity, log.sequence) {
+            return Ok(())
+        }
+
+        let (username, session) = match (&log.username, &log.session, thread) {
+            (None, None, Some(thread)) => match self.threads.get(&thread) {
+                Some((username, session)) => (username.clone(), session.clone()),
+                None => (None, None),
+            },
+            _ => (log.username.clone(), log.session.clone()),
+        };
+
+        let key = ErrorKey {
+            severity: log.severity.to_string(),
+            fileline: log.fileline.to_string(),
+            msg_norm: log.msg_norm.clone().unwrap_or_default(),
+        };
+        let entry = self.entries.entry(key).or_insert_with(|| ErrorsEntry {
+            severity: log.severity.clone(),
+            count: 0,
+            first_seen: log.logtime,
+            last_seen: log.logtime,
+            users: BTreeSet::new(),
+            sessions: BTreeSet::new(),
+            event: log.event.clone(),
+            msg: log.msg.to_string(),
+        });
+        entry.count += 1;
+        entry.first_seen = entry.first_seen.min(log.logtime);
+        entry.last_seen = entry.last_seen.max(log.logtime);
+        if let Some(x) = username {
+            entry.users.insert(x);
+        }
+        if let Some(x) = session {
+            entry.sessions.insert(x);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
@@ -0,0 +1,126 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Write;
+use std::io::{Error, ErrorKind};
+
+use chrono::format::{Item, StrftimeItems};
+
+use serde_json::{json, Value};
+
+use sha2::{Digest, Sha256};
+
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::ISO_TIME_FORMAT;
+
+
+// strftime of the logtime, so an index per day
+pub const DEFAULT_INDEX: &str = "omnisci-logs-%Y.%m.%d";
+
+const KEYWORDS: [&str; 16] = ["severity", "fileline", "event", "session", "dbname", "username", "operation", "client",
+    "hostname", "logfile", "msg_norm", "dashboardid", "chartid", "fingerprint", "name_values", "template_params"];
+const INTEGERS: [&str; 9] = ["pid", "threadid", "sequence", "dur_ms", "execution_time", "total_time", "queryid", "template_id",
+    "verbosity"];
+const TEXTS: [&str; 7] = ["msg", "query", "timers", "qmd", "plan", "memory", "vega"];
+
+// The same line always has the same _id, so loading a file twice does not duplicate it.
+pub fn doc_id(log: &LogLine) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        log.hostname.as_deref().unwrap_or(""),
+        &log.logtime.format(ISO_TIME_FORMAT).to_string(),
+        &log.pid.to_string(),
+        &log.threadid.map(|x| x.to_string()).unwrap_or_default(),
+        &log.fileline,
+        &log.msg,
+        log.query.as_deref().unwrap_or(""),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update(b"\x1f");
+    }
+    hasher.finalize()[..16].iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+// the index names of a pattern, like omnisci-logs-* for omnisci-logs-%Y.%m.%d
+fn index_wildcard(pattern: &str) -> String {
+    match pattern.find('%') {
+        Some(i) => format!("{}*", &pattern[..i]),
+        None => pattern.to_string(),
+    }.to_lowercase()
+}
+
+// A composable index template, PUT to _index_template/omnisci-logs
+pub fn index_template(pattern: &str) -> Value {
+    let mut properties = serde_json::Map::new();
+    properties.insert(String::from("logtime"), json!({"type": "date_nanos"}));
+    for name in KEYWORDS.iter() {
+        properties.insert(name.to_string(), json!({"type": "keyword", "ignore_above": 1024}));
+    }
+    for name in INTEGERS.iter() {
+        properties.insert(name.to_string(), json!({"type": "integer"}));
+    }
+    for name in TEXTS.iter() {
+        properties.insert(name.to_string(), json!({"type": "text"}));
+    }
+    json!({
+        "index_patterns": [index_wildcard(pattern)],
+        "template": {
+            "mappings": {
+                "dynamic": false,
+                "properties": properties,
+            },
+        },
+    })
+}
+
+// The NDJSON body of Elasticsearch and OpenSearch _bulk requests, an index action per line.
+pub struct EsBulkWriter {
+    writer: Box<dyn Write>,
+    index: String,
+}
+
+impl EsBulkWriter {
+    pub fn new(writer: Box<dyn Write>, index: &str) -> SResult<EsBulkWriter> {
+        if StrftimeItems::new(index).any(|x| x == Item::Error) {
+            return Err(Box::new(Error::new(ErrorKind::InvalidInput, format!("Invalid index pattern: {}", index))))
+        }
+        Ok(EsBulkWriter {
+            writer,
+            index: index.to_string(),
+        })
+    }
+}
+
+impl LogWriter for EsBulkWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        // index names are lowercase, like oct of %b
+        let index = log.logtime.format(&self.index).to_string().to_lowercase();
+        let action = json!({"index": {"_index": index, "_id": doc_id(log)}});
+        let mut doc = serde_json::to_value(log)?;
+        // ISO 8601, the default date format of the mapping
+        doc["logtime"] = json!(log.logtime.format(ISO_TIME_FORMAT).to_string());
+        match writeln!(self.writer, "{}\n{}", action, doc) {
+            // assumes the user quit the output early, like LineWriter
+            Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+            x => Ok(x?),
+        }
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use lazy_static::lazy_static;
+
+use regex;
+
+
+// Normalize a query so that queries which differ only by literals, IN-lists,
+// comments, case or whitespace end up with the same text, like pt-query-digest.
+//   SELECT * FROM t WHERE a = 'x' AND b IN (1, 2, 3) LIMIT 10;
+//   select * from t where a = ? and b in (?+) limit ?
+pub fn fingerprint(query: &str) -> String {
+    lazy_static! {
+        static ref RE_LINE_COMMENT: regex::Regex = regex::Regex::new(r"--[^\n]*").unwrap();
+        static ref RE_BLOCK_COMMENT: regex::Regex = regex::Regex::new(r"(?s)/\*.*?\*/").unwrap();
+        static ref RE_SINGLEQUOTED: regex::Regex = regex::Regex::new(r"'(?:[^']|'')*'").unwrap();
+        static ref RE_NUMBERS: regex::Regex = regex::Regex::new(r"\b\d+(?:\.\d+)?(?:[eE][-+]?\d+)?\b").unwrap();
+        static ref RE_NEGATIVE: regex::Regex = regex::Regex::new(r"(?P<op>[=<>(,]\s*)-\s*\?").unwrap();
+        static ref RE_WHITESPACE: regex::Regex = regex::Regex::new(r"\s+").unwrap();
+        static ref RE_IN_LIST: regex::Regex = regex::Regex::new(r"\bin\s*\(\s*\?(?:\s*,\s*\?)*\s*\)").unwrap();
+        static ref RE_VALUES: regex::Regex = regex::Regex::new(r"\bvalues\s*\([^)]*\)(?:\s*,\s*\([^)]*\))*").unwrap();
+    }
+
+    let norm = RE_LINE_COMMENT.replace_all(query, "");
+    let norm = RE_BLOCK_COMMENT.replace_all(&norm, "");
+    let norm = RE_SINGLEQUOTED.replace_all(&norm, "?");
+    let norm = RE_NUMBERS.replace_all(&norm, "?");
+    let norm = RE_NEGATIVE.replace_all(&norm, "$op?");
+    let norm = RE_WHITESPACE.replace_all(&norm, " ");
+    let norm = norm.to_lowercase();
+    let norm = RE_IN_LIST.replace_all(&norm, "in (?+)");
+    let norm = RE_VALUES.replace_all(&norm, "values (?+)");
+    norm.trim().trim_end_matches(';').trim_end().to_string()
+}
+
+// 64 bit FNV-1a, written as hex. Unlike std's DefaultHasher, the result is
+// stable across rust versions, so it can be stored in tables and compared later.
+pub fn fingerprint_hash(fingerprint: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in fingerprint.as_bytes() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error as StdError;
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use super::lineparser::SResult;
+
+
+// lines per batch
+const BATCH_SIZE: usize = 1000;
+// lines waiting for the sender thread, reading the logs waits when it is full, e.g. while the sink is down
+const QUEUE_SIZE: usize = 10 * BATCH_SIZE;
+// a batch is sent at least this often, also while a followed log is quiet
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+// a failed batch is sent again until it succeeds, waiting twice as long each time up to MAX_BACKOFF,
+// so a --follow run outlives an outage of the sink, while the full queue holds up reading the logs
+const FIRST_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// nanoseconds since the epoch, as a string like Loki and OTLP JSON take
+pub(crate) fn unix_nanos(time: &NaiveDateTime) -> String {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    (*time - epoch).num_nanoseconds().unwrap_or(0).to_string()
+}
+
+// An error that sending again cannot fix, like a request the sink rejects or a bad URL
+pub(crate) fn rejected(message: String) -> Box<dyn StdError> {
+    Box::new(Error::new(ErrorKind::InvalidInput, message))
+}
+
+fn is_rejected(e: &(dyn StdError + 'static)) -> bool {
+    matches!(e.downcast_ref::<Error>(), Some(x) if x.kind() == ErrorKind::InvalidInput)
+}
+
+// Sends until it succeeds, only a rejected batch is an error
+pub(crate) fn with_backoff<F>(mut send: F) -> SResult<()>
+        where F: FnMut() -> SResult<()> {
+
+    let mut backoff = FIRST_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match send() {
+            Ok(()) => return Ok(()),
+            Err(e) if is_rejected(e.as_ref()) => return Err(e),
+            Err(e) => eprintln!("attempt {} failed, retrying in {:?}: {}", attempt, backoff, e),
+        }
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempt += 1;
+    }
+}
+
+// Batches items in another thread, so sending and retries do not hold up reading the logs,
+// and a batch is sent after FLUSH_INTERVAL even if no more lines come.
+// A batch that failed is sent again whole, so delivery is at least once.
+pub(crate) struct Forwarder<T> {
+    sender: Option<mpsc::SyncSender<T>>,
+    thread: Option<thread::JoinHandle<Result<(), String>>>,
+}
+
+impl<T: Send + 'static> Forwarder<T> {
+    pub(crate) fn new<F>(mut send: F) -> Forwarder<T>
+            where F: FnMut(&[T]) -> SResult<()> + Send + 'static {
+
+        let (sender, receiver) = mpsc::sync_channel::<T>(QUEUE_SIZE);
+        let thread = thread::spawn(move || {
+            let mut batch: Vec<T> = vec![];
+            let mut flushed = Instant::now();
+            loop {
+                let timeout = FLUSH_INTERVAL.checked_sub(flushed.elapsed()).unwrap_or_default();
+                let done = match receiver.recv_timeout(timeout) {
+                    Ok(item) => {
+                        batch.push(item);
+                        false
+                    },
+                    Err(mpsc::RecvTimeoutError::Timeout) => false,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => true,
+                };
+                if done || batch.len() >= BATCH_SIZE || flushed.elapsed() >= FLUSH_INTERVAL {
+                    if !batch.is_empty() {
+                        with_backoff(|| send(&batch)).map_err(|e| e.to_string())?;
+                        batch.clear();
+                    }
+                    flushed = Instant::now();
+                }
+                if done {
+                    return Ok(())
+                }
+            }
+        });
+        Forwarder {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    // the error that stopped the sender thread, a rejected batch
+    fn join(&mut self) -> SResult<()> {
+        self.sender = None;
+        match self.thread.take().map(|x| x.join()) {
+            None | Some(Ok(Ok(()))) => Ok(()),
+            Some(Ok(Err(e))) => Err(e.into()),
+            Some(Err(_)) => panic!("forwarder thread panicked"),
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: T) -> SResult<()> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(item).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(())
+        }
+        self.join()?;
+        Err(Box::new(Error::new(ErrorKind::BrokenPipe, "forwarder is closed")))
+    }
+
+    // sends what is left, and waits for it
+    pub(crate) fn close(&mut self) -> SResult<()> {
+        self.join()
+    }
+}
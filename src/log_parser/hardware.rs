@@ -0,0 +1,277 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::calls::server_of;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+// One row of the hardware report, the host of a startup or one of its GPUs.
+// The slab and pool sizes are of the CPU in the host row, and of the GPU in a GPU row.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct HardwareRow {
+    pub start: String,
+    pub hostname: String,
+    pub pid: i32,
+    pub version: String,
+    // host or gpu
+    pub device: String,
+    pub id: Option<i32>,
+    // host: GPU or CPU mode, and the number of GPUs
+    pub mode: String,
+    pub gpus: Option<i32>,
+    pub system_memory_mb: Option<i64>,
+    pub reserved_gpu_memory_mb: Option<f64>,
+    pub egl_version: String,
+    pub opengl_version: String,
+    // gpu
+    pub uuid: String,
+    pub compute_capability: String,
+    pub clock_khz: Option<i64>,
+    pub global_memory_gb: Option<f64>,
+    pub memory_clock_khz: Option<i64>,
+    pub memory_bandwidth_gbs: Option<f64>,
+    pub memory_bus_width: Option<i32>,
+    pub mps: Option<i32>,
+    pub pci_bus_id: Option<i32>,
+    // an EGL device for rendering
+    pub render: Option<bool>,
+    // both
+    pub min_slab_mb: Option<i64>,
+    pub max_slab_mb: Option<i64>,
+    pub memory_pool_mb: Option<i64>,
+}
+
+struct Inventory {
+    start: NaiveDateTime,
+    version: Option<String>,
+    host: HardwareRow,
+    gpus: Vec<HardwareRow>,
+}
+
+impl Inventory {
+    fn gpu(&mut self, id: i32) -> &mut HardwareRow {
+        let index = match self.gpus.iter().position(|x| x.id == Some(id)) {
+            Some(i) => i,
+            None => {
+                self.gpus.push(HardwareRow {
+                    device: String::from("gpu"),
+                    id: Some(id),
+                    ..Default::default()
+                });
+                self.gpus.len() - 1
+            },
+        };
+        &mut self.gpus[index]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.gpus.is_empty() && self.host.gpus.is_none() && self.host.system_memory_mb.is_none()
+    }
+}
+
+// The GPUs, memory and render devices each server start logs, by CudaMgr.cpp, DataMgr.cpp and EglPlatform.cpp.
+// The CudaMgr.cpp device lines are DEBUG1.
+// A startup is the lines of a server and pid, until the GPUs or the system memory are logged again.
+pub struct HardwareWriter {
+    options: ReportOptions,
+    inventories: Vec<Inventory>,
+    // (server, pid) -> index of its last inventory
+    current: HashMap<(Option<String>, i32), usize>,
+}
+
+impl HardwareWriter {
+    pub fn new(options: ReportOptions) -> HardwareWriter {
+        HardwareWriter {
+            options,
+            inventories: vec![],
+            current: HashMap::new(),
+        }
+    }
+
+    fn inventory(&mut self, log: &LogLine, new: bool) -> &mut Inventory {
+        let key = (server_of(log), log.pid);
+        let index = match self.current.get(&key) {
+            Some(i) if !new => *i,
+            _ => {
+                self.inventories.push(Inventory {
+                    start: log.logtime,
+                    version: None,
+                    host: HardwareRow {
+                        hostname: log.hostname.clone().unwrap_or_default(),
+                        pid: log.pid,
+                        device: String::from("host"),
+                        ..Default::default()
+                    },
+                    gpus: vec![],
+                });
+                self.current.insert(key, self.inventories.len() - 1);
+                self.inventories.len() - 1
+            },
+        };
+        &mut self.inventories[index]
+    }
+
+    fn has(&self, log: &LogLine, f: &dyn Fn(&Inventory) -> bool) -> bool {
+        match self.current.get(&(server_of(log), log.pid)) {
+            Some(i) => f(&self.inventories[*i]),
+            None => false,
+        }
+    }
+
+    // the host and then its GPUs, of each startup in order
+    pub fn rows(&self) -> Vec<HardwareRow> {
+        let mut inventories: Vec<&Inventory> = self.inventories.iter().filter(|x| !x.is_empty()).collect();
+        inventories.sort_by_key(|x| x.start);
+        let mut rows = vec![];
+        for inventory in inventories {
+            let mut gpus: Vec<&HardwareRow> = inventory.gpus.iter().collect();
+            gpus.sort_by_key(|x| x.id);
+            for row in std::iter::once(&inventory.host).chain(gpus) {
+                let mut row = row.clone();
+                row.start = inventory.start.format(TIME_FORMAT).to_string();
+                row.hostname = inventory.host.hostname.clone();
+                row.pid = inventory.host.pid;
+                row.version = inventory.version.clone().unwrap_or_default();
+                rows.push(row);
+            }
+        }
+        rows
+    }
+
+    fn cuda(&mut self, log: &LogLine, msg: &str) {
+        lazy_static! {
+            static ref RE_GPUS: regex::Regex = regex::Regex::new(r"^Using ([0-9]+) Gpus").unwrap();
+        }
+        if let Some(caps) = RE_GPUS.captures(msg) {
+            let new = self.has(log, &|x| x.host.gpus.is_some());
+            self.inventory(log, new).host.gpus = caps[1].parse().ok();
+            return
+        }
+        let (key, value) = match msg.find(": ") {
+            Some(i) => (&msg[..i], msg[i + 2..].trim()),
+            None => return,
+        };
+        let first = value.split_whitespace().next().unwrap_or("");
+        let inventory = self.inventory(log, false);
+        if key == "Device" {
+            if let Ok(id) = value.parse() {
+                inventory.gpu(id);
+            }
+            return
+        }
+        let gpu = match inventory.gpus.last_mut() {
+            Some(x) => x,
+            None => return,
+        };
+        match key {
+            "UUID" => gpu.uuid = value.to_string(),
+            "Clock (khz)" => gpu.clock_khz = value.parse().ok(),
+            // the major is logged first
+            "Compute Major" => gpu.compute_capability = value.to_string(),
+            "Compute Minor" => gpu.compute_capability = format!("{}.{}", gpu.compute_capability, value),
+            "PCI bus id" => gpu.pci_bus_id = value.parse().ok(),
+            "Per device global memory" => gpu.global_memory_gb = first.parse().ok(),
+            "Memory clock (khz)" => gpu.memory_clock_khz = value.parse().ok(),
+            "Memory bandwidth" => gpu.memory_bandwidth_gbs = first.parse().ok(),
+            "Number of MPs" => gpu.mps = value.parse().ok(),
+            "Memory bus width in bits" => gpu.memory_bus_width = value.parse().ok(),
+            _ => (),
+        }
+    }
+
+    fn data(&mut self, log: &LogLine, msg: &str) {
+        lazy_static! {
+            static ref RE_SYSTEM: regex::Regex = regex::Regex::new(
+                r"^Detected ([0-9]+)M of total system memory").unwrap();
+            static ref RE_SLAB: regex::Regex = regex::Regex::new(
+                r"^(Min|Max) (CPU|GPU) Slab [Ss]ize (?:for GPU ([0-9]+) )?is ([0-9]+)MB").unwrap();
+            static ref RE_POOL: regex::Regex = regex::Regex::new(
+                r"^Max memory pool size for (?:CPU|GPU ([0-9]+)) is ([0-9]+)MB").unwrap();
+            static ref RE_RESERVED: regex::Regex = regex::Regex::new(
+                r"^Reserved GPU memory is ([0-9.]+)MB").unwrap();
+        }
+        if let Some(caps) = RE_SYSTEM.captures(msg) {
+            let new = self.has(log, &|x| x.host.system_memory_mb.is_some());
+            self.inventory(log, new).host.system_memory_mb = caps[1].parse().ok();
+        } else if let Some(caps) = RE_SLAB.captures(msg) {
+            let inventory = self.inventory(log, false);
+            let row = match caps.get(3).and_then(|x| x.as_str().parse().ok()) {
+                Some(id) => inventory.gpu(id),
+                None => &mut inventory.host,
+            };
+            match &caps[1] {
+                "Min" => row.min_slab_mb = caps[4].parse().ok(),
+                _ => row.max_slab_mb = caps[4].parse().ok(),
+            }
+        } else if let Some(caps) = RE_POOL.captures(msg) {
+            let inventory = self.inventory(log, false);
+            let row = match caps.get(1).and_then(|x| x.as_str().parse().ok()) {
+                Some(id) => inventory.gpu(id),
+                None => &mut inventory.host,
+            };
+            row.memory_pool_mb = caps[2].parse().ok();
+        } else if let Some(caps) = RE_RESERVED.captures(msg) {
+            self.inventory(log, false).host.reserved_gpu_memory_mb = caps[1].parse().ok();
+        }
+    }
+
+    fn render(&mut self, log: &LogLine, msg: &str) {
+        lazy_static! {
+            static ref RE_DEVICE: regex::Regex = regex::Regex::new(r"^Cuda ID: ([0-9]+)").unwrap();
+        }
+        if let Some(x) = msg.strip_prefix("EGL Version: ") {
+            self.inventory(log, false).host.egl_version = x.to_string();
+        } else if let Some(x) = msg.strip_prefix("OpenGL Version: ") {
+            self.inventory(log, false).host.opengl_version = x.to_string();
+        } else if let Some(caps) = RE_DEVICE.captures(msg) {
+            if let Ok(id) = caps[1].parse() {
+                self.inventory(log, false).gpu(id).render = Some(true);
+            }
+        }
+    }
+}
+
+impl LogWriter for HardwareWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let msg = log.msg.trim();
+        let file = log.fileline.split(':').next().unwrap_or("");
+        match file {
+            "CudaMgr.cpp" => self.cuda(log, msg),
+            "DataMgr.cpp" => self.data(log, msg),
+            "EglPlatform.cpp" | "OpenGLDriver.cpp" => self.render(log, msg),
+            _ => if let (Some("version"), Some(version)) = (log.event.as_deref(), &log.msg_norm) {
+                let new = self.has(log, &|x| x.version.is_some());
+                self.inventory(log, new).version = Some(version.to_string());
+            } else if msg.starts_with("Started in ") && msg.ends_with(" mode") {
+                self.inventory(log, false).host.mode = msg[11..msg.len() - 5].to_string();
+            },
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
@@ -0,0 +1,109 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{Error, ErrorKind};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use super::forward::rejected;
+use super::lineparser::SResult;
+
+
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(30);
+
+// The response of a failed POST, inside its io::Error, so a sink can tell the status
+#[derive(Debug)]
+struct Status {
+    code: u16,
+    message: String,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl StdError for Status {}
+
+// the HTTP status of an error of post
+pub(crate) fn status(e: &(dyn StdError + 'static)) -> Option<u16> {
+    let inner = e.downcast_ref::<Error>()?.get_ref()?;
+    inner.downcast_ref::<Status>().map(|x| x.code)
+}
+
+// Plain http://host:port/path only, the sinks are local collectors and agents.
+struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> SResult<Url> {
+    let rest = match url.strip_prefix("http://") {
+        Some(x) => x,
+        None => return Err(rejected(format!("Only http:// URLs are supported: {}", url))),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rfind(':') {
+        Some(i) => (&authority[..i], authority[i + 1..].parse().map_err(|_| rejected(format!("Bad port in URL: {}", url)))?),
+        None => (authority, 80),
+    };
+    Ok(Url {
+        host: host.to_string(),
+        port,
+        path: path.to_string(),
+    })
+}
+
+// POSTs the body, an error unless the status is 2xx.
+// A 4xx other than 408 and 429 is rejected, the same body would fail again.
+pub(crate) fn post(url: &str, content_type: &str, body: &[u8]) -> SResult<()> {
+    let url = parse_url(url)?;
+    let stream = TcpStream::connect((url.host.as_str(), url.port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut writer = &stream;
+    write!(writer, "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        url.path, url.host, url.port, content_type, body.len())?;
+    writer.write_all(body)?;
+    writer.flush()?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let code: u16 = status.split_whitespace().nth(1).and_then(|x| x.parse().ok()).unwrap_or(0);
+    if (200..300).contains(&code) {
+        return Ok(())
+    }
+    let mut response = String::new();
+    let _ = reader.take(4096).read_to_string(&mut response);
+    let body = response.split("\r\n\r\n").nth(1).unwrap_or("").trim();
+    let message = format!("POST {} failed: {} {}", url.path, status.trim(), body);
+    // the kind of rejected, see forward.rs
+    let kind = if (400..500).contains(&code) && code != 408 && code != 429 {
+        ErrorKind::InvalidInput
+    } else {
+        ErrorKind::InvalidData
+    };
+    Err(Box::new(Error::new(kind, Status { code, message })))
+}
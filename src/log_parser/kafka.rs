@@ -0,0 +1,311 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use lazy_static::lazy_static;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext, PurgeConfig};
+use rdkafka::{ClientContext, Message};
+
+use serde::{Deserialize, Serialize};
+
+use super::es::doc_id;
+use super::forward::{rejected, Forwarder};
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::ISO_TIME_FORMAT;
+
+
+pub const DEFAULT_BROKERS: &str = "localhost:9092";
+pub const DEFAULT_TOPIC: &str = "omnisci-logs";
+
+const CLIENT_ID: &str = "omnisci-log-scraper";
+// a line not acknowledged by then fails its batch, which is sent again
+const MESSAGE_TIMEOUT_MS: &str = "30000";
+// longer than the message timeout, so the delivery reports come before it
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(60);
+// between tries to queue a line while the queue of librdkafka is full
+const QUEUE_FULL_WAIT: Duration = Duration::from_millis(100);
+
+// hostname/session, so the lines of a session stay in order on one partition
+pub fn record_key(log: &LogLine) -> String {
+    format!("{}/{}", log.hostname.as_deref().unwrap_or(""), log.session.as_deref().unwrap_or(""))
+}
+
+// The librdkafka properties of a --kafka-config file, a name=value per line, and # for comments:
+//   security.protocol=SASL_SSL
+//   sasl.mechanisms=SCRAM-SHA-512
+pub fn read_properties(path: &str) -> SResult<Vec<(String, String)>> {
+    let mut properties = vec![];
+    for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+        match line.find('=') {
+            Some(x) => properties.push((line[..x].trim().to_string(), line[x + 1..].trim().to_string())),
+            None => return Err(format!("{} line {} is not name=value: {}", path, i + 1, line).into()),
+        }
+    }
+    Ok(properties)
+}
+
+// The last line of an input that the brokers acknowledged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub logtime: String,
+    pub id: String,
+    pub partition: i32,
+    pub offset: i64,
+}
+
+lazy_static! {
+    // the inputs of a run share the checkpoint file, each writing it from the thread of its Forwarder
+    static ref CHECKPOINT_LOCK: Mutex<()> = Mutex::new(());
+}
+
+// checkpoints by input file
+pub fn read_checkpoints(path: &str) -> SResult<BTreeMap<String, Checkpoint>> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(serde_json::from_str(&text)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+pub fn write_checkpoint(path: &str, logfile: &str, checkpoint: Checkpoint) -> SResult<()> {
+    // held until the rename, so no input overwrites the checkpoint another just wrote
+    let _lock = CHECKPOINT_LOCK.lock().map_err(|e| format!("kafka checkpoint lock poisoned, {}", e))?;
+    let mut checkpoints = read_checkpoints(path)?;
+    checkpoints.insert(logfile.to_string(), checkpoint);
+    // a whole file even if killed while writing
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, serde_json::to_string_pretty(&checkpoints)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// The error of a batch, rejected if sending it again cannot fix it, like a topic the client may not write to,
+// so the run stops instead of retrying forever, see forward.rs
+fn batch_error(e: &KafkaError) -> Box<dyn StdError> {
+    use RDKafkaErrorCode::*;
+    match e.rdkafka_error_code() {
+        Some(TopicAuthorizationFailed | ClusterAuthorizationFailed | SaslAuthenticationFailed | Authentication
+            | UnknownTopic | UnknownTopicOrPartition | InvalidTopic | MessageSizeTooLarge | InvalidMessageSize
+            | InvalidMessage | InvalidRecord | InvalidRequiredAcks | PolicyViolation | UnsupportedVersion
+            | UnsupportedForMessageFormat | InvalidArgument | Fatal) => rejected(format!("Kafka rejected the lines: {}", e)),
+        _ => Box::new(e.clone()),
+    }
+}
+
+struct Record {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    timestamp: i64,
+    logtime: NaiveDateTime,
+    id: String,
+}
+
+#[derive(Default)]
+struct Delivered {
+    // index in the batch of its last line
+    last: usize,
+    // partition and offset of the last line
+    offset: Option<(i32, i64)>,
+    error: Option<KafkaError>,
+}
+
+// The delivery reports of the batch being sent, the index of the line in the batch is the opaque
+#[derive(Default)]
+struct Deliveries {
+    delivered: Mutex<Delivered>,
+}
+
+impl ClientContext for Deliveries {}
+
+impl ProducerContext for Deliveries {
+    type DeliveryOpaque = usize;
+
+    fn delivery(&self, result: &DeliveryResult<'_>, index: usize) {
+        if let Ok(mut delivered) = self.delivered.lock() {
+            match result {
+                Ok(message) if index == delivered.last => delivered.offset = Some((message.partition(), message.offset())),
+                Ok(_) => (),
+                Err((e, _)) => if delivered.error.is_none() {
+                    delivered.error = Some(e.clone());
+                },
+            }
+        }
+    }
+}
+
+impl Deliveries {
+    fn start(&self, len: usize) -> SResult<()> {
+        let mut delivered = self.delivered.lock().map_err(|e| format!("kafka deliveries lock poisoned, {}", e))?;
+        *delivered = Delivered { last: len.saturating_sub(1), ..Default::default() };
+        Ok(())
+    }
+
+    // the partition and offset of the last line, once the batch is flushed
+    fn finish(&self) -> SResult<(i32, i64)> {
+        let delivered = self.delivered.lock().map_err(|e| format!("kafka deliveries lock poisoned, {}", e))?;
+        match (&delivered.error, delivered.offset) {
+            (Some(e), _) => Err(batch_error(e)),
+            (None, Some(x)) => Ok(x),
+            (None, None) => Err("Kafka delivery of the last line was not reported".into()),
+        }
+    }
+}
+
+// librdkafka with acks from all in-sync replicas, idempotent, so its own retries keep the lines
+// of a partition in order and once, and the partitioner of the Java client, so our keys land where theirs would.
+// The properties of the --kafka-config file come last, and can change these.
+fn producer(brokers: &str, properties: &[(String, String)]) -> SResult<BaseProducer<Deliveries>> {
+    let mut config = ClientConfig::new();
+    config.set("bootstrap.servers", brokers)
+        .set("client.id", CLIENT_ID)
+        .set("enable.idempotence", "true")
+        .set("acks", "all")
+        .set("compression.type", "lz4")
+        .set("partitioner", "murmur2_random")
+        .set("message.timeout.ms", MESSAGE_TIMEOUT_MS);
+    for (name, value) in properties {
+        config.set(name, value);
+    }
+    Ok(config.create_with_context(Deliveries::default())?)
+}
+
+// The partition and offset of the last line, once all are acknowledged.
+// Some may be sent again after an error, so delivery is at least once.
+fn send(producer: &BaseProducer<Deliveries>, topic: &str, batch: &[Record]) -> SResult<(i32, i64)> {
+    let result = send_batch(producer, topic, batch);
+    if result.is_err() {
+        // the reports of what is left, before the batch is sent again
+        producer.purge(PurgeConfig::default().queue().inflight());
+        let _ = producer.flush(FLUSH_TIMEOUT);
+    }
+    result
+}
+
+fn send_batch(producer: &BaseProducer<Deliveries>, topic: &str, batch: &[Record]) -> SResult<(i32, i64)> {
+    producer.context().start(batch.len())?;
+    for (i, record) in batch.iter().enumerate() {
+        let mut message = BaseRecord::with_opaque_to(topic, i)
+            .key(&record.key)
+            .payload(&record.value)
+            .timestamp(record.timestamp);
+        loop {
+            match producer.send(message) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), x)) => {
+                    producer.poll(QUEUE_FULL_WAIT);
+                    message = x;
+                },
+                Err((e, _)) => return Err(batch_error(&e)),
+            }
+        }
+    }
+    producer.flush(FLUSH_TIMEOUT)?;
+    producer.context().finish()
+}
+
+// Publishes lines as JSON to a Kafka topic, keyed by hostname/session.
+// With a checkpoint file, the last acknowledged line of each input is saved,
+// and lines up to it are skipped the next time, to resume a followed log.
+pub struct KafkaWriter {
+    forwarder: Forwarder<Record>,
+    // the checkpoint of this input, until a line after it
+    resume: Option<(NaiveDateTime, String)>,
+}
+
+impl KafkaWriter {
+    pub fn new(brokers: &str, topic: &str, config: Option<&str>, checkpoint: Option<&str>, logfile: &str) -> SResult<KafkaWriter> {
+        let mut resume = None;
+        if let Some(path) = checkpoint {
+            if let Some(x) = read_checkpoints(path)?.remove(logfile) {
+                resume = Some((NaiveDateTime::parse_from_str(&x.logtime, ISO_TIME_FORMAT)?, x.id));
+            }
+        }
+        let properties = match config {
+            Some(path) => read_properties(path)?,
+            None => vec![],
+        };
+        let producer = producer(brokers, &properties)?;
+        let topic = topic.to_string();
+        let checkpoint = checkpoint.map(|x| x.to_string());
+        let logfile = logfile.to_string();
+        Ok(KafkaWriter {
+            forwarder: Forwarder::new(move |batch: &[Record]| {
+                let (partition, offset) = send(&producer, &topic, batch)?;
+                match (&checkpoint, batch.last()) {
+                    (Some(path), Some(last)) => write_checkpoint(path, &logfile, Checkpoint {
+                        logtime: last.logtime.format(ISO_TIME_FORMAT).to_string(),
+                        id: last.id.clone(),
+                        partition,
+                        offset,
+                    }),
+                    _ => Ok(()),
+                }
+            }),
+            resume,
+        })
+    }
+
+    // if the line was sent before the checkpoint
+    fn sent(&mut self, log: &LogLine, id: &str) -> bool {
+        let (logtime, last) = match &self.resume {
+            None => return false,
+            Some(x) => x,
+        };
+        if log.logtime < *logtime || (log.logtime == *logtime && id != last) {
+            return true
+        }
+        // the checkpoint line itself, or the first line after it
+        let sent = log.logtime == *logtime;
+        self.resume = None;
+        sent
+    }
+}
+
+impl LogWriter for KafkaWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let id = doc_id(log);
+        if self.sent(log, &id) {
+            return Ok(())
+        }
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        self.forwarder.push(Record {
+            key: record_key(log).into_bytes(),
+            value: serde_json::to_vec(log)?,
+            timestamp: (log.logtime - epoch).num_milliseconds(),
+            logtime: log.logtime,
+            id,
+        })
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.forwarder.close()
+    }
+}
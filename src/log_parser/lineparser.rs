@@ -46,6 +46,33 @@ use omnisci::omnisci::TQueryResult;
 
 use serde_json;
 
+use super::fingerprint::{fingerprint, fingerprint_hash};
+use super::report::ReportOptions;
+use super::digest::DigestWriter;
+use super::stats::StatsWriter;
+use super::errors::ErrorsWriter;
+use super::sessions::SessionsWriter;
+use super::audit::AuditWriter;
+use super::templates::TemplateWriter;
+use super::redact::{Redactor, RedactWriter};
+use super::otlp::{OtlpSink, OtlpWriter};
+use super::chrome_trace::ChromeTraceWriter;
+use super::es::{EsBulkWriter, DEFAULT_INDEX};
+use super::loki::LokiWriter;
+use super::syslog::SyslogWriter;
+#[cfg(feature = "kafka")]
+use super::kafka::{self, KafkaWriter};
+use super::{loki, syslog};
+use super::timers::{parse_stacked_times, FoldedWriter, StackedTimes, TimersWriter};
+use super::qmd::{self, parse_qmd, QmdWriter};
+use super::plan::{self, parse_plan, Plan, PlanWriter};
+use super::config::ConfigWriter;
+use super::hardware::HardwareWriter;
+use super::memory::{parse_allocation, Allocation, MemoryWriter};
+use super::locks::{parse_lock, LockWriter};
+use super::tables::TableWriter;
+use super::vega::{parse_vega, Vega, VegaWriter};
+
 
 // standard result with error boxed so original errors are preserved
 // https://doc.rust-lang.org/stable/rust-by-example/error/multiple_error_types/boxing_errors.html
@@ -75,12 +102,38 @@ impl fmt::Display for Severity {
     }
 }
 
+impl Severity {
+    // for --min-severity
+    pub fn new(name: &str) -> SResult<Severity> {
+        match name.to_uppercase().as_str() {
+            "DEBUG" => Ok(Severity::DEBUG),
+            "INFO" => Ok(Severity::INFO),
+            "WARNING" => Ok(Severity::WARNING),
+            "ERROR" => Ok(Severity::ERROR),
+            "FATAL" => Ok(Severity::FATAL),
+            _ => Err(format!("Unknown --min-severity, expected DEBUG, INFO, WARNING, ERROR or FATAL: '{}'", name).into()),
+        }
+    }
+
+    // like glog, from DEBUG to FATAL. OTHER is an unknown level, so with DEBUG.
+    // INPUT and AUTH are made up by change_severity, they are never a glog level.
+    pub fn rank(&self) -> u8 {
+        match self {
+            Severity::DEBUG | Severity::OTHER => 0,
+            Severity::INFO => 1,
+            Severity::WARNING | Severity::INPUT | Severity::AUTH => 2,
+            Severity::ERROR => 3,
+            Severity::FATAL => 4,
+        }
+    }
+}
+
 
 mod serde_date_format {
     use chrono::{NaiveDateTime};
     use serde::{self, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S%.f";
+    use super::super::report::TIME_FORMAT;
 
     pub fn serialize<S>(
         date: &NaiveDateTime,
@@ -88,7 +141,7 @@ mod serde_date_format {
     ) -> Result<S::Ok, S::Error>
     where S: Serializer,
     {
-        let s = format!("{}", date.format(FORMAT));
+        let s = format!("{}", date.format(TIME_FORMAT));
         serializer.serialize_str(&s)
     }
 }
@@ -124,6 +177,23 @@ mod serde_vec_format {
     }
 }
 
+// nested values as a JSON string, so they fit in one csv column
+mod serde_json_format {
+    use serde::{self, Serialize, Serializer};
+
+    pub fn serialize<S, T>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where S: Serializer, T: Serialize,
+    {
+        match value {
+            None => serializer.serialize_none(),
+            Some(value) => serializer.serialize_str(&serde_json::to_string(value).map_err(serde::ser::Error::custom)?),
+        }
+    }
+}
+
 
 #[derive(Serialize, Debug, Clone)]
 pub struct LogLine {
@@ -134,6 +204,9 @@ pub struct LogLine {
     pub logtime: NaiveDateTime,
 
     pub severity: Severity,
+    // the level glog wrote, before change_severity, for --min-severity
+    #[serde(skip)]
+    pub glog_severity: Severity,
     pub pid: i32,
     pub threadid: Option<i32>,
     pub fileline: String,
@@ -166,6 +239,37 @@ pub struct LogLine {
     pub dashboardid: Option<String>,
     pub chartid: Option<String>,
     pub queryid: Option<i32>,
+
+    // hash of the normalized query, see fingerprint.rs
+    pub fingerprint: Option<String>,
+
+    // with --templates, msg_norm is a mined template, see templates.rs
+    pub template_id: Option<i32>,
+    #[serde(with = "serde_vec_format")]
+    pub template_params: Option<Vec<String>>,
+
+    // the call tree of a stacked_times line, see timers.rs
+    #[serde(with = "serde_json_format")]
+    pub timers: Option<StackedTimes>,
+
+    // a Query Memory Descriptor, see qmd.rs
+    #[serde(with = "serde_json_format")]
+    pub qmd: Option<serde_json::Map<String, serde_json::Value>>,
+
+    // the Calcite plan of a query, see plan.rs
+    #[serde(with = "serde_json_format")]
+    pub plan: Option<Plan>,
+
+    // the level of DEBUG lines, 1 to 4, like log-severity = DEBUG4
+    pub verbosity: Option<i32>,
+
+    // the numbers of a GPU or CPU memory allocation line, see memory.rs
+    #[serde(with = "serde_json_format")]
+    pub memory: Option<Allocation>,
+
+    // the data sql, marks and size of a render_vega call, see vega.rs
+    #[serde(with = "serde_json_format")]
+    pub vega: Option<Vega>,
 }
 
 
@@ -197,7 +301,16 @@ pub const CREATE_TABLE: &str = "CREATE TABLE omnisci_log_scraper (
     msg_norm TEXT,
     dashboardid TEXT,
     chartid TEXT,
-    queryid INTEGER
+    queryid INTEGER,
+    fingerprint TEXT ENCODING DICT(32),
+    template_id INTEGER,
+    template_params TEXT[],
+    timers TEXT ENCODING NONE,
+    qmd TEXT ENCODING NONE,
+    plan TEXT ENCODING NONE,
+    verbosity INTEGER,
+    memory TEXT ENCODING NONE,
+    vega TEXT ENCODING NONE
 ) with (max_rows=640000000);
 ";
 
@@ -205,6 +318,19 @@ pub const CREATE_TABLE: &str = "CREATE TABLE omnisci_log_scraper (
 const ADD_COL_DASHBOARD: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN dashboardid TEXT";
 const ADD_COL_CHART: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN chartid TEXT";
 const ADD_COL_QUERYID: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN queryid INTEGER";
+const ADD_COL_FINGERPRINT: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN fingerprint TEXT ENCODING DICT(32)";
+const ADD_COL_TEMPLATE_ID: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN template_id INTEGER";
+const ADD_COL_TEMPLATE_PARAMS: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN template_params TEXT[]";
+// The JSON columns are not dictionary encoded, so they are not limited to STRING_DICT_MAX_LEN,
+// and are never truncated, which would make them invalid JSON.
+const ADD_COL_TIMERS: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN timers TEXT ENCODING NONE";
+const ADD_COL_QMD: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN qmd TEXT ENCODING NONE";
+const ADD_COL_PLAN: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN plan TEXT ENCODING NONE";
+// DEBUG2 to DEBUG4 were loaded as OTHER, and DEBUG1 as DEBUG without verbosity. To update old rows:
+// # print(con.con.execute(f"update {t.name} set verbosity = 1 where severity = 'DEBUG' and verbosity is null").fetchall())
+const ADD_COL_VERBOSITY: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN verbosity INTEGER";
+const ADD_COL_MEMORY: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN memory TEXT ENCODING NONE";
+const ADD_COL_VEGA: &str = "ALTER TABLE omnisci_log_scraper ADD COLUMN vega TEXT ENCODING NONE";
 
 
 enum LogEntry {
@@ -277,7 +403,12 @@ impl LogLine {
                     Severity::ERROR => "red",
                     Severity::WARNING => "red",
                     Severity::INFO => "blue",
-                    Severity::DEBUG => "green",
+                    Severity::DEBUG => match self.verbosity {
+                        Some(2) => "bright green",
+                        Some(3) => "bright cyan",
+                        Some(4) => "bright black",
+                        _ => "green",
+                    },
                     Severity::OTHER => "cyan",
                     Severity::INPUT => "grey",
                     Severity::AUTH => "magenta",
@@ -308,10 +439,18 @@ impl LogLine {
             // work was done in stdlog
         } else if self.regex_msg() {
             // work was done in regex_msg
+        } else if self.stacked_times() {
+            // work was done in stacked_times
+        } else if self.query_memory_descriptor() {
+            // work was done in query_memory_descriptor
+        } else if self.query_plan() {
+            // work was done in query_plan
         } else {
             self.msg_norm();
         }
+        self.fingerprint();
         self.change_severity();
+        self.allocation();
         self.truncate_strings();
     }
 
@@ -366,11 +505,22 @@ impl LogLine {
         // Incorrect Row (expected 37 columns, has 36):
         // QueryDispatchQueue.h:61 Dispatching query with 0 queries in the queue.
         // QueryDispatchQueue.h:92 Worker 1 running query and returning control. There are now 0 queries in the queue.
-        // Acquiring Table Data Read Lock for table: t
         // User mapd connected to database mapd
         // Could not encode string: x, the encoded value doesn't fit in 16 bits. Will store NULL instead.
         // LeafAggregator.cpp:494 Leaf 0 executed in 1400 ms, entry_count(1), unserialized 8 bytes of results in 0 ms.
 
+        // LockMgr.h:207 Acquiring Table Data Read Lock for table: omnisci_states, see locks.rs
+        if let Some(lock) = parse_lock(&self.msg) {
+            self.event = Some(String::from("table_lock"));
+            self.msg_norm = Some(format!("Acquiring Table {} {} Lock", lock.lock_type, lock.mode));
+            self.name_values = Some(vec![
+                String::from("lock_type"), lock.lock_type,
+                String::from("lock_mode"), lock.mode,
+                String::from("table"), lock.table,
+            ]);
+            return true
+        }
+
         // DBHandler.cpp:238 OmniSci Server 5.4.0-20200904-1b17b5c4e2
         if self.msg.starts_with("OmniSci Server 5") {
             self.event = Some(String::from("version"));
@@ -382,6 +532,64 @@ impl LogLine {
         false
     }
 
+    // debug timers, not a stdlog, so the sequence of the call is only in timers
+    fn stacked_times(self: &mut LogLine) -> bool {
+        if let Some(stacked) = parse_stacked_times(&self.msg) {
+            self.event = Some(String::from("stacked_times"));
+            self.total_time = Some(stacked.timers[0].total_ms as i32);
+            self.msg_norm = Some(format!("stacked_times {}", stacked.timers[0].name));
+            self.timers = Some(stacked);
+            return true
+        }
+        false
+    }
+
+    // on the threadid and queryid of the executor, see qmd.rs for linking it to its call
+    fn query_memory_descriptor(self: &mut LogLine) -> bool {
+        if let Some(x) = parse_qmd(&self.msg) {
+            self.event = Some(String::from("query_memory_descriptor"));
+            self.msg_norm = Some(String::from(qmd::HEADER));
+            self.qmd = Some(x);
+            return true
+        }
+        false
+    }
+
+    // on the threadid of the executor, see plan.rs for linking it to its call
+    fn query_plan(self: &mut LogLine) -> bool {
+        if let Some(x) = parse_plan(&self.msg) {
+            self.event = Some(String::from("query_plan"));
+            self.msg_norm = Some(String::from(plan::HEADER));
+            self.plan = Some(x);
+            return true
+        }
+        false
+    }
+
+    // after change_severity, which tags the failures and punts
+    fn allocation(self: &mut LogLine) {
+        if let Some(event) = &self.event {
+            if !event.starts_with("memory_") {
+                return
+            }
+        }
+        if let Some(x) = parse_allocation(&self.msg) {
+            if self.event.is_none() {
+                self.event = Some(String::from("memory_slab"));
+            }
+            self.memory = Some(x);
+        }
+    }
+
+    // before truncate_strings, which cuts long vega_json, the vega column is not dictionary encoded so it is kept whole
+    fn vega(self: &mut LogLine) {
+        if let (Some(event), Some(query)) = (&self.event, &self.query) {
+            if event == "render_vega" || event == "render_vega_begin" {
+                self.vega = parse_vega(query, &self.name_values);
+            }
+        }
+    }
+
     fn msg_norm(self: &mut LogLine) {
         lazy_static! {
             // static ref RE: Regex = Regex::new("...").unwrap();
@@ -406,6 +614,13 @@ impl LogLine {
         }
     }
 
+    // only sql_execute has an operation, vega_json in query is not sql
+    fn fingerprint(self: &mut LogLine) {
+        if let (Some(_), Some(query)) = (&self.operation, &self.query) {
+            self.fingerprint = Some(fingerprint_hash(&fingerprint(query)));
+        }
+    }
+
     fn truncate_strings(self: &mut LogLine) {
         match &self.query {
             None => (),
@@ -465,6 +680,7 @@ impl LogLine {
                     // Session 148-6778 idle duration 7207 seconds exceeds maximum idle duration 7200 seconds. Invalidating session.
                     self.severity = Severity::AUTH;
                     self.event = Some(String::from("session_invalidated"));
+                    self.session = self.msg.split_whitespace().nth(1).map(|x| x.to_string());
                 }
                 else if let Some(event) = &self.event {
                     if event == "connect" || event == "connect_begin"
@@ -578,6 +794,7 @@ impl LogLine {
             let remainder = msg_elements[i].to_string();
             self.parse_key_value_arrays(remainder);
         }
+        self.vega();
         return true
     }
 
@@ -748,9 +965,13 @@ impl LogLine {
             "E" => Severity::ERROR,
             "W" => Severity::WARNING,
             "F" => Severity::FATAL,
-            "1" => Severity::DEBUG,
+            "1" | "2" | "3" | "4" => Severity::DEBUG,
             _ => Severity::OTHER,
         };
+        let verbosity = match severity {
+            Severity::DEBUG => parts[i].parse().ok(),
+            _ => None,
+        };
 
         let i = i + 1;
         let pid: i32 = match parts[i].parse() {
@@ -797,6 +1018,7 @@ impl LogLine {
         let msg = parts[i..].join(" ").trim().to_string();
         let result = LogLine{
             logtime,
+            glog_severity: severity.clone(),
             severity,
             pid,
             threadid,
@@ -820,6 +1042,15 @@ impl LogLine {
             dashboardid: None,
             chartid: None,
             queryid,
+            fingerprint: None,
+            template_id: None,
+            template_params: None,
+            timers: None,
+            qmd: None,
+            plan: None,
+            verbosity,
+            memory: None,
+            vega: None,
         };
         return Ok(result)
     }
@@ -922,6 +1153,24 @@ impl<'a, R: BufRead> Iterator for ParsingLine<'a, R> {
     }
 }
 
+// Settings for the output types that send to another system
+#[derive(Debug, Clone, Default)]
+pub struct SinkOptions {
+    // URL to send to instead of the output file
+    pub endpoint: Option<String>,
+    // es-bulk index name, strftime of the logtime
+    pub index: Option<String>,
+    // kafka topic
+    #[cfg(feature = "kafka")]
+    pub topic: Option<String>,
+    // file of the last line kafka acknowledged for each input
+    #[cfg(feature = "kafka")]
+    pub checkpoint: Option<String>,
+    // file of librdkafka properties, like the TLS and SASL of the brokers
+    #[cfg(feature = "kafka")]
+    pub kafka_config: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum OutputType {
     CSV,
@@ -931,6 +1180,31 @@ pub enum OutputType {
     SQL,
     Execute,
     Load,
+    Digest,
+    Stats,
+    Errors,
+    Sessions,
+    Audit,
+    Tui,
+    Top,
+    Metrics,
+    Otlp,
+    ChromeTrace,
+    EsBulk,
+    EsTemplate,
+    Loki,
+    Syslog,
+    Kafka,
+    Timers,
+    Folded,
+    Qmd,
+    Plans,
+    Config,
+    Hardware,
+    Memory,
+    Locks,
+    Tables,
+    Vega,
 }
 
 impl fmt::Display for OutputType {
@@ -948,12 +1222,55 @@ impl OutputType {
             &"sql" => OutputType::SQL,
             &"execute" => OutputType::Execute,
             &"load" => OutputType::Load,
+            &"digest" => OutputType::Digest,
+            &"stats" => OutputType::Stats,
+            &"errors" => OutputType::Errors,
+            &"sessions" => OutputType::Sessions,
+            &"audit" => OutputType::Audit,
+            &"tui" => OutputType::Tui,
+            &"top" => OutputType::Top,
+            &"metrics" => OutputType::Metrics,
+            &"otlp" => OutputType::Otlp,
+            &"chrome-trace" => OutputType::ChromeTrace,
+            &"es-bulk" => OutputType::EsBulk,
+            &"es-template" => OutputType::EsTemplate,
+            &"loki" => OutputType::Loki,
+            &"syslog" => OutputType::Syslog,
+            &"kafka" => OutputType::Kafka,
+            &"timers" => OutputType::Timers,
+            &"folded" => OutputType::Folded,
+            &"qmd" => OutputType::Qmd,
+            &"plans" => OutputType::Plans,
+            &"config" => OutputType::Config,
+            &"hardware" => OutputType::Hardware,
+            &"memory" => OutputType::Memory,
+            &"locks" => OutputType::Locks,
+            &"tables" => OutputType::Tables,
+            &"vega" => OutputType::Vega,
             _ => panic!(format!("Unknown OutputType: '{}'", name))
         }
     }
+
+    // reports aggregate all inputs, and are written once at the end
+    pub fn is_report(&self) -> bool {
+        match self {
+            OutputType::Digest => true,
+            OutputType::Stats => true,
+            OutputType::Errors => true,
+            OutputType::Sessions => true,
+            OutputType::Audit => true,
+            OutputType::Config => true,
+            OutputType::Hardware => true,
+            OutputType::Memory => true,
+            OutputType::Locks => true,
+            OutputType::Tables => true,
+            OutputType::Vega => true,
+            _ => false,
+        }
+    }
 }
 
-trait LogWriter {
+pub trait LogWriter {
     fn write(&mut self, log: &LogLine) -> SResult<()>;
     fn close(&mut self) -> SResult<()> { Ok(()) }
 }
@@ -1152,7 +1469,9 @@ impl LogLoader {
     fn new(db: &str) -> SResult<LogLoader> {
         let mut con = omnisci::client::connect_url(db)?;
 
-        for alter in vec![CREATE_TABLE, ADD_COL_DASHBOARD, ADD_COL_CHART, ADD_COL_QUERYID] {
+        for alter in vec![CREATE_TABLE, ADD_COL_DASHBOARD, ADD_COL_CHART, ADD_COL_QUERYID, ADD_COL_FINGERPRINT,
+                ADD_COL_TEMPLATE_ID, ADD_COL_TEMPLATE_PARAMS, ADD_COL_TIMERS, ADD_COL_QMD, ADD_COL_PLAN, ADD_COL_VERBOSITY,
+                ADD_COL_MEMORY, ADD_COL_VEGA] {
             match con.sql_execute(String::from(alter), true, String::from("omnisci_log_scraper")) {
                 // ignore errors, assuming the table or columns already exist (which otherwise will lead to an error on load_table)
                 Err(_) => (), // eprintln!("Error \"{}\" caused by SQL: {}", e, alter),
@@ -1169,6 +1488,21 @@ impl LogLoader {
     }
 
     fn to_tcolumns(lines: &Vec<LogLine>) -> Vec<TColumn> {
+        let timers: Vec<Option<String>> = lines.iter()
+            .map(|val| val.timers.as_ref().and_then(|x| serde_json::to_string(x).ok()))
+            .collect();
+        let qmds: Vec<Option<String>> = lines.iter()
+            .map(|val| val.qmd.as_ref().and_then(|x| serde_json::to_string(x).ok()))
+            .collect();
+        let plans: Vec<Option<String>> = lines.iter()
+            .map(|val| val.plan.as_ref().and_then(|x| serde_json::to_string(x).ok()))
+            .collect();
+        let memories: Vec<Option<String>> = lines.iter()
+            .map(|val| val.memory.as_ref().and_then(|x| serde_json::to_string(x).ok()))
+            .collect();
+        let vegas: Vec<Option<String>> = lines.iter()
+            .map(|val| val.vega.as_ref().and_then(|x| serde_json::to_string(x).ok()))
+            .collect();
         vec![
             TColumn::from(lines.iter().map(
                 |val| val.logtime.timestamp() * 1000000 as i64 + val.logtime.timestamp_subsec_micros() as i64
@@ -1196,6 +1530,15 @@ impl LogLoader {
             TColumn::from(lines.iter().map(|val| &val.dashboardid).collect::<Vec<&Option<String>>>()),
             TColumn::from(lines.iter().map(|val| &val.chartid).collect::<Vec<&Option<String>>>()),
             TColumn::from(lines.iter().map(|val| val.queryid).collect::<Vec<Option<i32>>>()),
+            TColumn::from(lines.iter().map(|val| &val.fingerprint).collect::<Vec<&Option<String>>>()),
+            TColumn::from(lines.iter().map(|val| val.template_id).collect::<Vec<Option<i32>>>()),
+            TColumn::from(&lines.iter().map(|val| &val.template_params).collect()),
+            TColumn::from(timers.iter().collect::<Vec<&Option<String>>>()),
+            TColumn::from(qmds.iter().collect::<Vec<&Option<String>>>()),
+            TColumn::from(plans.iter().collect::<Vec<&Option<String>>>()),
+            TColumn::from(lines.iter().map(|val| val.verbosity).collect::<Vec<Option<i32>>>()),
+            TColumn::from(memories.iter().collect::<Vec<&Option<String>>>()),
+            TColumn::from(vegas.iter().collect::<Vec<&Option<String>>>()),
         ]
     }
 }
@@ -1246,19 +1589,84 @@ fn output_filename(input: &str, output: &str, extension: &str) -> String {
     }
 }
 
-fn new_log_writer(input: &str, filter: &Vec<&str>, output: Option<&str>, output_type: &OutputType, db: Option<&str>) -> SResult<Box<dyn LogWriter>> {
+#[cfg(feature = "kafka")]
+fn kafka_writer(input: &str, sink: &SinkOptions) -> SResult<Box<dyn LogWriter>> {
+    Ok(Box::new(KafkaWriter::new(
+        sink.endpoint.as_deref().unwrap_or(kafka::DEFAULT_BROKERS),
+        sink.topic.as_deref().unwrap_or(kafka::DEFAULT_TOPIC),
+        sink.kafka_config.as_deref(),
+        sink.checkpoint.as_deref(),
+        input)?))
+}
+
+#[cfg(not(feature = "kafka"))]
+fn kafka_writer(_input: &str, _sink: &SinkOptions) -> SResult<Box<dyn LogWriter>> {
+    Err("-t kafka needs a build with the kafka feature: cargo build --release --features kafka".into())
+}
+
+fn new_log_writer(
+        input: &str,
+        filter: &LineFilter,
+        output: Option<&str>,
+        output_type: &OutputType,
+        db: Option<&str>,
+        sink: &SinkOptions,
+        ) -> SResult<Box<dyn LogWriter>> {
+
+    if let (OutputType::Otlp, Some(url)) = (output_type, &sink.endpoint) {
+        return Ok(Box::new(OtlpWriter::new(OtlpSink::Http(url.to_string()))))
+    }
+    // forwarders always send to the endpoint, never to OUTPUT
+    if let OutputType::Loki = output_type {
+        return Ok(Box::new(LokiWriter::new(sink.endpoint.as_deref().unwrap_or(loki::DEFAULT_ENDPOINT))))
+    }
+    if let OutputType::Syslog = output_type {
+        return Ok(Box::new(SyslogWriter::new(sink.endpoint.as_deref().unwrap_or(syslog::DEFAULT_ENDPOINT))?))
+    }
+    if let OutputType::Kafka = output_type {
+        return kafka_writer(input, sink)
+    }
+    let index = sink.index.as_deref().unwrap_or(DEFAULT_INDEX);
     match output {
         Some(path) => match output_type {
             OutputType::Terminal => Ok(Box::new(TerminalWriter::new())),
             OutputType::CSV => {
                 let x = csv::Writer::from_path(output_filename(input, path, "csv"))?;
-                if filter.contains(&"sql") {
+                if filter.has("sql") {
                     // TODO write only sql fields
                     Ok(Box::new(CsvFileLogWriter{ writer: x}))
                 } else {
                     Ok(Box::new(CsvFileLogWriter{ writer: x}))
                 }
             },
+            OutputType::Otlp => {
+                let x = fs::File::create(output_filename(input, path, "json"))?;
+                Ok(Box::new(OtlpWriter::new(OtlpSink::File(Box::new(x)))))
+            },
+            OutputType::ChromeTrace => {
+                let x = fs::File::create(output_filename(input, path, "json"))?;
+                Ok(Box::new(ChromeTraceWriter::new(Box::new(io::BufWriter::new(x)))))
+            },
+            OutputType::EsBulk => {
+                let x = fs::File::create(output_filename(input, path, "ndjson"))?;
+                Ok(Box::new(EsBulkWriter::new(Box::new(io::BufWriter::new(x)), index)?))
+            },
+            OutputType::Timers => {
+                let x = fs::File::create(output_filename(input, path, "timers.csv"))?;
+                Ok(Box::new(TimersWriter::new(Box::new(x))))
+            },
+            OutputType::Folded => {
+                let x = fs::File::create(output_filename(input, path, "folded"))?;
+                Ok(Box::new(FoldedWriter::new(Box::new(x))))
+            },
+            OutputType::Qmd => {
+                let x = fs::File::create(output_filename(input, path, "qmd.csv"))?;
+                Ok(Box::new(QmdWriter::new(Box::new(x))))
+            },
+            OutputType::Plans => {
+                let x = fs::File::create(output_filename(input, path, "plans.csv"))?;
+                Ok(Box::new(PlanWriter::new(Box::new(x))))
+            },
             _ => panic!(format!("Output type not supported yet, {}", output_type)), // TODO
         },
         None => match output_type {
@@ -1275,6 +1683,13 @@ fn new_log_writer(input: &str, filter: &Vec<&str>, output: Option<&str>, output_
                     .from_writer(io::stdout())
                 })),
             OutputType::JSON => Ok(Box::new(LineWriter{writer: io::stdout()})),
+            OutputType::Otlp => Ok(Box::new(OtlpWriter::new(OtlpSink::File(Box::new(io::stdout()))))),
+            OutputType::ChromeTrace => Ok(Box::new(ChromeTraceWriter::new(Box::new(io::BufWriter::new(io::stdout()))))),
+            OutputType::EsBulk => Ok(Box::new(EsBulkWriter::new(Box::new(io::stdout()), index)?)),
+            OutputType::Timers => Ok(Box::new(TimersWriter::new(Box::new(io::stdout())))),
+            OutputType::Folded => Ok(Box::new(FoldedWriter::new(Box::new(io::stdout())))),
+            OutputType::Qmd => Ok(Box::new(QmdWriter::new(Box::new(io::stdout())))),
+            OutputType::Plans => Ok(Box::new(PlanWriter::new(Box::new(io::stdout())))),
             OutputType::SQL => Ok(Box::new(SqlLogWriter{})),
             OutputType::Execute => match db {
                 None => panic!("EXECUTE requires DB URL"),
@@ -1284,19 +1699,156 @@ fn new_log_writer(input: &str, filter: &Vec<&str>, output: Option<&str>, output_
                 None => panic!("LOAD requires DB URL"),
                 Some(db) => Ok(Box::new(LogLoader::new(db)?)),
             },
+            _ => panic!("Output type is a report or a live view, see report_logs, browse_logs, top_logs or serve_metrics, {}", output_type),
         }
     }
 }
 
+fn new_report_writer(output_type: &OutputType, options: &ReportOptions) -> SResult<Box<dyn LogWriter>> {
+    match output_type {
+        OutputType::Digest => Ok(Box::new(DigestWriter::new(options.clone()))),
+        OutputType::Stats => Ok(Box::new(StatsWriter::new(options.clone()))),
+        OutputType::Errors => Ok(Box::new(ErrorsWriter::new(options.clone())?)),
+        OutputType::Sessions => Ok(Box::new(SessionsWriter::new(options.clone()))),
+        OutputType::Audit => Ok(Box::new(AuditWriter::new(options.clone()))),
+        OutputType::Config => Ok(Box::new(ConfigWriter::new(options.clone()))),
+        OutputType::Hardware => Ok(Box::new(HardwareWriter::new(options.clone()))),
+        OutputType::Memory => Ok(Box::new(MemoryWriter::new(options.clone()))),
+        OutputType::Locks => Ok(Box::new(LockWriter::new(options.clone()))),
+        OutputType::Tables => Ok(Box::new(TableWriter::new(options.clone()))),
+        OutputType::Vega => Ok(Box::new(VegaWriter::new(options.clone()))),
+        _ => panic!("Output type is not a report, {}", output_type),
+    }
+}
+
+
+// Adds the writers that change lines before they are written.
+// Redaction is outermost, so templates are mined from the redacted msg.
+pub(crate) fn wrap_writer(
+        writer: Box<dyn LogWriter>,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        ) -> SResult<Box<dyn LogWriter>> {
+
+    let mut writer = writer;
+    if let Some(path) = templates {
+        writer = Box::new(TemplateWriter::new(path, writer)?);
+    }
+    if let Some(redactor) = redactor {
+        writer = Box::new(RedactWriter::new(redactor.clone(), writer));
+    }
+    Ok(writer)
+}
+
 
 pub fn transform_logs(
         input: &str,
         output: Option<&str>,
-        filter: &Vec<&str>,
+        filter: &LineFilter,
         output_type: &OutputType,
         db: Option<&str>,
         hostname: Option<&str>,
         follow: bool,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        sink: &SinkOptions,
+        ) -> SResult<()> {
+
+    let writer = new_log_writer(input, filter, output, &output_type, db, sink)?;
+    let mut writer = wrap_writer(writer, templates, redactor)?;
+    scrape_log(input, filter, hostname, follow, &mut writer)?;
+    writer.close()
+}
+
+// Like transform_logs, but all inputs go into one trace on stdout, a JSON array per input would not be valid JSON.
+pub fn trace_logs(
+        inputs: &Vec<String>,
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        follow: bool,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        ) -> SResult<()> {
+
+    if follow && inputs.len() > 1 {
+        return Err("--follow of several inputs to one chrome-trace is not supported, use --output".into())
+    }
+    let writer: Box<dyn LogWriter> = Box::new(ChromeTraceWriter::new(Box::new(io::BufWriter::new(io::stdout()))));
+    let mut writer = wrap_writer(writer, templates, redactor)?;
+    for input in inputs {
+        scrape_log(input, filter, hostname, follow, &mut writer)?;
+    }
+    writer.close()
+}
+
+// Like transform_logs, but for the report output types, all inputs go into one report.
+// A report is written once all inputs are read, so it cannot follow them.
+pub fn report_logs(
+        inputs: &Vec<String>,
+        filter: &LineFilter,
+        output_type: &OutputType,
+        hostname: Option<&str>,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        options: &ReportOptions,
+        ) -> SResult<()> {
+
+    let writer = new_report_writer(&output_type, options)?;
+    let mut writer = wrap_writer(writer, templates, redactor)?;
+    for input in inputs {
+        scrape_log(input, filter, hostname, false, &mut writer)?;
+    }
+    writer.close()
+}
+
+// The lines to scrape: the -f tags, like sql or select, and the levels of --min-severity and --max-verbosity
+#[derive(Debug, Clone, Default)]
+pub struct LineFilter {
+    pub tags: Vec<String>,
+    // compared with the glog level, not with the severity change_severity gave the line
+    pub min_severity: Option<Severity>,
+    pub max_verbosity: Option<i32>,
+}
+
+impl LineFilter {
+    pub fn new(tags: &str, min_severity: Option<&str>, max_verbosity: Option<&str>) -> SResult<LineFilter> {
+        let max_verbosity = match max_verbosity {
+            None => None,
+            Some(x) => match x.trim().parse() {
+                Ok(n) if (0..=4).contains(&n) => Some(n),
+                _ => return Err(format!("Unknown --max-verbosity, expected 0 to 4: '{}'", x).into()),
+            },
+        };
+        Ok(LineFilter {
+            tags: tags.split(",").map(|x| x.trim().to_string()).collect(),
+            min_severity: min_severity.map(Severity::new).transpose()?,
+            max_verbosity,
+        })
+    }
+
+    pub fn has(&self, tag: &str) -> bool {
+        self.tags.iter().any(|x| x == tag)
+    }
+
+    pub fn keep(&self, log: &LogLine) -> bool {
+        if let Some(min) = &self.min_severity {
+            if log.glog_severity.rank() < min.rank() {
+                return false
+            }
+        }
+        match (self.max_verbosity, log.verbosity) {
+            (Some(max), Some(x)) => x <= max,
+            _ => true,
+        }
+    }
+}
+
+pub(crate) fn scrape_log(
+        input: &str,
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        follow: bool,
+        writer: &mut Box<dyn LogWriter>,
         ) -> SResult<()> {
 
     let query_operations = vec!("SELECT", "WITH");
@@ -1304,7 +1856,6 @@ pub fn transform_logs(
     let file = fs::File::open(Path::new(input))?;
     let mut reader = BufReader::new(file);
 
-    let mut writer = new_log_writer(input, filter, output, &output_type, db)?;
     let hostname: Option<String> = match hostname {
         None => None,
         Some(x) => Some(x.to_string())
@@ -1314,16 +1865,19 @@ pub fn transform_logs(
         match entry {
             Err(e) => return Err(Box::new(e)),
             Ok(mut log) => {
+                if !filter.keep(&log) {
+                    continue
+                }
                 // TODO use lifetime to avoid copying these for every line
                 log.hostname = hostname.clone();
                 log.logfile = Some(input.to_string());
                 
-                if filter.contains(&"sql") {
+                if filter.has("sql") {
                     match log.query {
                         None => (),
                         Some(_) => writer.write(&log)?
                     }
-                } else if filter.contains(&"select") {
+                } else if filter.has("select") {
                     match log.query {
                         None => (),
                         Some(_) => match &log.operation {
@@ -1341,5 +1895,5 @@ pub fn transform_logs(
             },
         }
     };
-    writer.close()
+    Ok(())
 }
@@ -0,0 +1,382 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use chrono::NaiveDateTime;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::calls::call_window;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lock {
+    // Schema or Data
+    pub lock_type: String,
+    // Read or Write
+    pub mode: String,
+    pub table: String,
+}
+
+// LockMgr.h:113 Acquiring Table Schema Read Lock for table: omnisci_states
+// LockMgr.h:207 Acquiring Table Data Write Lock for table: flights
+pub fn parse_lock(msg: &str) -> Option<Lock> {
+    lazy_static! {
+        static ref RE_LOCK: regex::Regex = regex::Regex::new(
+            r"^Acquiring Table ([A-Za-z]+) (Read|Write) Lock for table: (.+)$").unwrap();
+    }
+    let caps = RE_LOCK.captures(msg.trim())?;
+    Some(Lock {
+        lock_type: caps[1].to_string(),
+        mode: caps[2].to_string(),
+        table: caps[3].to_string(),
+    })
+}
+
+// One row of the locks report. Each category uses the same columns:
+// table with the acquisitions of each table from the first to the last,
+// blocked for a read lock acquired while another call held a write lock on the table,
+// with the writer and how long until its call ended,
+// and wait with the time of a call from its last lock to its first kernel launch.
+#[derive(Serialize, Debug, Clone)]
+pub struct LockRow {
+    // table, blocked or wait
+    pub category: String,
+    pub time: String,
+    pub end: String,
+    pub hostname: String,
+    pub pid: i32,
+    pub threadid: Option<i32>,
+    pub table: String,
+    // like Data Read
+    pub lock: String,
+    pub count: Option<u64>,
+    pub reads: Option<u64>,
+    pub writes: Option<u64>,
+    pub wait_ms: Option<i64>,
+    pub sequence: Option<i32>,
+    pub session: String,
+    pub username: String,
+    pub total_time: Option<i32>,
+    pub query: String,
+    // blocked
+    pub writer_sequence: Option<i32>,
+    pub writer_session: String,
+    pub writer_username: String,
+    pub writer_query: String,
+}
+
+impl LockRow {
+    fn new(category: &str, time: &NaiveDateTime, end: &NaiveDateTime, hostname: &str, pid: i32) -> LockRow {
+        LockRow {
+            category: category.to_string(),
+            time: time.format(TIME_FORMAT).to_string(),
+            end: end.format(TIME_FORMAT).to_string(),
+            hostname: hostname.to_string(),
+            pid,
+            threadid: None,
+            table: String::from(""),
+            lock: String::from(""),
+            count: None,
+            reads: None,
+            writes: None,
+            wait_ms: None,
+            sequence: None,
+            session: String::from(""),
+            username: String::from(""),
+            total_time: None,
+            query: String::from(""),
+            writer_sequence: None,
+            writer_session: String::from(""),
+            writer_username: String::from(""),
+            writer_query: String::from(""),
+        }
+    }
+
+    fn with_call(mut self, call: &Call) -> LockRow {
+        self.threadid = call.threadid;
+        self.sequence = call.sequence;
+        self.session = call.session.clone();
+        self.username = call.username.clone();
+        self.total_time = call.total_time;
+        self.query = call.query.clone();
+        self
+    }
+}
+
+// The locks one thread acquired for a call, and what the stdlog of the call says
+#[derive(Default)]
+struct Call {
+    hostname: String,
+    pid: i32,
+    threadid: Option<i32>,
+    locks: Vec<(NaiveDateTime, Lock)>,
+    launch: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    sequence: Option<i32>,
+    session: String,
+    username: String,
+    total_time: Option<i32>,
+    query: String,
+}
+
+// (hostname, pid, threadid)
+type Thread = (String, i32, Option<i32>);
+
+// (time, index of the call, index of the lock)
+type ReadLock = (NaiveDateTime, usize, usize);
+
+#[derive(Default)]
+struct Table {
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    count: u64,
+    reads: u64,
+    writes: u64,
+}
+
+// Builds the locks report from the table_lock lines, which are DEBUG1.
+// The locks of a thread are of the stdlog call that ends next on its pid, within the total_time of the call.
+// The query of a call is dispatched to an executor thread, which launches the kernels:
+//   QueryDispatchQueue.h:49 Dispatching query with 0 queries in the queue.
+//   QueryDispatchQueue.h:80 Running query and returning control. There are now 0 queries in the queue.
+//   Execute.cpp:2078 Launching 1 kernels for query.
+pub struct LockWriter {
+    options: ReportOptions,
+    calls: Vec<Call>,
+    // (hostname, pid, threadid) -> index of the call its locks are for, until the call ends
+    open: HashMap<Thread, usize>,
+    // (hostname, pid) -> indexes of the calls dispatched and not running yet
+    queues: HashMap<(String, i32), VecDeque<usize>>,
+    // (hostname, pid, threadid of the executor) -> index of the call it runs
+    executors: HashMap<Thread, usize>,
+}
+
+impl LockWriter {
+    pub fn new(options: ReportOptions) -> LockWriter {
+        LockWriter {
+            options,
+            calls: vec![],
+            open: HashMap::new(),
+            queues: HashMap::new(),
+            executors: HashMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<LockRow> {
+        let mut rows = self.table_rows();
+        rows.append(&mut self.blocked_rows());
+        rows.append(&mut self.wait_rows());
+        rows
+    }
+
+    // the most locked tables first
+    fn table_rows(&self) -> Vec<LockRow> {
+        let mut tables: BTreeMap<(String, i32, String), Table> = BTreeMap::new();
+        for call in &self.calls {
+            for (time, lock) in &call.locks {
+                let table = tables.entry((call.hostname.clone(), call.pid, lock.table.clone())).or_default();
+                table.first = Some(table.first.map_or(*time, |x| x.min(*time)));
+                table.last = Some(table.last.map_or(*time, |x| x.max(*time)));
+                table.count += 1;
+                match lock.mode.as_str() {
+                    "Write" => table.writes += 1,
+                    _ => table.reads += 1,
+                }
+            }
+        }
+        let mut tables: Vec<((String, i32, String), Table)> = tables.into_iter().collect();
+        tables.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(&b.0)));
+        tables.into_iter().filter_map(|((hostname, pid, name), table)| {
+            let (first, last) = (table.first?, table.last?);
+            let mut row = LockRow::new("table", &first, &last, &hostname, pid);
+            row.table = name;
+            row.count = Some(table.count);
+            row.reads = Some(table.reads);
+            row.writes = Some(table.writes);
+            Some(row)
+        }).collect()
+    }
+
+    // the longest waits first
+    fn blocked_rows(&self) -> Vec<LockRow> {
+        // (hostname, pid, table) -> the read locks, by time
+        let mut reads: HashMap<(&str, i32, &str), Vec<ReadLock>> = HashMap::new();
+        for (r, reader) in self.calls.iter().enumerate() {
+            for (l, (time, read)) in reader.locks.iter().enumerate().filter(|(_, (_, x))| x.mode == "Read") {
+                reads.entry((&reader.hostname, reader.pid, &read.table)).or_default().push((*time, r, l));
+            }
+        }
+        for x in reads.values_mut() {
+            x.sort();
+        }
+
+        let mut rows = vec![];
+        for (w, writer) in self.calls.iter().enumerate() {
+            let end = match writer.end {
+                Some(x) => x,
+                None => continue,
+            };
+            for (begin, write) in writer.locks.iter().filter(|(_, x)| x.mode == "Write") {
+                let locks = match reads.get(&(writer.hostname.as_str(), writer.pid, write.table.as_str())) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let first = locks.partition_point(|(time, _, _)| time < begin);
+                for (time, r, l) in locks[first..].iter().take_while(|(time, _, _)| *time <= end) {
+                    if *r == w {
+                        continue
+                    }
+                    let reader = &self.calls[*r];
+                    let read = &reader.locks[*l].1;
+                    let mut row = LockRow::new("blocked", time, &end, &reader.hostname, reader.pid).with_call(reader);
+                    row.table = read.table.clone();
+                    row.lock = format!("{} {}", read.lock_type, read.mode);
+                    row.wait_ms = Some((end - *time).num_milliseconds());
+                    row.writer_sequence = writer.sequence;
+                    row.writer_session = writer.session.clone();
+                    row.writer_username = writer.username.clone();
+                    row.writer_query = writer.query.clone();
+                    rows.push(row);
+                }
+            }
+        }
+        rows.sort_by(|a, b| b.wait_ms.cmp(&a.wait_ms).then(a.time.cmp(&b.time)));
+        rows
+    }
+
+    // the longest waits first
+    fn wait_rows(&self) -> Vec<LockRow> {
+        let mut rows = vec![];
+        for call in &self.calls {
+            let launch = match call.launch {
+                Some(x) => x,
+                None => continue,
+            };
+            let last = match call.locks.iter().map(|(time, _)| *time).filter(|x| *x <= launch).max() {
+                Some(x) => x,
+                None => continue,
+            };
+            let mut tables: Vec<&str> = vec![];
+            for (_, lock) in &call.locks {
+                if !tables.contains(&lock.table.as_str()) {
+                    tables.push(&lock.table);
+                }
+            }
+            let mut row = LockRow::new("wait", &last, &launch, &call.hostname, call.pid).with_call(call);
+            row.table = tables.join(",");
+            row.wait_ms = Some((launch - last).num_milliseconds());
+            rows.push(row);
+        }
+        rows.sort_by(|a, b| b.wait_ms.cmp(&a.wait_ms).then(a.time.cmp(&b.time)));
+        rows
+    }
+
+    fn lock(&mut self, log: &LogLine, lock: Lock) {
+        let key = (log.hostname.clone().unwrap_or_default(), log.pid, log.threadid);
+        let index = match self.open.get(&key) {
+            Some(i) => *i,
+            None => {
+                self.calls.push(Call {
+                    hostname: key.0.clone(),
+                    pid: log.pid,
+                    threadid: log.threadid,
+                    ..Default::default()
+                });
+                self.open.insert(key, self.calls.len() - 1);
+                self.calls.len() - 1
+            },
+        };
+        self.calls[index].locks.push((log.logtime, lock));
+    }
+
+    // the call of the locks ended
+    fn call(&mut self, log: &LogLine) {
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let (begin, end) = call_window(log);
+        let calls = &self.calls;
+        let threads: Vec<(Thread, NaiveDateTime)> = self.open.iter()
+            .filter(|((h, pid, threadid), _)| *h == hostname && *pid == log.pid
+                && (log.threadid.is_none() || *threadid == log.threadid))
+            .map(|(key, i)| (key.clone(), calls[*i].locks[0].0))
+            .collect();
+        let key = threads.iter()
+            .filter(|(_, first)| *first >= begin && *first <= end)
+            .min_by_key(|(_, first)| *first)
+            .map(|(key, _)| key.clone());
+        if let Some(i) = key.and_then(|x| self.open.remove(&x)) {
+            let call = &mut self.calls[i];
+            call.end = Some(log.logtime);
+            call.sequence = log.sequence;
+            call.session = log.session.clone().unwrap_or_default();
+            call.username = log.username.clone().unwrap_or_default();
+            call.total_time = log.total_time.or(log.dur_ms);
+            call.query = log.query.clone().unwrap_or_default();
+        }
+        // the locks from before this call had their stdlog outside its window, or none,
+        // so the next locks of their thread are of a new call
+        for (key, first) in threads {
+            if first < begin {
+                self.open.remove(&key);
+            }
+        }
+    }
+}
+
+impl LogWriter for LockWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let key = (hostname.clone(), log.pid, log.threadid);
+        let msg = log.msg.trim();
+        if log.event.as_deref() == Some("table_lock") {
+            if let Some(lock) = parse_lock(msg) {
+                self.lock(log, lock);
+            }
+        } else if let (Some(event), Some(_)) = (&log.event, log.sequence) {
+            if !event.ends_with("_begin") {
+                self.call(log);
+            }
+        } else if msg.starts_with("Dispatching query with ") {
+            if let Some(i) = self.open.get(&key) {
+                self.queues.entry((hostname, log.pid)).or_default().push_back(*i);
+            }
+        } else if msg.contains("unning query and returning control") {
+            if let Some(i) = self.queues.get_mut(&(hostname, log.pid)).and_then(|x| x.pop_front()) {
+                self.executors.insert(key, i);
+            }
+        } else if msg.starts_with("Launching ") && msg.ends_with(" kernels for query.") {
+            // without a dispatch queue, the thread of the call runs the query
+            if let Some(i) = self.executors.get(&key).or_else(|| self.open.get(&key)).copied() {
+                let call = &mut self.calls[i];
+                if call.launch.is_none() {
+                    call.launch = Some(log.logtime);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
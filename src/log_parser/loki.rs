@@ -0,0 +1,115 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+
+use serde_json::{json, Value};
+
+use super::forward::{unix_nanos, Forwarder};
+use super::http;
+use super::lineparser::{LogLine, LogWriter, SResult};
+
+
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:3100/loki/api/v1/push";
+
+type Labels = BTreeMap<&'static str, String>;
+
+struct Entry {
+    labels: Labels,
+    logtime: NaiveDateTime,
+    line: String,
+}
+
+// labels of the stream of a line, only the ones with a value
+fn labels(log: &LogLine) -> Labels {
+    let mut labels = Labels::new();
+    labels.insert("job", String::from("omnisci"));
+    labels.insert("severity", log.severity.to_string());
+    for (name, value) in [
+        ("hostname", &log.hostname),
+        ("event", &log.event),
+        ("dbname", &log.dbname),
+    ] {
+        if let Some(value) = value {
+            if !value.is_empty() {
+                labels.insert(name, value.to_string());
+            }
+        }
+    }
+    labels
+}
+
+// the body of a Loki push request, a stream per set of labels
+fn push_request(batch: &[Entry]) -> Value {
+    let mut streams: BTreeMap<&Labels, Vec<Value>> = BTreeMap::new();
+    for entry in batch {
+        streams.entry(&entry.labels).or_default().push(json!([unix_nanos(&entry.logtime), entry.line]));
+    }
+    let streams: Vec<Value> = streams.into_iter().map(|(labels, values)| json!({"stream": labels, "values": values})).collect();
+    json!({"streams": streams})
+}
+
+// Pushes lines to Grafana Loki, labeled by hostname, severity, event and dbname.
+// The line is the query of a stdlog, otherwise the msg.
+pub struct LokiWriter {
+    forwarder: Forwarder<Entry>,
+}
+
+impl LokiWriter {
+    pub fn new(url: &str) -> LokiWriter {
+        let url = url.to_string();
+        LokiWriter {
+            forwarder: Forwarder::new(move |batch: &[Entry]| {
+                match http::post(&url, "application/json", push_request(batch).to_string().as_bytes()) {
+                    // Loki answers 400 to entries older than reject_old_samples_max_age or out of order,
+                    // which sending again would not fix, and the lines after them are still wanted
+                    Err(e) if http::status(e.as_ref()) == Some(400) => {
+                        eprintln!("Loki dropped a batch of {} lines: {}", batch.len(), e);
+                        Ok(())
+                    },
+                    x => x,
+                }
+            }),
+        }
+    }
+}
+
+impl LogWriter for LokiWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        // the version lines have their msg in msg_norm, and the stdlog calls without a query only their event
+        let line = [log.query.as_deref(), Some(log.msg.as_str()), log.msg_norm.as_deref(), log.event.as_deref()]
+            .iter()
+            .flatten()
+            .find(|x| !x.is_empty())
+            .map(|x| x.to_string());
+        // Loki rejects empty lines
+        let line = match line {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        self.forwarder.push(Entry {
+            labels: labels(log),
+            logtime: log.logtime,
+            line,
+        })
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.forwarder.close()
+    }
+}
@@ -0,0 +1,275 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::calls::CallLines;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TimeBucket, TIME_FORMAT};
+
+
+// The numbers of a buffer manager allocation line
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Allocation {
+    // GPU or CPU
+    pub device: String,
+    pub device_id: Option<i32>,
+    // of the slab, or requested
+    pub bytes: Option<i64>,
+    pub pages: Option<i64>,
+    pub ms: Option<i64>,
+}
+
+impl Allocation {
+    // GPU 1, or GPU when the line has no id
+    pub fn device_name(&self) -> String {
+        match self.device_id {
+            Some(id) => format!("{} {}", self.device, id),
+            None => self.device.to_string(),
+        }
+    }
+}
+
+// The lines of BufferMgr.cpp, and the out of memory lines of the executor:
+//   ALLOCATION slab of 8388608 pages (4294967296B) created in 5 ms GPU_MGR:1
+//   ALLOCATION Attempted slab of 8388608 pages (4294967296B) failed GPU_MGR:0
+//   ALLOCATION failed to find 16777216B free. Forcing Eviction. Eviction start 0 Number pages requested 32768 Best Eviction Start Slab 0 GPU_MGR:0
+//   Caught an out-of-gpu-memory error
+//   Query ran out of GPU memory, attempting punt to CPU
+pub fn parse_allocation(msg: &str) -> Option<Allocation> {
+    lazy_static! {
+        static ref RE_SLAB: regex::Regex = regex::Regex::new(
+            r"^ALLOCATION (?:Attempted )?slab of ([0-9]+) pages \(([0-9]+)B\)(?: created in ([0-9]+) ms)?").unwrap();
+        static ref RE_FIND: regex::Regex = regex::Regex::new(
+            r"^ALLOCATION failed to find ([0-9]+)B free\..*Number pages requested ([0-9]+)").unwrap();
+        static ref RE_MGR: regex::Regex = regex::Regex::new(r" (GPU|CPU)_MGR:([0-9]+)$").unwrap();
+    }
+    let mut allocation = Allocation {
+        device: String::from("GPU"),
+        device_id: None,
+        bytes: None,
+        pages: None,
+        ms: None,
+    };
+    if let Some(caps) = RE_SLAB.captures(msg) {
+        allocation.pages = caps[1].parse().ok();
+        allocation.bytes = caps[2].parse().ok();
+        allocation.ms = caps.get(3).and_then(|x| x.as_str().parse().ok());
+    } else if let Some(caps) = RE_FIND.captures(msg) {
+        allocation.bytes = caps[1].parse().ok();
+        allocation.pages = caps[2].parse().ok();
+    } else if !msg.starts_with("Caught an out-of-gpu-memory error")
+        && !msg.starts_with("Query ran out of GPU memory") {
+        return None
+    }
+    if let Some(caps) = RE_MGR.captures(msg.trim_end()) {
+        allocation.device = caps[1].to_string();
+        allocation.device_id = caps[2].parse().ok();
+    }
+    Some(allocation)
+}
+
+// One row of the memory report. Each category uses the same columns:
+// allocation and failure in time order, with the slab bytes of the device after it,
+// punt with the call that ran out of GPU memory,
+// and peak per device and --bucket, with the most slab bytes the device had.
+#[derive(Serialize, Debug, Clone)]
+pub struct MemoryRow {
+    // allocation, failure, punt or peak
+    pub category: String,
+    pub time: String,
+    pub hostname: String,
+    pub pid: i32,
+    pub device: String,
+    pub event: String,
+    pub bytes: Option<i64>,
+    pub pages: Option<i64>,
+    pub ms: Option<i64>,
+    pub allocated_bytes: Option<i64>,
+    // peak
+    pub peak_bytes: Option<i64>,
+    pub allocations: Option<u64>,
+    pub failures: Option<u64>,
+    // punt
+    pub sequence: Option<i32>,
+    pub session: String,
+    pub username: String,
+    pub dbname: String,
+    pub execution_time: Option<i32>,
+    pub query: String,
+}
+
+impl MemoryRow {
+    fn new(category: &str, time: String, hostname: &str, pid: i32, device: &str) -> MemoryRow {
+        MemoryRow {
+            category: category.to_string(),
+            time,
+            hostname: hostname.to_string(),
+            pid,
+            device: device.to_string(),
+            event: String::from(""),
+            bytes: None,
+            pages: None,
+            ms: None,
+            allocated_bytes: None,
+            peak_bytes: None,
+            allocations: None,
+            failures: None,
+            sequence: None,
+            session: String::from(""),
+            username: String::from(""),
+            dbname: String::from(""),
+            execution_time: None,
+            query: String::from(""),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Peak {
+    allocated_bytes: i64,
+    peak_bytes: i64,
+    allocations: u64,
+    failures: u64,
+}
+
+// Builds the memory report from the allocation column.
+// The slab bytes of a device add up until a clear_gpu_memory or clear_cpu_memory call, or a restart.
+pub struct MemoryWriter {
+    options: ReportOptions,
+    // in log order
+    timeline: Vec<(NaiveDateTime, MemoryRow)>,
+    // indexes in the timeline of the punts without a call yet
+    punts: CallLines<usize>,
+    // (hostname, pid, device) -> slab bytes
+    allocated: HashMap<(String, i32, String), i64>,
+    // (bucket, hostname, pid, device)
+    peaks: BTreeMap<(String, String, i32, String), Peak>,
+}
+
+impl MemoryWriter {
+    pub fn new(options: ReportOptions) -> MemoryWriter {
+        MemoryWriter {
+            options,
+            timeline: vec![],
+            punts: CallLines::new(),
+            allocated: HashMap::new(),
+            peaks: BTreeMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<MemoryRow> {
+        let mut rows: Vec<MemoryRow> = self.timeline.iter().map(|(_, row)| row.clone()).collect();
+        for ((bucket, hostname, pid, device), peak) in &self.peaks {
+            let mut row = MemoryRow::new("peak", bucket.to_string(), hostname, *pid, device);
+            row.allocated_bytes = Some(peak.allocated_bytes);
+            row.peak_bytes = Some(peak.peak_bytes);
+            row.allocations = Some(peak.allocations);
+            row.failures = Some(peak.failures);
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn allocation(&mut self, log: &LogLine, allocation: &Allocation) {
+        let event = log.event.as_deref().unwrap_or("");
+        let category = match event {
+            "memory_slab" => "allocation",
+            "memory_punt_to_cpu" => "punt",
+            _ => "failure",
+        };
+        let device = allocation.device_name();
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let mut row = MemoryRow::new(category, log.logtime.format(TIME_FORMAT).to_string(), &hostname, log.pid, &device);
+        row.event = event.to_string();
+        row.bytes = allocation.bytes;
+        row.pages = allocation.pages;
+        row.ms = allocation.ms;
+        if category == "punt" {
+            self.punts.push(log, self.timeline.len());
+            self.timeline.push((log.logtime, row));
+            return
+        }
+
+        let key = (hostname.clone(), log.pid, device.clone());
+        let before = *self.allocated.get(&key).unwrap_or(&0);
+        let after = if category == "allocation" { before + allocation.bytes.unwrap_or(0) } else { before };
+        self.allocated.insert(key, after);
+        row.allocated_bytes = Some(after);
+
+        let bucket = self.options.bucket.clone().unwrap_or(TimeBucket::Hour);
+        let peak = self.peaks.entry((bucket.format(&log.logtime), hostname, log.pid, device)).or_default();
+        peak.allocated_bytes = after;
+        // what the device had from the previous bucket counts too
+        peak.peak_bytes = peak.peak_bytes.max(before).max(after);
+        match category {
+            "allocation" => peak.allocations += 1,
+            _ => peak.failures += 1,
+        }
+        self.timeline.push((log.logtime, row));
+    }
+
+    // the punts during the call, which ended at this line
+    fn call(&mut self, log: &LogLine) {
+        for i in self.punts.take(log, |_| true) {
+            let row = &mut self.timeline[i].1;
+            row.sequence = log.sequence;
+            row.session = log.session.clone().unwrap_or_default();
+            row.username = log.username.clone().unwrap_or_default();
+            row.dbname = log.dbname.clone().unwrap_or_default();
+            row.execution_time = log.execution_time;
+            row.query = log.query.clone().unwrap_or_default();
+        }
+        // the punts too old for a call stay without one
+        self.punts.expire();
+    }
+
+    fn clear(&mut self, log: &LogLine, device: Option<&str>) {
+        let hostname = log.hostname.clone().unwrap_or_default();
+        self.allocated.retain(|(h, pid, name), _| {
+            let cleared = *h == hostname && *pid == log.pid && device.iter().all(|x| name.starts_with(x));
+            !cleared
+        });
+    }
+}
+
+impl LogWriter for MemoryWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        if let Some(allocation) = &log.memory {
+            self.allocation(log, allocation);
+            return Ok(())
+        }
+        match (log.event.as_deref(), log.sequence) {
+            (Some("version"), _) => self.clear(log, None),
+            (Some("clear_gpu_memory"), Some(_)) => self.clear(log, Some("GPU")),
+            (Some("clear_cpu_memory"), Some(_)) => self.clear(log, Some("CPU")),
+            (Some("sql_execute"), Some(_)) | (Some("render_vega"), Some(_)) => self.call(log),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::NaiveDateTime;
+
+use super::calls::{server_of, ExpireTick, MAX_CALL_SECONDS};
+use super::http::TIMEOUT;
+use super::lineparser::{scrape_log, LineFilter, LogLine, LogWriter, SResult, Severity};
+
+
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:9184";
+
+// upper bounds of the histogram buckets, in seconds
+const BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0];
+
+struct Histogram {
+    // not cumulative, the last is +Inf
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        Histogram {
+            counts: vec![0; BUCKETS.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, seconds: f64) {
+        let i = BUCKETS.iter().position(|x| seconds <= *x).unwrap_or(BUCKETS.len());
+        self.counts[i] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+        let mut cumulative = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            let le = match BUCKETS.get(i) {
+                Some(x) => x.to_string(),
+                None => String::from("+Inf"),
+            };
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn label(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace('\n', r"\n")
+}
+
+// Prometheus metrics of the lines seen so far
+pub struct Metrics {
+    // (event, severity, operation, dbname) -> lines
+    lines: BTreeMap<(String, String, String, String), u64>,
+    execution_time: Histogram,
+    total_time: Histogram,
+    memory_allocation_failed: u64,
+    memory_punt_to_cpu: u64,
+    login_failures: u64,
+    // (server, pid, sequence) -> the begin of the calls with a stdlog_begin and no stdlog yet
+    in_flight: HashMap<(String, i32, i32), NaiveDateTime>,
+    // server -> pid, a new pid is a restarted server, and its calls never end
+    pids: HashMap<String, i32>,
+    expire: ExpireTick,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            lines: BTreeMap::new(),
+            execution_time: Histogram::new(),
+            total_time: Histogram::new(),
+            memory_allocation_failed: 0,
+            memory_punt_to_cpu: 0,
+            login_failures: 0,
+            in_flight: HashMap::new(),
+            pids: HashMap::new(),
+            expire: ExpireTick::default(),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    // text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        header(&mut out, "omnisci_log_lines_total", "Log lines by event, severity, operation and dbname", "counter");
+        for ((event, severity, operation, dbname), count) in &self.lines {
+            let _ = writeln!(out, "omnisci_log_lines_total{{event=\"{}\",severity=\"{}\",operation=\"{}\",dbname=\"{}\"}} {}",
+                label(event), label(severity), label(operation), label(dbname), count);
+        }
+        self.execution_time.render(&mut out, "omnisci_execution_time_seconds", "execution_time_ms of the calls");
+        self.total_time.render(&mut out, "omnisci_total_time_seconds", "total_time_ms of the calls");
+        for (name, help, count) in [
+            ("omnisci_memory_allocation_failed_total", "GPU memory allocations that failed", self.memory_allocation_failed),
+            ("omnisci_memory_punt_to_cpu_total", "Queries punted to CPU, out of GPU memory", self.memory_punt_to_cpu),
+            ("omnisci_login_failures_total", "Failed logins and authentications", self.login_failures),
+        ] {
+            header(&mut out, name, help, "counter");
+            let _ = writeln!(out, "{} {}", name, count);
+        }
+        header(&mut out, "omnisci_in_flight_calls", "Calls begun and not ended yet", "gauge");
+        let _ = writeln!(out, "omnisci_in_flight_calls {}", self.in_flight());
+        out
+    }
+}
+
+impl LogWriter for Metrics {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let value = |x: &Option<String>| x.clone().unwrap_or_default();
+        // the input file or --hostname, so servers with the same pid are apart
+        let server = server_of(log).unwrap_or_default();
+        let event = value(&log.event);
+
+        if !server.is_empty() {
+            match self.pids.insert(server.clone(), log.pid) {
+                Some(pid) if pid != log.pid => self.in_flight.retain(|x, _| x.0 != server || x.1 != pid),
+                _ => (),
+            }
+        }
+        // calls whose stdlog never came
+        if let Some(latest) = self.expire.tick(log.logtime) {
+            let begun = latest - chrono::Duration::seconds(MAX_CALL_SECONDS);
+            self.in_flight.retain(|_, x| *x >= begun);
+        }
+
+        let key = (event.clone(), log.severity.to_string(), value(&log.operation), value(&log.dbname));
+        *self.lines.entry(key).or_insert(0) += 1;
+
+        match event.as_str() {
+            "memory_allocation_failed" => self.memory_allocation_failed += 1,
+            "memory_punt_to_cpu" => self.memory_punt_to_cpu += 1,
+            "login_failure" | "authentication_failure" => if let Severity::AUTH = log.severity {
+                self.login_failures += 1
+            },
+            _ => (),
+        }
+
+        if let Some(sequence) = log.sequence {
+            let call = (server, log.pid, sequence);
+            if event.ends_with("_begin") {
+                self.in_flight.insert(call, log.logtime);
+            } else {
+                self.in_flight.remove(&call);
+                if let Some(x) = log.execution_time {
+                    self.execution_time.observe(x as f64 / 1000.0);
+                }
+                if let Some(x) = log.total_time {
+                    self.total_time.observe(x as f64 / 1000.0);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Adds the lines to metrics shared with the server
+struct MetricsWriter {
+    metrics: Arc<Mutex<Metrics>>,
+}
+
+impl LogWriter for MetricsWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        match self.metrics.lock() {
+            Ok(mut metrics) => metrics.write(log),
+            Err(e) => Err(format!("metrics lock poisoned, {}", e).into()),
+        }
+    }
+}
+
+fn respond(stream: TcpStream, metrics: &Arc<Mutex<Metrics>>) -> SResult<()> {
+    // a client that never sends its request only holds its own thread
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // skip the headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break
+        }
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if !request.starts_with("GET ") {
+        ("405 Method Not Allowed", String::from("only GET\n"))
+    } else if path == "/metrics" || path.starts_with("/metrics?") {
+        match metrics.lock() {
+            Ok(metrics) => ("200 OK", metrics.render()),
+            Err(e) => return Err(format!("metrics lock poisoned, {}", e).into()),
+        }
+    } else {
+        ("404 Not Found", String::from("see /metrics\n"))
+    };
+    let mut stream = reader.into_inner();
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body)?;
+    stream.flush()?;
+    Ok(())
+}
+
+// Serves GET /metrics, each connection on its own thread, forever
+pub fn serve(listener: TcpListener, metrics: Arc<Mutex<Metrics>>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let metrics = metrics.clone();
+                thread::spawn(move || {
+                    if let Err(e) = respond(stream, &metrics) {
+                        eprintln!("metrics: {}", e);
+                    }
+                });
+            },
+            Err(e) => eprintln!("metrics: {}", e),
+        }
+    }
+}
+
+// Daemon that follows the logs, and serves their metrics on http://LISTEN/metrics
+pub fn serve_metrics(
+        inputs: &[String],
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        listen: &str,
+        ) -> SResult<()> {
+
+    let metrics = Arc::new(Mutex::new(Metrics::new()));
+    let listener = TcpListener::bind(listen)?;
+    {
+        let metrics = metrics.clone();
+        thread::spawn(move || serve(listener, metrics));
+    }
+
+    // each input is followed forever, so each needs a thread
+    let readers: Vec<thread::JoinHandle<Result<(), String>>> = inputs.iter().map(|input| {
+        let input = input.to_string();
+        let filter = filter.clone();
+        let hostname = hostname.map(|x| x.to_string());
+        let metrics = metrics.clone();
+        thread::spawn(move || {
+            let mut writer: Box<dyn LogWriter> = Box::new(MetricsWriter { metrics });
+            scrape_log(&input, &filter, hostname.as_deref(), true, &mut writer).map_err(|e| format!("{}: {}", input, e))
+        })
+    }).collect();
+    for reader in readers {
+        match reader.join() {
+            Ok(result) => result?,
+            Err(_) => panic!("metrics reader panicked"),
+        }
+    }
+    Ok(())
+}
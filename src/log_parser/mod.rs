@@ -15,5 +15,37 @@
  */
  
 pub mod lineparser;
+pub mod fingerprint;
+pub mod report;
+pub mod calls;
+pub mod digest;
+pub mod stats;
+pub mod errors;
+pub mod templates;
+pub mod sessions;
+pub mod audit;
+pub mod redact;
+pub mod browser;
+pub mod top;
+pub mod metrics;
+pub mod http;
+pub mod otlp;
+pub mod chrome_trace;
+pub mod es;
+pub mod forward;
+pub mod loki;
+pub mod syslog;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod timers;
+pub mod qmd;
+pub mod plan;
+pub mod config;
+pub mod hardware;
+pub mod memory;
+pub mod locks;
+pub mod tables;
+pub mod vega;
 
 pub use self::lineparser::*;
+pub use self::report::*;
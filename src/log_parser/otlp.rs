@@ -0,0 +1,325 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::Write;
+
+use chrono::{Duration, NaiveDateTime};
+
+use serde_json::{json, Value};
+
+use sha2::{Digest, Sha256};
+
+use super::calls::{ExpireTick, MAX_CALL_SECONDS};
+use super::forward::{rejected, unix_nanos, Forwarder};
+use super::http;
+use super::lineparser::{LogLine, LogWriter, SResult, Severity};
+
+// SpanKind in the OTLP protobuf
+const SPAN_KIND_INTERNAL: i32 = 1;
+const SPAN_KIND_SERVER: i32 = 2;
+// StatusCode
+const STATUS_OK: i32 = 1;
+const STATUS_ERROR: i32 = 2;
+
+// (hostname, pid, sequence) of a stdlog call
+type CallKey = (String, i32, i32);
+
+struct Span {
+    hostname: String,
+    trace_id: String,
+    span_id: String,
+    parent_span_id: String,
+    name: String,
+    kind: i32,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    attributes: Vec<Value>,
+    error: Option<String>,
+}
+
+// a stdlog_begin without its stdlog yet
+struct Call {
+    begin: NaiveDateTime,
+    // sub-events on the same thread, without ids until the call ends
+    children: Vec<Span>,
+    error: Option<String>,
+}
+
+fn hex_hash(parts: &[&str], bytes: usize) -> String {
+    let hash = Sha256::digest(parts.join("\u{1f}").as_bytes());
+    hash[..bytes].iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+fn string_attribute(key: &str, value: &str) -> Value {
+    json!({"key": key, "value": {"stringValue": value}})
+}
+
+// int64 is a string in OTLP JSON
+fn int_attribute(key: &str, value: i32) -> Value {
+    json!({"key": key, "value": {"intValue": value.to_string()}})
+}
+
+fn attributes(log: &LogLine) -> Vec<Value> {
+    let mut attributes = vec![string_attribute("db.system", "omnisci")];
+    for (key, value) in [
+        ("db.name", &log.dbname),
+        ("db.user", &log.username),
+        ("db.statement", &log.query),
+        ("db.operation", &log.operation),
+        ("omnisci.event", &log.event),
+        ("omnisci.session", &log.session),
+        ("omnisci.client", &log.client),
+        ("omnisci.dashboardid", &log.dashboardid),
+        ("omnisci.chartid", &log.chartid),
+    ] {
+        if let Some(value) = value {
+            attributes.push(string_attribute(key, value));
+        }
+    }
+    for (key, value) in [
+        ("omnisci.pid", Some(log.pid)),
+        ("omnisci.threadid", log.threadid),
+        ("omnisci.queryid", log.queryid),
+        ("omnisci.sequence", log.sequence),
+        ("omnisci.execution_time_ms", log.execution_time),
+        ("omnisci.total_time_ms", log.total_time),
+    ] {
+        if let Some(value) = value {
+            attributes.push(int_attribute(key, value));
+        }
+    }
+    attributes
+}
+
+pub enum OtlpSink {
+    // one ExportTraceServiceRequest per line
+    File(Box<dyn Write + Send>),
+    // OTLP/HTTP with JSON encoding, like http://localhost:4318/v1/traces, retried, see forward.rs
+    Http(String),
+}
+
+// the OTLP JSON of a batch of ended spans, a resource per hostname
+fn request(spans: &[Span]) -> Value {
+    let mut hosts: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+    for span in spans {
+        let status = match &span.error {
+            Some(message) => json!({"code": STATUS_ERROR, "message": message}),
+            None => json!({"code": STATUS_OK}),
+        };
+        hosts.entry(&span.hostname).or_default().push(json!({
+            "traceId": span.trace_id,
+            "spanId": span.span_id,
+            "parentSpanId": span.parent_span_id,
+            "name": span.name,
+            "kind": span.kind,
+            "startTimeUnixNano": unix_nanos(&span.start),
+            "endTimeUnixNano": unix_nanos(&span.end),
+            "attributes": span.attributes,
+            "status": status,
+        }));
+    }
+    let resource_spans: Vec<Value> = hosts.into_iter().map(|(hostname, spans)| {
+        let mut resource = vec![string_attribute("service.name", "omnisci")];
+        if !hostname.is_empty() {
+            resource.push(string_attribute("host.name", hostname));
+        }
+        json!({
+            "resource": {"attributes": resource},
+            "scopeSpans": [{
+                "scope": {"name": "omnisci-log-scraper", "version": env!("CARGO_PKG_VERSION")},
+                "spans": spans,
+            }],
+        })
+    }).collect();
+    json!({"resourceSpans": resource_spans})
+}
+
+// Each stdlog call, from stdlog_begin to stdlog, is a span of its own trace,
+// and the events logged meanwhile with its queryid, or else on its thread, like sql_parse, are child spans.
+// The ended spans are sent in batches from another thread, also while a followed log is quiet.
+// A call whose stdlog does not come within MAX_CALL_SECONDS, like of a restarted server, is dropped.
+pub struct OtlpWriter {
+    forwarder: Forwarder<Span>,
+    calls: HashMap<CallKey, Call>,
+    // (hostname, pid, threadid) -> the call running on the thread
+    threads: HashMap<(String, i32, i32), CallKey>,
+    // (hostname, pid, queryid) -> the call of the query, whose lines may be on other threads
+    queries: HashMap<(String, i32, i32), CallKey>,
+    // calls begun without a threadid, and not yet given a thread
+    unthreaded: Vec<CallKey>,
+    expire: ExpireTick,
+}
+
+impl OtlpWriter {
+    pub fn new(sink: OtlpSink) -> OtlpWriter {
+        let forwarder = match sink {
+            OtlpSink::File(mut writer) => Forwarder::new(move |batch: &[Span]| {
+                // writing again would not help, e.g. a closed pipe
+                writeln!(writer, "{}", request(batch)).and_then(|_| writer.flush()).map_err(|e| rejected(e.to_string()))
+            }),
+            OtlpSink::Http(url) => Forwarder::new(move |batch: &[Span]| {
+                http::post(&url, "application/json", request(batch).to_string().as_bytes())
+            }),
+        };
+        OtlpWriter {
+            forwarder,
+            calls: HashMap::new(),
+            threads: HashMap::new(),
+            queries: HashMap::new(),
+            unthreaded: vec![],
+            expire: ExpireTick::default(),
+        }
+    }
+
+    fn end_call(&mut self, key: CallKey, log: &LogLine) -> SResult<()> {
+        self.threads.retain(|_, x| *x != key);
+        self.queries.retain(|_, x| *x != key);
+        self.unthreaded.retain(|x| *x != key);
+        let call = self.calls.remove(&key);
+        let (hostname, pid, sequence) = &key;
+        let trace_id = hex_hash(&[hostname, &pid.to_string(), &sequence.to_string(), log.session.as_deref().unwrap_or("")], 16);
+        let span_id = hex_hash(&[&trace_id, "call"], 8);
+        let start = match (&call, log.dur_ms) {
+            (Some(call), _) => call.begin,
+            (None, Some(dur_ms)) => log.logtime - Duration::milliseconds(dur_ms as i64),
+            (None, None) => log.logtime,
+        };
+        let (children, error) = match call {
+            Some(call) => (call.children, call.error),
+            None => (vec![], None),
+        };
+        self.forwarder.push(Span {
+            hostname: hostname.to_string(),
+            trace_id: trace_id.clone(),
+            span_id: span_id.clone(),
+            parent_span_id: String::from(""),
+            name: log.event.clone().unwrap_or_default(),
+            kind: SPAN_KIND_SERVER,
+            start,
+            end: log.logtime,
+            attributes: attributes(log),
+            error,
+        })?;
+        for (i, child) in children.into_iter().enumerate() {
+            self.forwarder.push(Span {
+                trace_id: trace_id.clone(),
+                span_id: hex_hash(&[&trace_id, &i.to_string()], 8),
+                parent_span_id: span_id.clone(),
+                ..child
+            })?;
+        }
+        Ok(())
+    }
+
+    // the call a line is of, by its queryid, or else by its thread
+    fn call_of(&mut self, log: &LogLine, hostname: &str) -> Option<CallKey> {
+        let query = log.queryid.filter(|x| *x > 0).map(|x| (hostname.to_string(), log.pid, x));
+        if let Some(key) = query.as_ref().and_then(|x| self.queries.get(x)) {
+            return Some(key.clone())
+        }
+        let thread = log.threadid.map(|x| (hostname.to_string(), log.pid, x))?;
+        // older logs have no threadid in stdlog, so a thread belongs to the last call begun without one
+        if !self.threads.contains_key(&thread) {
+            if let Some(key) = self.unthreaded.pop() {
+                self.threads.insert(thread.clone(), key);
+            }
+        }
+        let key = self.threads.get(&thread)?.clone();
+        // the other threads of the query are linked by its queryid
+        if let Some(query) = query {
+            self.queries.entry(query).or_insert_with(|| key.clone());
+        }
+        Some(key)
+    }
+
+    // drops the calls whose stdlog never came
+    fn expire(&mut self, logtime: NaiveDateTime) {
+        let latest = match self.expire.tick(logtime) {
+            Some(x) => x,
+            None => return,
+        };
+        let since = latest - Duration::seconds(MAX_CALL_SECONDS);
+        self.calls.retain(|_, x| x.begin >= since);
+        let calls = &self.calls;
+        self.threads.retain(|_, x| calls.contains_key(x));
+        self.queries.retain(|_, x| calls.contains_key(x));
+        self.unthreaded.retain(|x| calls.contains_key(x));
+    }
+}
+
+impl LogWriter for OtlpWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        self.expire(log.logtime);
+        let hostname = log.hostname.clone().unwrap_or_default();
+        let event = log.event.clone().unwrap_or_default();
+
+        if let Some(sequence) = log.sequence {
+            let key = (hostname.clone(), log.pid, sequence);
+            if !event.ends_with("_begin") {
+                return self.end_call(key, log)
+            }
+            let queryid = log.queryid.filter(|x| *x > 0);
+            self.calls.insert(key.clone(), Call {
+                begin: log.logtime,
+                children: vec![],
+                error: None,
+            });
+            if let Some(queryid) = queryid {
+                self.queries.insert((hostname.clone(), log.pid, queryid), key.clone());
+            }
+            match log.threadid {
+                Some(threadid) => { self.threads.insert((hostname, log.pid, threadid), key); },
+                None => self.unthreaded.push(key),
+            }
+            return Ok(())
+        }
+
+        let key = match self.call_of(log, &hostname) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        if let Some(call) = self.calls.get_mut(&key) {
+            if let Severity::ERROR | Severity::FATAL = log.severity {
+                call.error = Some(log.msg.clone());
+            }
+            if !event.is_empty() {
+                let dur_ms = log.total_time.or(log.dur_ms).unwrap_or(0);
+                let mut attributes = attributes(log);
+                attributes.push(string_attribute("code.filepath", &log.fileline));
+                call.children.push(Span {
+                    hostname,
+                    trace_id: String::from(""),
+                    span_id: String::from(""),
+                    parent_span_id: String::from(""),
+                    name: event,
+                    kind: SPAN_KIND_INTERNAL,
+                    start: log.logtime - Duration::milliseconds(dur_ms as i64),
+                    end: log.logtime,
+                    attributes,
+                    error: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // sends the ended spans, and waits for them
+    fn close(&mut self) -> SResult<()> {
+        self.forwarder.close()
+    }
+}
@@ -0,0 +1,356 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::calls::CallLines;
+use super::fingerprint::fingerprint_hash;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_csv_row, TIME_FORMAT};
+
+
+pub const HEADER: &str = "Parsing query RA JSON";
+
+// One relational operator of a Calcite plan, with its inputs
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PlanNode {
+    pub id: String,
+    // relOp, e.g. LogicalAggregate
+    pub op: String,
+    // the table of a scan, the condition of a filter, the aggregates of an aggregate ...
+    pub detail: Option<String>,
+    pub inputs: Vec<PlanNode>,
+}
+
+// The optimized plan of a query
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Plan {
+    // hash of the operators and their structure, without tables, columns and literals
+    pub fingerprint: String,
+    pub explain: String,
+    pub root: PlanNode,
+}
+
+// A rex expression as text, $n for the input columns
+fn rex(value: &Value) -> String {
+    if let Some(x) = value.get("input") {
+        return format!("${}", x)
+    }
+    if let Some(x) = value.get("literal") {
+        return match x {
+            // quoted like SQL, so the literal ends at its closing quote, e.g. for --redact literals
+            Value::String(x) => format!("'{}'", x.replace('\'', "''")),
+            Value::Null => String::from("NULL"),
+            // DECIMAL literals are unscaled, e.g. -17812315200000032 with scale 14
+            Value::Number(n) if n.is_i64() => {
+                let scale = value.get("scale").and_then(|x| x.as_u64()).unwrap_or(0) as usize;
+                let digits = n.as_i64().unwrap_or(0).abs().to_string();
+                let sign = if n.as_i64().unwrap_or(0) < 0 { "-" } else { "" };
+                if scale == 0 {
+                    format!("{}{}", sign, digits)
+                } else {
+                    let digits = format!("{:0>width$}", digits, width = scale + 1);
+                    let (int, frac) = digits.split_at(digits.len() - scale);
+                    format!("{}{}.{}", sign, int, frac)
+                }
+            },
+            _ => x.to_string(),
+        }
+    }
+    if let Some(op) = value.get("op").and_then(|x| x.as_str()) {
+        let operands: Vec<String> = value.get("operands")
+            .and_then(|x| x.as_array())
+            .map(|x| x.iter().map(rex).collect())
+            .unwrap_or_default();
+        let infix = op == "AND" || op == "OR" || !op.chars().any(|x| x.is_ascii_alphanumeric());
+        if infix && operands.len() > 1 {
+            return format!("({})", operands.join(&format!(" {} ", op)))
+        }
+        return format!("{}({})", op, operands.join(", "))
+    }
+    String::from("?")
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value.and_then(|x| x.as_array())
+        .map(|x| x.iter().map(|x| match x {
+            Value::String(x) => x.clone(),
+            _ => x.to_string(),
+        }).collect())
+        .unwrap_or_default()
+}
+
+fn aggs(rel: &Value) -> Vec<String> {
+    let aggs = match rel.get("aggs").and_then(|x| x.as_array()) {
+        None => return vec![],
+        Some(x) => x,
+    };
+    aggs.iter().map(|agg| {
+        let operands: Vec<String> = strings(agg.get("operands")).iter().map(|x| format!("${}", x)).collect();
+        let distinct = if agg.get("distinct").and_then(|x| x.as_bool()).unwrap_or(false) { "DISTINCT " } else { "" };
+        format!("{}({}{})", agg.get("agg").and_then(|x| x.as_str()).unwrap_or("?"), distinct, operands.join(", "))
+    }).collect()
+}
+
+fn detail(rel: &Value) -> Option<String> {
+    let op = rel.get("relOp")?.as_str()?;
+    match op.trim_start_matches("Logical") {
+        "TableScan" => Some(strings(rel.get("table")).join(".")),
+        "Project" => {
+            let fields = strings(rel.get("fields"));
+            let exprs = rel.get("exprs")?.as_array()?;
+            Some(fields.iter().zip(exprs).map(|(f, e)| format!("{}={}", f, rex(e))).collect::<Vec<String>>().join(", "))
+        },
+        "Filter" => Some(rex(rel.get("condition")?)),
+        "Join" => Some(format!("{} {}",
+            rel.get("joinType").and_then(|x| x.as_str()).unwrap_or("inner"),
+            rel.get("condition").map(rex).unwrap_or_default())),
+        "Aggregate" => Some(format!("group=[{}] {}",
+            strings(rel.get("group")).join(", "),
+            aggs(rel).join(", "))),
+        "Sort" => {
+            let mut parts: Vec<String> = rel.get("collation")
+                .and_then(|x| x.as_array())
+                .map(|x| x.iter().map(|c| format!("${} {}",
+                    c.get("field").map(|x| x.to_string()).unwrap_or_default(),
+                    if c.get("direction").and_then(|x| x.as_str()) == Some("DESCENDING") { "DESC" } else { "ASC" }
+                )).collect())
+                .unwrap_or_default();
+            if let Some(x) = rel.get("fetch") {
+                parts.push(format!("fetch={}", rex(x)));
+            }
+            if let Some(x) = rel.get("offset") {
+                parts.push(format!("offset={}", rex(x)));
+            }
+            Some(parts.join(", "))
+        },
+        _ => None,
+    }.filter(|x| !x.is_empty())
+}
+
+// The operator, its join type or aggregate functions, and its inputs, e.g. Aggregate[0;COUNT](Project(TableScan))
+fn shape(node: &PlanNode, rels: &HashMap<&str, &Value>) -> String {
+    let op = node.op.trim_start_matches("Logical");
+    let mut text = String::from(op);
+    if let Some(rel) = rels.get(node.id.as_str()) {
+        if let Some(x) = rel.get("joinType").and_then(|x| x.as_str()) {
+            text.push_str(&format!("[{}]", x));
+        }
+        if rel.get("aggs").is_some() {
+            let functions: Vec<String> = aggs(rel).iter().map(|x| x.split('(').next().unwrap_or("").to_string()).collect();
+            text.push_str(&format!("[{};{}]", strings(rel.get("group")).len(), functions.join(",")));
+        }
+    }
+    if !node.inputs.is_empty() {
+        let inputs: Vec<String> = node.inputs.iter().map(|x| shape(x, rels)).collect();
+        text.push_str(&format!("({})", inputs.join(",")));
+    }
+    text
+}
+
+// One line per operator, the inputs indented below it
+fn explain(node: &PlanNode, depth: usize, lines: &mut Vec<String>) {
+    let op = node.op.trim_start_matches("Logical");
+    lines.push(match &node.detail {
+        Some(x) => format!("{}{} {}", "  ".repeat(depth), op, x),
+        None => format!("{}{}", "  ".repeat(depth), op),
+    });
+    for input in &node.inputs {
+        explain(input, depth + 1, lines);
+    }
+}
+
+fn node(id: &str, rels: &HashMap<&str, &Value>, order: &[&str], depth: usize) -> Option<PlanNode> {
+    // a bad plan could have cycles
+    if depth > order.len() {
+        return None
+    }
+    let rel = rels.get(id)?;
+    let inputs: Vec<String> = match rel.get("inputs") {
+        Some(_) => strings(rel.get("inputs")),
+        // without inputs, the input is the previous rel
+        None => match order.iter().position(|x| *x == id) {
+            Some(i) if i > 0 => vec![order[i - 1].to_string()],
+            _ => vec![],
+        },
+    };
+    Some(PlanNode {
+        id: id.to_string(),
+        op: rel.get("relOp")?.as_str()?.to_string(),
+        detail: detail(rel),
+        inputs: inputs.iter().map(|x| node(x, rels, order, depth + 1)).collect::<Option<Vec<PlanNode>>>()?,
+    })
+}
+
+// The msg of RelAlgDagBuilder.cpp, "Parsing query RA JSON: " and the plan on the continuation lines:
+// {"rels": [{"id": "0", "relOp": "LogicalTableScan", ...}, ...]}, the last rel is the root.
+pub fn parse_plan(msg: &str) -> Option<Plan> {
+    if !msg.starts_with(HEADER) {
+        return None
+    }
+    let json: Value = serde_json::from_str(&msg[msg.find('{')?..]).ok()?;
+    let list = json.get("rels")?.as_array()?;
+    let mut rels = HashMap::new();
+    let mut order = vec![];
+    for rel in list {
+        let id = rel.get("id")?.as_str()?;
+        rels.insert(id, rel);
+        order.push(id);
+    }
+    let root = node(order.last()?, &rels, &order, 0)?;
+    let mut lines = vec![];
+    explain(&root, 0, &mut lines);
+    Some(Plan {
+        fingerprint: fingerprint_hash(&shape(&root, &rels)),
+        explain: lines.join("\n"),
+        root,
+    })
+}
+
+// ra_node=(RelCompound<140689908460656>(...)) input_col_descs_unique.size()=0 ..., without the addresses
+fn ra_node(msg: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE_NODE: regex::Regex = regex::Regex::new(r"<[0-9]+>").unwrap();
+        static ref RE_INPUT: regex::Regex = regex::Regex::new(r"\(RexInput ([0-9]+) [0-9]+\)").unwrap();
+    }
+    let node = msg.strip_prefix("ra_node=")?;
+    let node = node.split(" input_col_descs_unique").next().unwrap_or(node);
+    Some(RE_INPUT.replace_all(&RE_NODE.replace_all(node, ""), "(RexInput $1)").to_string())
+}
+
+#[derive(Serialize)]
+struct PlanRow {
+    logtime: String,
+    hostname: Option<String>,
+    pid: i32,
+    threadid: Option<i32>,
+    // of the server, from its startup line
+    version: Option<String>,
+    // of the call the plan was in
+    event: Option<String>,
+    sequence: Option<i32>,
+    fingerprint: Option<String>,
+    plan_fingerprint: String,
+    execution_time: Option<i32>,
+    total_time: Option<i32>,
+    query: Option<String>,
+    explain: String,
+    ra_nodes: String,
+    plan: String,
+}
+
+struct Pending {
+    ra_nodes: Vec<String>,
+    row: PlanRow,
+}
+
+// A csv row per plan, with the sequence, fingerprint and query of the sql_execute or render_vega it was in,
+// linked like the descriptors of qmd.rs, and the distinct ra_node dumps of the executor thread after it.
+// The same fingerprint with another plan_fingerprint is a plan change, e.g. after an upgrade.
+pub struct PlanWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+    pending: CallLines<Pending>,
+    versions: HashMap<(Option<String>, i32), String>,
+}
+
+impl PlanWriter {
+    pub fn new(writer: Box<dyn Write>) -> PlanWriter {
+        PlanWriter {
+            writer: csv::Writer::from_writer(writer),
+            pending: CallLines::new(),
+            versions: HashMap::new(),
+        }
+    }
+
+    fn serialize(&mut self, mut x: Pending) -> SResult<()> {
+        x.row.ra_nodes = x.ra_nodes.join("\n");
+        write_csv_row(&mut self.writer, x.row)
+    }
+}
+
+impl LogWriter for PlanWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        if let (Some("version"), Some(x)) = (log.event.as_deref(), &log.msg_norm) {
+            self.versions.insert((log.hostname.clone(), log.pid), x.clone());
+            return Ok(())
+        }
+        if let Some(plan) = &log.plan {
+            self.pending.push(log, Pending {
+                ra_nodes: vec![],
+                row: PlanRow {
+                    logtime: log.logtime.format(TIME_FORMAT).to_string(),
+                    hostname: log.hostname.clone(),
+                    pid: log.pid,
+                    threadid: log.threadid,
+                    version: self.versions.get(&(log.hostname.clone(), log.pid)).cloned(),
+                    event: None,
+                    sequence: None,
+                    fingerprint: None,
+                    plan_fingerprint: plan.fingerprint.clone(),
+                    execution_time: None,
+                    total_time: None,
+                    query: None,
+                    explain: plan.explain.clone(),
+                    ra_nodes: String::new(),
+                    plan: serde_json::to_string(&plan.root)?,
+                },
+            });
+            return Ok(())
+        }
+        if let Some(node) = ra_node(&log.msg) {
+            let last = self.pending.last_mut(log, |x| x.row.threadid == log.threadid);
+            if let Some(x) = last {
+                if !x.ra_nodes.contains(&node) {
+                    x.ra_nodes.push(node);
+                }
+            }
+            return Ok(())
+        }
+        match (log.event.as_deref(), log.sequence) {
+            (Some("sql_execute"), Some(_)) | (Some("render_vega"), Some(_)) => (),
+            _ => return Ok(()),
+        }
+        for mut x in self.pending.take(log, |_| true) {
+            x.row.event = log.event.clone();
+            x.row.sequence = log.sequence;
+            x.row.fingerprint = log.fingerprint.clone();
+            x.row.execution_time = log.execution_time;
+            x.row.total_time = log.total_time.or(log.dur_ms);
+            x.row.query = log.query.clone();
+            self.serialize(x)?;
+        }
+        // the plans too old for a call, written without one
+        for x in self.pending.expire() {
+            self.serialize(x)?;
+        }
+        Ok(())
+    }
+
+    // the plans of calls without a stdlog
+    fn close(&mut self) -> SResult<()> {
+        for x in self.pending.take_all() {
+            self.serialize(x)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
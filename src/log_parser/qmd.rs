@@ -0,0 +1,180 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Write;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use super::calls::CallLines;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_csv_row, TIME_FORMAT};
+
+
+pub const HEADER: &str = "Query Memory Descriptor State";
+
+// Query Type: Non-grouped Aggregate -> query_type
+fn key(name: &str) -> String {
+    let key: String = name.chars()
+        .map(|x| if x.is_ascii_alphanumeric() { x.to_ascii_lowercase() } else { '_' })
+        .collect();
+    key.split('_').filter(|x| !x.is_empty()).collect::<Vec<&str>>().join("_")
+}
+
+fn value(text: &str) -> Value {
+    match text {
+        "True" => Value::Bool(true),
+        "False" => Value::Bool(false),
+        _ => match text.parse::<i64>() {
+            Ok(x) => json!(x),
+            Err(_) => json!(text),
+        },
+    }
+}
+
+// The tab indented block of Execute.cpp after "Query Memory Descriptor State",
+// as snake_case keys, with True/False as bools and numbers as numbers.
+// The "N | P , L" rows of the Col Slot Context are col_slot_context: [{slot, padded, logical}].
+pub fn parse_qmd(msg: &str) -> Option<Map<String, Value>> {
+    lazy_static! {
+        static ref RE_SLOT: regex::Regex = regex::Regex::new(
+            r"^([0-9]+) \| ([0-9]+) , ([0-9]+)").unwrap();
+    }
+    let mut lines = msg.lines();
+    if lines.next()?.trim() != HEADER {
+        return None
+    }
+    let mut qmd = Map::new();
+    let mut slots = vec![];
+    for line in lines {
+        let line = line.trim();
+        if let Some(caps) = RE_SLOT.captures(line) {
+            slots.push(json!({
+                "slot": value(&caps[1]),
+                "padded": value(&caps[2]),
+                "logical": value(&caps[3]),
+            }));
+        } else if let Some(i) = line.find(": ") {
+            qmd.insert(key(&line[..i]), value(line[i + 2..].trim()));
+        }
+    }
+    if !slots.is_empty() {
+        qmd.insert(String::from("col_slot_context"), Value::Array(slots));
+    }
+    Some(qmd)
+}
+
+#[derive(Serialize)]
+struct QmdRow {
+    logtime: String,
+    hostname: Option<String>,
+    pid: i32,
+    queryid: Option<i32>,
+    threadid: Option<i32>,
+    query_type: Option<String>,
+    keyless_hash: Option<bool>,
+    entry_count: Option<i64>,
+    output_columnar: Option<bool>,
+    use_streaming_top_n: Option<bool>,
+    sort_on_gpu: Option<bool>,
+    render_output: Option<bool>,
+    // of the call the descriptor was in
+    sequence: Option<i32>,
+    execution_time: Option<i32>,
+    total_time: Option<i32>,
+    query: Option<String>,
+    qmd: String,
+}
+
+impl QmdRow {
+    fn new(log: &LogLine, qmd: &Map<String, Value>) -> QmdRow {
+        let flag = |name: &str| qmd.get(name).and_then(|x| x.as_bool());
+        QmdRow {
+            logtime: log.logtime.format(TIME_FORMAT).to_string(),
+            hostname: log.hostname.clone(),
+            pid: log.pid,
+            queryid: log.queryid,
+            threadid: log.threadid,
+            query_type: qmd.get("query_type").and_then(|x| x.as_str()).map(|x| x.to_string()),
+            keyless_hash: flag("keyless_hash"),
+            entry_count: qmd.get("entry_count").and_then(|x| x.as_i64()),
+            output_columnar: flag("output_columnar"),
+            use_streaming_top_n: flag("use_streaming_top_n"),
+            sort_on_gpu: flag("sort_on_gpu"),
+            render_output: flag("render_output"),
+            sequence: None,
+            execution_time: None,
+            total_time: None,
+            query: None,
+            qmd: Value::Object(qmd.clone()).to_string(),
+        }
+    }
+}
+
+// A csv row per Query Memory Descriptor, with the execution_time and query of the sql_execute or render_vega it was in:
+// the next one of the same hostname and pid that began before it, and of the same queryid if both have one.
+pub struct QmdWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+    pending: CallLines<QmdRow>,
+}
+
+impl QmdWriter {
+    pub fn new(writer: Box<dyn Write>) -> QmdWriter {
+        QmdWriter {
+            writer: csv::Writer::from_writer(writer),
+            pending: CallLines::new(),
+        }
+    }
+}
+
+impl LogWriter for QmdWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        if let Some(qmd) = &log.qmd {
+            self.pending.push(log, QmdRow::new(log, qmd));
+            return Ok(())
+        }
+        match (log.event.as_deref(), log.sequence) {
+            (Some("sql_execute"), Some(_)) | (Some("render_vega"), Some(_)) => (),
+            _ => return Ok(()),
+        }
+        let done = self.pending.take(log, |x| {
+            x.queryid.is_none() || log.queryid.is_none() || x.queryid == log.queryid
+        });
+        for mut row in done {
+            row.sequence = log.sequence;
+            row.execution_time = log.execution_time;
+            row.total_time = log.total_time.or(log.dur_ms);
+            row.query = log.query.clone();
+            write_csv_row(&mut self.writer, row)?;
+        }
+        // the descriptors too old for a call, written without one
+        for row in self.pending.expire() {
+            write_csv_row(&mut self.writer, row)?;
+        }
+        Ok(())
+    }
+
+    // the descriptors of calls without a stdlog
+    fn close(&mut self) -> SResult<()> {
+        for row in self.pending.take_all() {
+            write_csv_row(&mut self.writer, row)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
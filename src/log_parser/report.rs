@@ -0,0 +1,365 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use chrono::NaiveDateTime;
+
+use colored::Colorize;
+
+use hdrhistogram::Histogram;
+
+use serde::Serialize;
+
+use super::lineparser::{LogLine, SResult};
+
+
+// the times in reports and their csv files, with the microseconds of the log
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+// ISO 8601, like the log itself, for the logtime sent to Elasticsearch and Kafka
+pub const ISO_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+// widest a cell may be in a terminal table, longer values are cut with "..."
+const TERMINAL_CELL_MAX_LEN: usize = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportFormat {
+    Terminal,
+    CSV,
+    JSON,
+    Markdown,
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl ReportFormat {
+    pub fn new(name: &str) -> ReportFormat {
+        match name {
+            "terminal" => ReportFormat::Terminal,
+            "csv" => ReportFormat::CSV,
+            "json" => ReportFormat::JSON,
+            "markdown" => ReportFormat::Markdown,
+            _ => panic!("Unknown ReportFormat: '{}'", name)
+        }
+    }
+}
+
+// LogLine columns that a report can be grouped by
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dimension {
+    Event,
+    Operation,
+    DBName,
+    Username,
+    Client,
+    DashboardID,
+    Hostname,
+}
+
+impl Dimension {
+    pub fn new(name: &str) -> Dimension {
+        match name {
+            "event" => Dimension::Event,
+            "operation" => Dimension::Operation,
+            "dbname" => Dimension::DBName,
+            "username" => Dimension::Username,
+            "client" => Dimension::Client,
+            "dashboardid" => Dimension::DashboardID,
+            "hostname" => Dimension::Hostname,
+            _ => panic!("Unknown Dimension: '{}'", name)
+        }
+    }
+
+    pub fn value<'a>(&self, log: &'a LogLine) -> &'a Option<String> {
+        match self {
+            Dimension::Event => &log.event,
+            Dimension::Operation => &log.operation,
+            Dimension::DBName => &log.dbname,
+            Dimension::Username => &log.username,
+            Dimension::Client => &log.client,
+            Dimension::DashboardID => &log.dashboardid,
+            Dimension::Hostname => &log.hostname,
+        }
+    }
+}
+
+// Time buckets for reports over time, named by the start of the bucket
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeBucket {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl TimeBucket {
+    pub fn new(name: &str) -> TimeBucket {
+        match name {
+            "minute" => TimeBucket::Minute,
+            "hour" => TimeBucket::Hour,
+            "day" => TimeBucket::Day,
+            _ => panic!("Unknown TimeBucket: '{}'", name)
+        }
+    }
+
+    pub fn format(&self, logtime: &NaiveDateTime) -> String {
+        match self {
+            TimeBucket::Minute => logtime.format("%Y-%m-%d %H:%M").to_string(),
+            TimeBucket::Hour => logtime.format("%Y-%m-%d %H:00").to_string(),
+            TimeBucket::Day => logtime.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+// Settings for the aggregate reports, which read all the inputs before writing anything.
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    pub format: ReportFormat,
+    // file to write the report to, otherwise stdout
+    pub output: Option<String>,
+    pub group_by: Vec<Dimension>,
+    pub bucket: Option<TimeBucket>,
+    // a previous report, to compare with
+    pub baseline: Option<String>,
+    // sessions report of the activity of only this session id
+    pub session: Option<String>,
+    // audit report, this many failed logins within the minutes is a brute force attack
+    pub brute_force_failures: usize,
+    pub brute_force_minutes: i64,
+    // config report of one startup, or the difference of two, 1 is the first and -1 the last
+    pub startups: Vec<i64>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> ReportOptions {
+        ReportOptions {
+            format: ReportFormat::Terminal,
+            output: None,
+            group_by: vec![],
+            bucket: None,
+            baseline: None,
+            session: None,
+            brute_force_failures: 5,
+            brute_force_minutes: 10,
+            startups: vec![],
+        }
+    }
+}
+
+fn report_writer(output: &Option<String>) -> SResult<Box<dyn Write>> {
+    match output {
+        None => Ok(Box::new(io::stdout())),
+        Some(path) => Ok(Box::new(fs::File::create(path)?)),
+    }
+}
+
+// Writes rows of a report in the requested format.
+// The row struct field names are used as the column names, in order.
+pub fn write_report<T: Serialize>(rows: &[T], options: &ReportOptions) -> SResult<()> {
+    let mut writer = report_writer(&options.output)?;
+    match options.format {
+        ReportFormat::CSV => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for row in rows {
+                csv_writer.serialize(row)?;
+            }
+            csv_writer.flush()?;
+        },
+        ReportFormat::JSON => {
+            for row in rows {
+                writer.write_all(&serde_json::to_string(row)?.into_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        },
+        ReportFormat::Terminal => {
+            writer.write_all(&format_table(rows)?.into_bytes())?;
+            writer.flush()?;
+        },
+        ReportFormat::Markdown => {
+            writer.write_all(&format_markdown(rows)?.into_bytes())?;
+            writer.flush()?;
+        },
+    }
+    Ok(())
+}
+
+// Writes a row of a csv output as it is parsed, like -t timers, qmd and plan
+pub fn write_csv_row<W: Write, T: Serialize>(writer: &mut csv::Writer<W>, row: T) -> SResult<()> {
+    match writer.serialize(row) {
+        Ok(()) => Ok(()),
+        // assumes the user quit the output early
+        Err(e) => match e.kind() {
+            csv::ErrorKind::Io(x) if x.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            _ => Err(Box::new(e)),
+        },
+    }
+}
+
+// header and rows of values as strings, in the order of the row struct fields
+fn table_records<T: Serialize>(rows: &[T]) -> SResult<Vec<Vec<String>>> {
+    let mut csv_writer = csv::WriterBuilder::new().from_writer(vec![]);
+    for row in rows {
+        csv_writer.serialize(row)?;
+    }
+    let data = csv_writer.into_inner().map_err(|e| io::Error::new(e.error().kind(), e.error().to_string()))?;
+
+    let mut records: Vec<Vec<String>> = vec![];
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(data.as_slice());
+    for record in reader.records() {
+        records.push(record?.iter().map(|x| x.to_string()).collect());
+    }
+    Ok(records)
+}
+
+// Lay out the rows as an aligned text table.
+// Serializing through csv keeps the struct field order for the header.
+pub fn format_table<T: Serialize>(rows: &[T]) -> SResult<String> {
+    let records: Vec<Vec<String>> = table_records(rows)?.iter()
+        .map(|record| record.iter().map(|x| table_cell(x)).collect())
+        .collect();
+    if records.is_empty() {
+        return Ok(String::from("(no rows)\n"))
+    }
+
+    let mut widths: Vec<usize> = vec![0; records[0].len()];
+    for record in &records {
+        for (i, cell) in record.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for (r, record) in records.iter().enumerate() {
+        let mut cells: Vec<String> = vec![];
+        for (i, cell) in record.iter().enumerate() {
+            let cell = if r > 0 && is_number(cell) {
+                format!("{:>width$}", cell, width = widths[i])
+            } else {
+                format!("{:<width$}", cell, width = widths[i])
+            };
+            cells.push(if r == 0 { cell.bold().to_string() } else { cell });
+        }
+        table.push_str(cells.join(" | ").trim_end());
+        table.push('\n');
+    }
+    Ok(table)
+}
+
+// A markdown table, to paste into tickets and wikis. Values are not truncated.
+pub fn format_markdown<T: Serialize>(rows: &[T]) -> SResult<String> {
+    let records = table_records(rows)?;
+    if records.is_empty() {
+        return Ok(String::from("(no rows)\n"))
+    }
+
+    let mut table = String::new();
+    for (r, record) in records.iter().enumerate() {
+        let cells: Vec<String> = record.iter()
+            .map(|x| x.replace('|', "\\|").replace('\n', "<br>"))
+            .collect();
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if r == 0 {
+            table.push_str(&format!("|{}\n", "---|".repeat(cells.len())));
+        }
+    }
+    Ok(table)
+}
+
+fn table_cell(value: &str) -> String {
+    let value = value.replace('\n', " ");
+    if value.chars().count() > TERMINAL_CELL_MAX_LEN {
+        let mut cut: String = value.chars().take(TERMINAL_CELL_MAX_LEN - 3).collect();
+        cut.push_str("...");
+        cut
+    } else {
+        value
+    }
+}
+
+fn is_number(value: &str) -> bool {
+    !value.is_empty() && value.parse::<f64>().is_ok()
+}
+
+
+// Running latency statistics in bounded memory, percentiles come from an HDR histogram.
+#[derive(Debug, Clone)]
+pub struct Latency {
+    hist: Histogram<u64>,
+    total: i64,
+    // exact, the histogram only keeps 2 significant digits
+    max: u64,
+}
+
+impl Default for Latency {
+    fn default() -> Latency {
+        Latency::new()
+    }
+}
+
+impl Latency {
+    pub fn new() -> Latency {
+        Latency {
+            // 2 significant digits keeps each histogram to a few KB, auto-resizing as larger values are recorded
+            hist: Histogram::new(2).unwrap(),
+            total: 0,
+            max: 0,
+        }
+    }
+
+    pub fn record(&mut self, ms: i32) {
+        if ms >= 0 {
+            // record resizes the histogram as needed, saturating_record does not
+            if self.hist.record(ms as u64).is_err() {
+                self.hist.saturating_record(ms as u64);
+            }
+            self.total += ms as i64;
+            self.max = self.max.max(ms as u64);
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.hist.len()
+    }
+
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+
+    // rounded to 0.1 ms, which is already finer than what the server logs
+    pub fn mean(&self) -> f64 {
+        if self.count() == 0 {
+            0.0
+        } else {
+            (self.total as f64 * 10.0 / self.count() as f64).round() / 10.0
+        }
+    }
+
+    pub fn quantile(&self, q: f64) -> u64 {
+        self.hist.value_at_quantile(q).min(self.max)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+}
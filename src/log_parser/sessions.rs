@@ -0,0 +1,245 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use serde::Serialize;
+
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+// One row of the sessions report
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionRow {
+    pub session: String,
+    pub username: String,
+    pub client: String,
+    pub dbname: String,
+    // connect or clone_session, or empty if the session was already open when the log starts
+    pub start_event: String,
+    pub start: String,
+    // disconnect or session_invalidated, or empty if the session is still open
+    pub end_event: String,
+    pub end: String,
+    pub duration_s: f64,
+    pub calls: u64,
+    pub queries: u64,
+    pub query_time_ms: i64,
+    pub hostname: String,
+}
+
+// One row of the activity of a single session, see ReportOptions::session
+#[derive(Serialize, Debug, Clone)]
+pub struct ActivityRow {
+    pub logtime: String,
+    pub severity: String,
+    pub threadid: Option<i32>,
+    pub fileline: String,
+    pub event: String,
+    pub dur_ms: Option<i32>,
+    // the query, or the msg for lines that are not stdlog calls
+    pub detail: String,
+}
+
+struct Session {
+    username: Option<String>,
+    client: Option<String>,
+    dbname: Option<String>,
+    start_event: Option<String>,
+    start: NaiveDateTime,
+    end_event: Option<String>,
+    end: Option<NaiveDateTime>,
+    last_seen: NaiveDateTime,
+    calls: u64,
+    queries: u64,
+    query_time_ms: i64,
+}
+
+// "tcp:172.17.0.1:53140" -> "172.17.0.1"
+pub fn client_ip(client: &str) -> &str {
+    let address = match client.find(':') {
+        Some(i) if client[..i].chars().all(|c| c.is_ascii_alphabetic()) => &client[i + 1..],
+        _ => client,
+    };
+    match address.rfind(':') {
+        Some(i) if address[i + 1..].chars().all(|c| c.is_ascii_digit()) => &address[..i],
+        _ => address,
+    }
+}
+
+// Rebuilds sessions from the stdlog calls, which all carry the session id.
+// A session starts with connect or clone_session, and ends with disconnect, or
+// with session_invalidated when it is idle too long.
+// With ReportOptions::session set, writes the ordered activity of that one session instead,
+// including the other lines logged by its threads while a call of the session was running.
+pub struct SessionsWriter {
+    options: ReportOptions,
+    // (hostname, session)
+    sessions: HashMap<(Option<String>, String), Session>,
+    activity: Vec<(NaiveDateTime, ActivityRow)>,
+    // (pid, threadid) -> session of the stdlog_begin running on the thread
+    threads: HashMap<(i32, i32), String>,
+}
+
+impl SessionsWriter {
+    pub fn new(options: ReportOptions) -> SessionsWriter {
+        SessionsWriter {
+            options,
+            sessions: HashMap::new(),
+            activity: vec![],
+            threads: HashMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<SessionRow> {
+        let mut sessions: Vec<(&(Option<String>, String), &Session)> = self.sessions.iter().collect();
+        sessions.sort_by(|(ka, a), (kb, b)| a.start.cmp(&b.start).then(ka.cmp(kb)));
+        let value = |x: &Option<String>| x.clone().unwrap_or_default();
+        sessions.iter().map(|((hostname, session), x)| SessionRow {
+            session: session.to_string(),
+            username: value(&x.username),
+            client: match &x.client {
+                None => String::from(""),
+                Some(client) => client_ip(client).to_string(),
+            },
+            dbname: value(&x.dbname),
+            start_event: value(&x.start_event),
+            start: x.start.format(TIME_FORMAT).to_string(),
+            end_event: value(&x.end_event),
+            end: match x.end {
+                None => String::from(""),
+                Some(end) => end.format(TIME_FORMAT).to_string(),
+            },
+            duration_s: (x.end.unwrap_or(x.last_seen) - x.start).num_milliseconds() as f64 / 1000.0,
+            calls: x.calls,
+            queries: x.queries,
+            query_time_ms: x.query_time_ms,
+            hostname: value(hostname),
+        }).collect()
+    }
+
+    pub fn activity(&self) -> Vec<ActivityRow> {
+        let mut activity: Vec<&(NaiveDateTime, ActivityRow)> = self.activity.iter().collect();
+        // stable, so lines with the same time stay in log order
+        activity.sort_by_key(|(logtime, _)| *logtime);
+        activity.iter().map(|(_, row)| row.clone()).collect()
+    }
+
+    fn add_activity(&mut self, log: &LogLine) {
+        let detail = match &log.query {
+            Some(query) => query.to_string(),
+            None => log.msg.to_string(),
+        };
+        self.activity.push((log.logtime, ActivityRow {
+            logtime: log.logtime.format(TIME_FORMAT).to_string(),
+            severity: log.severity.to_string(),
+            threadid: log.threadid,
+            fileline: log.fileline.to_string(),
+            event: log.event.clone().unwrap_or_default(),
+            dur_ms: log.dur_ms,
+            detail,
+        }));
+    }
+}
+
+impl LogWriter for SessionsWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let thread = log.threadid.map(|x| (log.pid, x));
+        let session = match (&log.session, thread) {
+            (Some(session), _) => session.to_string(),
+            (None, Some(thread)) => {
+                if self.options.session.is_some() && self.threads.get(&thread) == self.options.session.as_ref() {
+                    self.add_activity(log);
+                }
+                return Ok(())
+            },
+            (None, None) => return Ok(()),
+        };
+
+        let event = log.event.clone().unwrap_or_default();
+        let begin = log.sequence.is_some() && event.ends_with("_begin");
+        if let (Some(thread), Some(_)) = (thread, log.sequence) {
+            if begin {
+                self.threads.insert(thread, session.to_string());
+            } else {
+                self.threads.remove(&thread);
+            }
+        }
+
+        if self.options.session.is_some() {
+            if self.options.session.as_ref() == Some(&session) {
+                self.add_activity(log);
+            }
+            return Ok(())
+        }
+
+        let entry = self.sessions.entry((log.hostname.clone(), session)).or_insert_with(|| Session {
+            username: None,
+            client: None,
+            dbname: None,
+            start_event: None,
+            start: log.logtime,
+            end_event: None,
+            end: None,
+            last_seen: log.logtime,
+            calls: 0,
+            queries: 0,
+            query_time_ms: 0,
+        });
+        entry.last_seen = entry.last_seen.max(log.logtime);
+        if entry.username.is_none() {
+            entry.username = log.username.clone();
+        }
+        if entry.client.is_none() {
+            entry.client = log.client.clone();
+        }
+        if entry.dbname.is_none() {
+            entry.dbname = log.dbname.clone();
+        }
+
+        match event.as_str() {
+            "connect" | "clone_session" if entry.start_event.is_none() => {
+                entry.start_event = Some(event.to_string());
+                entry.start = entry.start.min(log.logtime);
+            },
+            "disconnect" | "session_invalidated" => {
+                entry.end_event = Some(event.to_string());
+                entry.end = Some(log.logtime);
+            },
+            _ => (),
+        }
+        if log.sequence.is_some() && !begin {
+            entry.calls += 1;
+            if event == "sql_execute" {
+                entry.queries += 1;
+                if let Some(ms) = log.total_time.or(log.dur_ms) {
+                    entry.query_time_ms += ms as i64;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        match self.options.session {
+            None => write_report(&self.rows(), &self.options),
+            Some(_) => write_report(&self.activity(), &self.options),
+        }
+    }
+}
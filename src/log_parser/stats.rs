@@ -0,0 +1,184 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, Dimension, Latency, ReportOptions};
+use super::sessions::client_ip;
+
+
+// groups with their own histograms in every bucket, further groups are counted together per bucket as OTHER
+const MAX_GROUPS: usize = 1000;
+const OTHER: &str = "(other)";
+
+
+// One row of the stats report, for one metric of a group.
+// Only the columns being grouped by are written, the others are skipped.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct StatsRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dbname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dashboardid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    pub metric: String,
+    pub count: u64,
+    pub sum: i64,
+    pub avg: f64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Default)]
+struct StatsEntry {
+    dur_ms: Latency,
+    execution_time: Latency,
+    total_time: Latency,
+}
+
+// (bucket, values of the grouped columns)
+type StatsKey = (Option<String>, Vec<String>);
+
+// Latency percentiles of dur_ms, execution_time and total_time, per group and time bucket.
+// Only histograms are kept per group, so memory does not grow with the number of lines,
+// and the number of groups is capped, so neither does it with a column like client.
+pub struct StatsWriter {
+    options: ReportOptions,
+    group_by: Vec<Dimension>,
+    entries: BTreeMap<StatsKey, StatsEntry>,
+    // the values of the groups with their own histograms, at most MAX_GROUPS
+    groups: HashSet<Vec<String>>,
+}
+
+impl StatsWriter {
+    pub fn new(options: ReportOptions) -> StatsWriter {
+        let group_by = if options.group_by.is_empty() {
+            vec![Dimension::Event]
+        } else {
+            options.group_by.clone()
+        };
+        StatsWriter {
+            options,
+            group_by,
+            entries: BTreeMap::new(),
+            groups: HashSet::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<StatsRow> {
+        let mut rows = vec![];
+        for ((bucket, values), entry) in &self.entries {
+            for (metric, latency) in [
+                ("dur_ms", &entry.dur_ms),
+                ("execution_time", &entry.execution_time),
+                ("total_time", &entry.total_time),
+            ] {
+                if latency.count() == 0 {
+                    continue
+                }
+                let mut row = StatsRow {
+                    bucket: bucket.clone(),
+                    metric: metric.to_string(),
+                    count: latency.count(),
+                    sum: latency.total(),
+                    avg: latency.mean(),
+                    p50: latency.quantile(0.50),
+                    p90: latency.quantile(0.90),
+                    p95: latency.quantile(0.95),
+                    p99: latency.quantile(0.99),
+                    max: latency.max(),
+                    ..Default::default()
+                };
+                for (dim, val) in self.group_by.iter().zip(values) {
+                    let val = Some(val.to_string());
+                    match dim {
+                        Dimension::Event => row.event = val,
+                        Dimension::Operation => row.operation = val,
+                        Dimension::DBName => row.dbname = val,
+                        Dimension::Username => row.username = val,
+                        Dimension::Client => row.client = val,
+                        Dimension::DashboardID => row.dashboardid = val,
+                        Dimension::Hostname => row.hostname = val,
+                    }
+                }
+                rows.push(row);
+            }
+        }
+        rows
+    }
+}
+
+impl LogWriter for StatsWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        // dur_ms is always 0 at the begin of a stdlog call
+        let dur_ms = match &log.event {
+            Some(event) if event.ends_with("_begin") => None,
+            _ => log.dur_ms,
+        };
+        if dur_ms.is_none() && log.execution_time.is_none() && log.total_time.is_none() {
+            return Ok(())
+        }
+
+        let bucket = self.options.bucket.as_ref().map(|b| b.format(&log.logtime));
+        let values: Vec<String> = self.group_by.iter().map(|dim| match (dim, dim.value(log)) {
+            // the port changes with each connection
+            (Dimension::Client, Some(x)) => client_ip(x).to_string(),
+            (_, Some(x)) => x.to_string(),
+            (_, None) => String::from(""),
+        }).collect();
+        let values = if self.groups.contains(&values) {
+            values
+        } else if self.groups.len() < MAX_GROUPS {
+            self.groups.insert(values.clone());
+            values
+        } else {
+            vec![String::from(OTHER); self.group_by.len()]
+        };
+        let entry = self.entries.entry((bucket, values)).or_default();
+        if let Some(ms) = dur_ms {
+            entry.dur_ms.record(ms);
+        }
+        if let Some(ms) = log.execution_time {
+            entry.execution_time.record(ms);
+        }
+        if let Some(ms) = log.total_time {
+            entry.total_time.record(ms);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
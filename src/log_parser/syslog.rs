@@ -0,0 +1,175 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Write;
+use std::io::{Error, ErrorKind};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use super::forward::Forwarder;
+use super::http::TIMEOUT;
+use super::lineparser::{LogLine, LogWriter, SResult, Severity};
+
+
+pub const DEFAULT_ENDPOINT: &str = "udp://127.0.0.1:514";
+
+// local0
+const FACILITY: u8 = 16;
+const APP_NAME: &str = "omnisci_server";
+// the example enterprise number of RFC 5424
+const SD_ID: &str = "omnisci@32473";
+// the longest message RFC 5426 asks receivers to take over UDP
+const MAX_UDP: usize = 2048;
+
+fn severity_code(severity: &Severity) -> u8 {
+    match severity {
+        Severity::FATAL => 2,
+        Severity::ERROR => 3,
+        Severity::WARNING | Severity::INPUT => 4,
+        Severity::AUTH => 5,
+        Severity::INFO | Severity::OTHER => 6,
+        Severity::DEBUG => 7,
+    }
+}
+
+// PARAM-VALUE escapes " \ and ]
+fn param_value(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', "\\\"").replace(']', "\\]")
+}
+
+// header fields are printable ASCII without spaces, or -
+fn header_field(value: Option<&str>, max: usize) -> String {
+    let value: String = value.unwrap_or("").chars().filter(|x| x.is_ascii_graphic()).take(max).collect();
+    if value.is_empty() { String::from("-") } else { value }
+}
+
+// An RFC 5424 message. The logtime is taken as UTC.
+pub fn format_message(log: &LogLine) -> String {
+    let mut params = vec![format!("severity=\"{}\"", log.severity)];
+    for (name, value) in [
+        ("hostname", &log.hostname),
+        ("event", &log.event),
+        ("dbname", &log.dbname),
+    ] {
+        if let Some(value) = value {
+            params.push(format!("{}=\"{}\"", name, param_value(value)));
+        }
+    }
+    let body = match &log.query {
+        Some(query) => query,
+        None => &log.msg,
+    };
+    format!("<{}>1 {}Z {} {} {} {} [{} {}] {}",
+        FACILITY * 8 + severity_code(&log.severity),
+        log.logtime.format("%Y-%m-%dT%H:%M:%S%.6f"),
+        header_field(log.hostname.as_deref(), 255),
+        APP_NAME,
+        log.pid,
+        header_field(log.event.as_deref(), 32),
+        SD_ID,
+        params.join(" "),
+        body)
+}
+
+enum Transport {
+    Udp(UdpSocket, String),
+    // octet counting framing of RFC 6587, reconnects after an error.
+    // The frames written before an error may have arrived, and are sent again with their batch.
+    Tcp(String, Option<TcpStream>),
+    #[cfg(unix)]
+    Unix(UnixDatagram, String),
+}
+
+impl Transport {
+    fn new(endpoint: &str) -> SResult<Transport> {
+        if let Some(address) = endpoint.strip_prefix("udp://") {
+            return Ok(Transport::Udp(UdpSocket::bind("0.0.0.0:0")?, address.to_string()))
+        }
+        if let Some(address) = endpoint.strip_prefix("tcp://") {
+            return Ok(Transport::Tcp(address.to_string(), None))
+        }
+        #[cfg(unix)]
+        {
+            if let Some(path) = endpoint.strip_prefix("unix://") {
+                return Ok(Transport::Unix(UnixDatagram::unbound()?, path.to_string()))
+            }
+        }
+        Err(Box::new(Error::new(ErrorKind::InvalidInput,
+            format!("Syslog endpoint must be udp://host:port, tcp://host:port or unix:///path: {}", endpoint))))
+    }
+
+    fn send(&mut self, batch: &[String]) -> SResult<()> {
+        match self {
+            Transport::Udp(socket, address) => for message in batch {
+                let bytes = message.as_bytes();
+                socket.send_to(&bytes[..bytes.len().min(MAX_UDP)], address.as_str())?;
+            },
+            Transport::Tcp(address, stream) => {
+                let mut frames = vec![];
+                for message in batch {
+                    frames.extend_from_slice(format!("{} {}", message.len(), message).as_bytes());
+                }
+                if stream.is_none() {
+                    let resolved = address.to_socket_addrs()?.next()
+                        .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Syslog endpoint not found: {}", address)))?;
+                    let connected = TcpStream::connect_timeout(&resolved, TIMEOUT)?;
+                    // a stalled collector fails the batch, which is retried, instead of hanging the thread
+                    connected.set_read_timeout(Some(TIMEOUT))?;
+                    connected.set_write_timeout(Some(TIMEOUT))?;
+                    *stream = Some(connected);
+                }
+                if let Some(connected) = stream {
+                    let result = connected.write_all(&frames).and_then(|_| connected.flush());
+                    if let Err(e) = result {
+                        *stream = None;
+                        return Err(Box::new(e))
+                    }
+                }
+            },
+            #[cfg(unix)]
+            Transport::Unix(socket, path) => for message in batch {
+                socket.send_to(message.as_bytes(), path.as_str())?;
+            },
+        }
+        Ok(())
+    }
+}
+
+// Forwards lines to syslog, with the hostname, severity, event and dbname as structured data.
+// The message is the query of a stdlog, otherwise the msg.
+pub struct SyslogWriter {
+    forwarder: Forwarder<String>,
+}
+
+impl SyslogWriter {
+    pub fn new(endpoint: &str) -> SResult<SyslogWriter> {
+        let mut transport = Transport::new(endpoint)?;
+        Ok(SyslogWriter {
+            forwarder: Forwarder::new(move |batch| transport.send(batch)),
+        })
+    }
+}
+
+impl LogWriter for SyslogWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        self.forwarder.push(format_message(log))
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.forwarder.close()
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::fingerprint::fingerprint;
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TimeBucket, TIME_FORMAT};
+
+
+// words after FROM or JOIN that are not a table
+const KEYWORDS: [&str; 24] = ["select", "where", "group", "order", "limit", "offset", "having", "on", "using",
+    "join", "inner", "left", "right", "full", "outer", "cross", "natural", "union", "lateral", "unnest",
+    "table", "values", "as", "with"];
+
+// words before a parenthesis that are not a function call
+const NOT_FUNCTIONS: [&str; 22] = ["from", "join", "in", "exists", "as", "any", "all", "some", "values", "lateral",
+    "copy", "select", "where", "on", "and", "or", "not", "union", "having", "when", "then", "else"];
+
+// A table of a query, and whether the query changes it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableAccess {
+    pub table: String,
+    pub write: bool,
+}
+
+fn is_name(token: &str) -> bool {
+    token.starts_with(|x: char| x.is_ascii_alphabetic() || x == '_' || x == '"' || x == '`')
+}
+
+fn name(token: &str) -> String {
+    token.trim_matches(|x| x == '"' || x == '`').to_string()
+}
+
+// The tables a query reads with FROM and JOIN, without the names of its WITH queries,
+// and changes with INSERT INTO, COPY FROM, DROP, ALTER, TRUNCATE, UPDATE, DELETE FROM and CREATE,
+// in lower case like its fingerprint, once each.
+//   INSERT INTO t2 SELECT a FROM t1 JOIN t3 ON t1.a = t3.a;
+//   [t2 write, t1 read, t3 read]
+pub fn query_tables(query: &str) -> Vec<TableAccess> {
+    lazy_static! {
+        static ref RE_TOKEN: regex::Regex = regex::Regex::new(
+            r#""[^"]*"|`[^`]*`|[a-z_][a-z0-9_$]*(?:\.[a-z_"`][a-z0-9_$"`]*)*|\S"#).unwrap();
+    }
+    let norm = fingerprint(query);
+    let tokens: Vec<&str> = RE_TOKEN.find_iter(&norm).map(|x| x.as_str()).collect();
+    let token = |i: usize| tokens.get(i).copied().unwrap_or("");
+    let is_table = |i: usize| is_name(token(i)) && !KEYWORDS.contains(&token(i));
+
+    // WITH x AS (...), y AS (...)
+    let mut ctes = vec![];
+    for i in 1..tokens.len() {
+        if (token(i - 1) == "with" || token(i - 1) == ",") && token(i + 1) == "as" && token(i + 2) == "(" {
+            ctes.push(name(token(i)));
+        }
+    }
+
+    let mut accesses: Vec<TableAccess> = vec![];
+    let mut add = |table: &str, write: bool| {
+        let access = TableAccess { table: name(table), write };
+        if !ctes.contains(&access.table) && !accesses.contains(&access) {
+            accesses.push(access);
+        }
+    };
+    // whether each open parenthesis is of a function call, like EXTRACT(YEAR FROM x)
+    let mut functions: Vec<bool> = vec![];
+    for i in 0..tokens.len() {
+        match token(i) {
+            "(" => functions.push(i > 0 && is_name(token(i - 1)) && !NOT_FUNCTIONS.contains(&token(i - 1))),
+            ")" => {
+                functions.pop();
+            },
+            "from" | "join" if !functions.last().copied().unwrap_or(false) => {
+                let previous = if i > 0 { token(i - 1) } else { "" };
+                // a IS DISTINCT FROM b
+                if previous == "distinct" {
+                    continue
+                }
+                let write = previous == "delete";
+                // FROM a, b AS x, c y
+                let mut j = i + 1;
+                while is_table(j) {
+                    add(token(j), write);
+                    j += 1;
+                    if token(j) == "as" {
+                        j += 2;
+                    } else if is_table(j) {
+                        j += 1;
+                    }
+                    if token(i) != "from" || token(j) != "," {
+                        break
+                    }
+                    j += 1;
+                }
+            },
+            "into" | "update" if is_table(i + 1) => add(token(i + 1), true),
+            "copy" if is_table(i + 1) && token(i + 2) == "from" => add(token(i + 1), true),
+            "drop" | "alter" | "truncate" | "create" => {
+                let mut j = i + 1;
+                while ["temporary", "table", "view", "if", "not", "exists"].contains(&token(j)) {
+                    j += 1;
+                }
+                if j > i + 1 && is_table(j) {
+                    add(token(j), true);
+                }
+            },
+            _ => (),
+        }
+    }
+    accesses
+}
+
+// One row of the tables report:
+// table with the totals of a table of a db, the never queried first and then the least recently accessed,
+// and bucket with them per table and --bucket.
+#[derive(Serialize, Debug, Clone)]
+pub struct TableRow {
+    // table or bucket
+    pub category: String,
+    // of a bucket row
+    pub time: String,
+    // empty for a lock or storage line whose db the log does not tell
+    pub dbname: String,
+    pub table: String,
+    pub reads: u64,
+    pub writes: u64,
+    // LockMgr.h and FileMgr.cpp lines
+    pub storage: u64,
+    pub first_access: String,
+    pub last_access: String,
+    pub last_read: String,
+    // no query read it
+    pub never_queried: bool,
+    pub users: String,
+    pub dashboards: String,
+    // the directory of its files
+    pub location: String,
+}
+
+enum Access {
+    Read,
+    Write,
+    Storage,
+}
+
+#[derive(Default)]
+struct Usage {
+    reads: u64,
+    writes: u64,
+    storage: u64,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    last_read: Option<NaiveDateTime>,
+    users: BTreeSet<String>,
+    dashboards: BTreeSet<String>,
+    location: Option<String>,
+}
+
+impl Usage {
+    fn add(&mut self, log: &LogLine, access: &Access) {
+        match access {
+            Access::Read => {
+                self.reads += 1;
+                self.last_read = Some(log.logtime);
+            },
+            Access::Write => self.writes += 1,
+            Access::Storage => self.storage += 1,
+        }
+        self.first = Some(self.first.map_or(log.logtime, |x| x.min(log.logtime)));
+        self.last = Some(self.last.map_or(log.logtime, |x| x.max(log.logtime)));
+        if let Access::Storage = access {
+            return
+        }
+        self.users.extend(log.username.clone());
+        self.dashboards.extend(log.dashboardid.clone());
+    }
+
+    fn row(&self, category: &str, time: &str, (dbname, table): &TableKey) -> TableRow {
+        let format = |x: &Option<NaiveDateTime>| x.map(|x| x.format(TIME_FORMAT).to_string()).unwrap_or_default();
+        let join = |x: &BTreeSet<String>| x.iter().cloned().collect::<Vec<String>>().join(",");
+        TableRow {
+            category: category.to_string(),
+            time: time.to_string(),
+            dbname: dbname.to_string(),
+            table: table.to_string(),
+            reads: self.reads,
+            writes: self.writes,
+            storage: self.storage,
+            first_access: format(&self.first),
+            last_access: format(&self.last),
+            last_read: format(&self.last_read),
+            never_queried: self.reads == 0,
+            users: join(&self.users),
+            dashboards: join(&self.dashboards),
+            location: self.location.clone().unwrap_or_default(),
+        }
+    }
+}
+
+// (dbname, table in lower case)
+type TableKey = (String, String);
+
+// Builds the tables report from the tables of the sql_execute queries, the load_table and import_table calls,
+// the table_lock lines of LockMgr.h, and the FileMgr.cpp lines of reading a table,
+// with the name from the Catalog.cpp line after it on the same thread:
+//   FileMgr.cpp:205 Completed Reading table's file metadata, ... table location: '/omnisci-storage/data/mapd_data/table_1_1'
+//   Catalog.cpp:1414 Instantiating Fragmenter for table omnisci_states took 12ms
+// The lock and storage lines are of the db of the call begun on their thread,
+// or else of the only db the table was seen in.
+pub struct TableWriter {
+    options: ReportOptions,
+    tables: BTreeMap<TableKey, Usage>,
+    // (table, bucket)
+    buckets: BTreeMap<(TableKey, String), Usage>,
+    // (hostname, pid, threadid) -> the table location of its last FileMgr.cpp line
+    locations: HashMap<(Option<String>, i32, Option<i32>), String>,
+    // (hostname, pid, threadid) -> the dbname of the call running on it
+    threads: HashMap<(Option<String>, i32, Option<i32>), String>,
+}
+
+impl TableWriter {
+    pub fn new(options: ReportOptions) -> TableWriter {
+        TableWriter {
+            options,
+            tables: BTreeMap::new(),
+            buckets: BTreeMap::new(),
+            locations: HashMap::new(),
+            threads: HashMap::new(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<TableRow> {
+        let mut tables: Vec<(&TableKey, &Usage)> = self.tables.iter().collect();
+        tables.sort_by_key(|(_, x)| (x.reads > 0, x.last));
+        let mut rows: Vec<TableRow> = tables.into_iter().map(|(table, x)| x.row("table", "", table)).collect();
+        for ((table, bucket), x) in &self.buckets {
+            rows.push(x.row("bucket", bucket, table));
+        }
+        rows
+    }
+
+    fn access(&mut self, key: TableKey, log: &LogLine, access: Access) {
+        let bucket = self.options.bucket.clone().unwrap_or(TimeBucket::Day);
+        self.tables.entry(key.clone()).or_default().add(log, &access);
+        self.buckets.entry((key, bucket.format(&log.logtime))).or_default().add(log, &access);
+    }
+
+    // the table of a call, in its db
+    fn call_table(&self, table: &str, log: &LogLine) -> TableKey {
+        (log.dbname.clone().unwrap_or_default(), table.to_lowercase())
+    }
+
+    // the table of a lock or storage line, which has no dbname
+    fn storage_table(&self, table: &str, log: &LogLine) -> TableKey {
+        let table = table.to_lowercase();
+        let thread = (log.hostname.clone(), log.pid, log.threadid);
+        if let Some(dbname) = self.threads.get(&thread) {
+            return (dbname.clone(), table)
+        }
+        let mut dbnames = self.tables.keys().filter(|x| x.1 == table && !x.0.is_empty());
+        match (dbnames.next(), dbnames.next()) {
+            (Some(x), None) => x.clone(),
+            _ => (String::from(""), table),
+        }
+    }
+}
+
+impl LogWriter for TableWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        lazy_static! {
+            static ref RE_LOCATION: regex::Regex = regex::Regex::new(r"table location: '([^']*)'").unwrap();
+            static ref RE_FRAGMENTER: regex::Regex = regex::Regex::new(
+                r"^Instantiating Fragmenter for table (\S+) took").unwrap();
+        }
+        let key = (log.hostname.clone(), log.pid, log.threadid);
+        if let (Some(event), Some(_), Some(_)) = (&log.event, log.sequence, log.threadid) {
+            match &log.dbname {
+                Some(dbname) if event.ends_with("_begin") && !dbname.is_empty() => {
+                    self.threads.insert(key.clone(), dbname.clone());
+                },
+                _ => {
+                    self.threads.remove(&key);
+                },
+            }
+        }
+        match (log.event.as_deref(), log.sequence) {
+            (Some("sql_execute"), Some(_)) => {
+                for x in query_tables(log.query.as_deref().unwrap_or("")) {
+                    let table = self.call_table(&x.table, log);
+                    self.access(table, log, if x.write { Access::Write } else { Access::Read });
+                }
+            },
+            (Some(event), Some(_)) if event.starts_with("load_table") || event == "import_table" => {
+                // {"table_name"} {"flights"}
+                if let Some(values) = &log.name_values {
+                    if let Some(pair) = values.chunks(2).find(|x| x.len() == 2 && x[0] == "table_name") {
+                        let table = self.call_table(&pair[1], log);
+                        self.access(table, log, Access::Write);
+                    }
+                }
+            },
+            (Some("table_lock"), _) => {
+                if let Some(values) = &log.name_values {
+                    if let Some(pair) = values.chunks(2).find(|x| x.len() == 2 && x[0] == "table") {
+                        let table = self.storage_table(&pair[1], log);
+                        self.access(table, log, Access::Storage);
+                    }
+                }
+            },
+            (Some("read_table_metadata"), _) => {
+                if let Some(caps) = RE_LOCATION.captures(&log.msg) {
+                    self.locations.insert(key, caps[1].to_string());
+                }
+            },
+            _ => if let Some(caps) = RE_FRAGMENTER.captures(log.msg.trim()) {
+                if let Some(location) = self.locations.remove(&key) {
+                    let table = self.storage_table(&caps[1], log);
+                    self.access(table.clone(), log, Access::Storage);
+                    if let Some(x) = self.tables.get_mut(&table) {
+                        x.location = Some(location);
+                    }
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
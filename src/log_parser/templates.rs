@@ -0,0 +1,252 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::lineparser::{LogLine, LogWriter, SResult};
+
+
+const WILDCARD: &str = "<*>";
+
+// fraction of tokens that must match for a msg to join a template, as in Drain
+const SIMILARITY_THRESHOLD: f64 = 0.5;
+
+// with --follow, the templates are saved at most this often
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Template {
+    pub id: i32,
+    pub fileline: String,
+    pub template: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct TemplateFile {
+    templates: Vec<Template>,
+}
+
+struct Cluster {
+    id: i32,
+    tokens: Vec<String>,
+    count: u64,
+}
+
+// Drain-style online template miner, see "Drain: An Online Log Parsing Approach with Fixed Depth Tree".
+// Messages are grouped by fileline, number of tokens and first token. Within a group, a message
+// joins the most similar template, and the tokens that differ become <*> wildcards.
+// Tokens with digits are always wildcards.
+pub struct TemplateMiner {
+    clusters: Vec<Cluster>,
+    // (fileline, token count, first token) -> indexes of clusters
+    groups: HashMap<(String, usize, String), Vec<usize>>,
+    filelines: Vec<String>,
+    next_id: i32,
+}
+
+impl Default for TemplateMiner {
+    fn default() -> TemplateMiner {
+        TemplateMiner::new()
+    }
+}
+
+impl TemplateMiner {
+    pub fn new() -> TemplateMiner {
+        TemplateMiner {
+            clusters: vec![],
+            groups: HashMap::new(),
+            filelines: vec![],
+            next_id: 1,
+        }
+    }
+
+    // Templates learned in earlier runs keep their ids. A missing file starts empty.
+    pub fn load(path: &str) -> SResult<TemplateMiner> {
+        let mut miner = TemplateMiner::new();
+        if Path::new(path).exists() {
+            let file: TemplateFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+            for t in file.templates {
+                let tokens = t.template.split_whitespace().map(|x| x.to_string()).collect();
+                miner.add_cluster(t.id, &t.fileline, tokens, t.count);
+            }
+        }
+        Ok(miner)
+    }
+
+    // written to a temporary file first, so a run that is killed never leaves half a file
+    pub fn save(&self, path: &str) -> SResult<()> {
+        let file = TemplateFile { templates: self.templates() };
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, serde_json::to_string_pretty(&file)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn templates(&self) -> Vec<Template> {
+        self.clusters.iter().zip(&self.filelines).map(|(c, fileline)| Template {
+            id: c.id,
+            fileline: fileline.to_string(),
+            template: c.tokens.join(" "),
+            count: c.count,
+        }).collect()
+    }
+
+    fn group_key(fileline: &str, tokens: &[String]) -> (String, usize, String) {
+        let first = match tokens.first() {
+            None => String::from(""),
+            Some(x) => x.to_string(),
+        };
+        (fileline.to_string(), tokens.len(), first)
+    }
+
+    fn add_cluster(&mut self, id: i32, fileline: &str, tokens: Vec<String>, count: u64) -> usize {
+        let key = TemplateMiner::group_key(fileline, &tokens);
+        let index = self.clusters.len();
+        self.clusters.push(Cluster { id, tokens, count });
+        self.filelines.push(fileline.to_string());
+        self.groups.entry(key).or_default().push(index);
+        self.next_id = self.next_id.max(id + 1);
+        index
+    }
+
+    // Returns the template id, the template, and the values of the wildcards in msg.
+    pub fn mine(&mut self, fileline: &str, msg: &str) -> (i32, String, Vec<String>) {
+        let words: Vec<&str> = msg.split_whitespace().collect();
+        let tokens: Vec<String> = words.iter().map(|x| {
+            if x.chars().any(|c| c.is_ascii_digit()) {
+                WILDCARD.to_string()
+            } else {
+                x.to_string()
+            }
+        }).collect();
+
+        let key = TemplateMiner::group_key(fileline, &tokens);
+        let mut best: Option<(usize, f64)> = None;
+        if let Some(indexes) = self.groups.get(&key) {
+            for i in indexes {
+                let sim = similarity(&self.clusters[*i].tokens, &tokens);
+                let better = match best {
+                    None => true,
+                    Some((_, b)) => sim > b,
+                };
+                if sim >= SIMILARITY_THRESHOLD && better {
+                    best = Some((*i, sim));
+                }
+            }
+        }
+
+        let index = match best {
+            Some((i, _)) => {
+                let cluster = &mut self.clusters[i];
+                for (t, token) in cluster.tokens.iter_mut().zip(&tokens) {
+                    if t != token {
+                        *t = WILDCARD.to_string();
+                    }
+                }
+                i
+            },
+            None => {
+                let id = self.next_id;
+                self.add_cluster(id, fileline, tokens, 0)
+            },
+        };
+
+        let cluster = &mut self.clusters[index];
+        cluster.count += 1;
+        let params = cluster.tokens.iter().zip(&words)
+            .filter(|(t, _)| *t == WILDCARD)
+            .map(|(_, w)| w.to_string())
+            .collect();
+        (cluster.id, cluster.tokens.join(" "), params)
+    }
+
+    // Replaces msg_norm with the template of the first line of msg.
+    // stdlog calls are already structured, so they are left alone.
+    pub fn apply(&mut self, log: &mut LogLine) {
+        if log.sequence.is_some() || log.msg.is_empty() {
+            return
+        }
+        let first_line = log.msg.lines().next().unwrap_or("");
+        let (id, template, params) = self.mine(&log.fileline, first_line);
+        log.msg_norm = Some(template);
+        log.template_id = Some(id);
+        if !params.is_empty() {
+            log.template_params = Some(params);
+        }
+    }
+}
+
+// fraction of positions where the template has the same token, wildcards do not count
+fn similarity(template: &[String], tokens: &[String]) -> f64 {
+    if tokens.is_empty() {
+        return 1.0
+    }
+    let same = template.iter().zip(tokens).filter(|(a, b)| a == b && *a != WILDCARD).count();
+    let wildcards = template.iter().filter(|x| *x == WILDCARD).count();
+    if wildcards == tokens.len() {
+        1.0
+    } else {
+        same as f64 / (tokens.len() - wildcards) as f64
+    }
+}
+
+// Mines templates before passing each line to another writer, and saves the templates on close,
+// and every SAVE_INTERVAL while lines are mined, because with --follow close never comes.
+pub struct TemplateWriter {
+    path: String,
+    miner: TemplateMiner,
+    writer: Box<dyn LogWriter>,
+    saved: Instant,
+    // lines mined since the last save
+    unsaved: bool,
+}
+
+impl TemplateWriter {
+    pub fn new(path: &str, writer: Box<dyn LogWriter>) -> SResult<TemplateWriter> {
+        Ok(TemplateWriter {
+            path: path.to_string(),
+            miner: TemplateMiner::load(path)?,
+            writer,
+            saved: Instant::now(),
+            unsaved: false,
+        })
+    }
+}
+
+impl LogWriter for TemplateWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let mut log = log.clone();
+        self.miner.apply(&mut log);
+        self.unsaved |= log.template_id.is_some();
+        if self.unsaved && self.saved.elapsed() >= SAVE_INTERVAL {
+            self.miner.save(&self.path)?;
+            self.saved = Instant::now();
+            self.unsaved = false;
+        }
+        self.writer.write(&log)
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.miner.save(&self.path)?;
+        self.writer.close()
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_csv_row, TIME_FORMAT};
+
+
+// One timer of a stacked_times tree, in depth first order, the root at depth 0
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Timer {
+    pub name: String,
+    // the root has no thread
+    pub thread: Option<u64>,
+    pub total_ms: i64,
+    pub depth: usize,
+}
+
+// The timers of one call, the sequence is the one of its stdlog line
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StackedTimes {
+    pub sequence: i32,
+    pub timers: Vec<Timer>,
+}
+
+// With debug timers, the msg of a stacked_times line and its indented continuation lines:
+//   stacked_times sql_execute 1 total time 906 ms
+//     parse_to_ra 140690399721216 - total time 711 ms
+//       processImpl 140690399721216 - total time 698 ms
+pub fn parse_stacked_times(msg: &str) -> Option<StackedTimes> {
+    lazy_static! {
+        static ref RE_ROOT: regex::Regex = regex::Regex::new(
+            r"^stacked_times (\S+) ([0-9]+) total time ([0-9]+) ms").unwrap();
+        static ref RE_TIMER: regex::Regex = regex::Regex::new(
+            r"^( *)(\S+) ([0-9]+) - total time ([0-9]+) ms").unwrap();
+    }
+    let mut lines = msg.lines();
+    let caps = RE_ROOT.captures(lines.next()?)?;
+    let sequence = caps[2].parse().ok()?;
+    let mut timers = vec![Timer {
+        name: caps[1].to_string(),
+        thread: None,
+        total_ms: caps[3].parse().ok()?,
+        depth: 0,
+    }];
+    for line in lines {
+        if let Some(caps) = RE_TIMER.captures(line) {
+            timers.push(Timer {
+                name: caps[2].to_string(),
+                thread: caps[3].parse().ok(),
+                total_ms: caps[4].parse().unwrap_or(0),
+                // two spaces per level
+                depth: (caps[1].len() / 2).max(1),
+            });
+        }
+    }
+    Some(StackedTimes { sequence, timers })
+}
+
+// The ;-separated names from the root of each timer, and its time not in its children.
+// Children on other threads can add up to more than the parent, then the self time is 0.
+pub fn stacks(timers: &[Timer]) -> Vec<(String, i64)> {
+    let mut stacks = vec![];
+    let mut path: Vec<&str> = vec![];
+    for (i, timer) in timers.iter().enumerate() {
+        path.truncate(timer.depth);
+        path.push(&timer.name);
+        let children: i64 = timers[i + 1..].iter()
+            .take_while(|x| x.depth > timer.depth)
+            .filter(|x| x.depth == timer.depth + 1)
+            .map(|x| x.total_ms)
+            .sum();
+        stacks.push((path.join(";"), (timer.total_ms - children).max(0)));
+    }
+    stacks
+}
+
+#[derive(Serialize)]
+struct TimerRow<'a> {
+    logtime: String,
+    hostname: Option<&'a str>,
+    pid: i32,
+    sequence: i32,
+    depth: usize,
+    name: &'a str,
+    thread: Option<u64>,
+    total_ms: i64,
+    self_ms: i64,
+    stack: String,
+}
+
+// A csv row per timer of the stacked_times lines
+pub struct TimersWriter {
+    writer: csv::Writer<Box<dyn Write>>,
+}
+
+impl TimersWriter {
+    pub fn new(writer: Box<dyn Write>) -> TimersWriter {
+        TimersWriter {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+}
+
+impl LogWriter for TimersWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let stacked = match &log.timers {
+            None => return Ok(()),
+            Some(x) => x,
+        };
+        for (timer, (stack, self_ms)) in stacked.timers.iter().zip(stacks(&stacked.timers)) {
+            let row = TimerRow {
+                logtime: log.logtime.format(TIME_FORMAT).to_string(),
+                hostname: log.hostname.as_deref(),
+                pid: log.pid,
+                sequence: stacked.sequence,
+                depth: timer.depth,
+                name: &timer.name,
+                thread: timer.thread,
+                total_ms: timer.total_ms,
+                self_ms,
+                stack,
+            };
+            write_csv_row(&mut self.writer, row)?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Folded stacks, the self time summed by stack, the input of flamegraph.pl and speedscope
+pub struct FoldedWriter {
+    writer: Box<dyn Write>,
+    stacks: BTreeMap<String, i64>,
+}
+
+impl FoldedWriter {
+    pub fn new(writer: Box<dyn Write>) -> FoldedWriter {
+        FoldedWriter {
+            writer,
+            stacks: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogWriter for FoldedWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        if let Some(stacked) = &log.timers {
+            for (stack, self_ms) in stacks(&stacked.timers) {
+                *self.stacks.entry(stack).or_insert(0) += self_ms;
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        for (stack, ms) in &self.stacks {
+            if *ms > 0 {
+                writeln!(self.writer, "{} {}", stack, ms)?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,381 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use tui::Frame;
+
+use super::browser::{read_logs, with_terminal, BATCH_SIZE};
+use super::calls::{server_of, ExpireTick, MAX_CALL_SECONDS};
+use super::lineparser::{LineFilter, LogLine, LogWriter, SResult, Severity};
+use super::redact::Redactor;
+
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+// seconds of log time for the per second rates
+const RATE_SECONDS: i64 = 10;
+// seconds of log time for the per minute rates
+const MINUTE_SECONDS: i64 = 60;
+// seconds of log time for the slowest recent queries
+const SLOW_SECONDS: i64 = 300;
+const SLOW_ROWS: usize = 10;
+// the default idle-session-duration of the server, which logs the invalidation only if the session is used again
+const SESSION_IDLE_SECONDS: i64 = 3600;
+
+#[derive(Debug, Clone)]
+pub struct RunningQuery {
+    pub begin: NaiveDateTime,
+    pub elapsed_ms: i64,
+    pub event: String,
+    pub session: String,
+    pub username: String,
+    pub dbname: String,
+    pub query: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub logtime: NaiveDateTime,
+    pub total_time: i32,
+    pub username: String,
+    pub dbname: String,
+    pub query: String,
+}
+
+// What the dashboard shows at one time
+#[derive(Debug, Clone)]
+pub struct TopSnapshot {
+    pub now: NaiveDateTime,
+    pub queries_per_s: f64,
+    pub calls_per_s: f64,
+    pub errors_per_min: usize,
+    pub warnings_per_min: usize,
+    pub memory_failed: u64,
+    pub memory_failed_per_min: usize,
+    pub memory_punts: u64,
+    pub memory_punts_per_min: usize,
+    pub sessions: usize,
+    // longest running first
+    pub running: Vec<RunningQuery>,
+    // slowest first
+    pub slowest: Vec<SlowQuery>,
+}
+
+fn trim(times: &mut VecDeque<NaiveDateTime>, since: NaiveDateTime) {
+    while let Some(time) = times.front() {
+        if *time >= since {
+            break
+        }
+        times.pop_front();
+    }
+}
+
+fn count_since(times: &VecDeque<NaiveDateTime>, since: NaiveDateTime) -> usize {
+    times.iter().filter(|x| **x > since).count()
+}
+
+// Aggregates for a live dashboard of a server, from the lines as they are logged.
+// Rates are over the log time, so they are right while catching up on a file too.
+pub struct Top {
+    queries: VecDeque<NaiveDateTime>,
+    calls: VecDeque<NaiveDateTime>,
+    errors: VecDeque<NaiveDateTime>,
+    warnings: VecDeque<NaiveDateTime>,
+    memory_failed: VecDeque<NaiveDateTime>,
+    memory_failed_total: u64,
+    memory_punts: VecDeque<NaiveDateTime>,
+    memory_punts_total: u64,
+    slow: VecDeque<SlowQuery>,
+    // (server, pid, sequence) -> the stdlog_begin of a call that has not ended
+    running: HashMap<(String, i32, i32), RunningQuery>,
+    // (server, session) -> (pid, the last log time of the session)
+    sessions: HashMap<(String, String), (i32, NaiveDateTime)>,
+    // server -> pid, a new pid is a restarted server, and its calls and sessions are gone
+    pids: HashMap<String, i32>,
+    expire: ExpireTick,
+    // the last log time, and when it was read
+    last: Option<(NaiveDateTime, Instant)>,
+}
+
+impl Default for Top {
+    fn default() -> Top {
+        Top::new()
+    }
+}
+
+impl Top {
+    pub fn new() -> Top {
+        Top {
+            queries: VecDeque::new(),
+            calls: VecDeque::new(),
+            errors: VecDeque::new(),
+            warnings: VecDeque::new(),
+            memory_failed: VecDeque::new(),
+            memory_failed_total: 0,
+            memory_punts: VecDeque::new(),
+            memory_punts_total: 0,
+            slow: VecDeque::new(),
+            running: HashMap::new(),
+            sessions: HashMap::new(),
+            pids: HashMap::new(),
+            expire: ExpireTick::default(),
+            last: None,
+        }
+    }
+
+    // The log time now, the last log time plus the time since it was read,
+    // so running queries keep aging while the server logs nothing.
+    pub fn now(&self) -> Option<NaiveDateTime> {
+        self.last.map(|(logtime, read)| logtime + chrono::Duration::from_std(read.elapsed()).unwrap_or_else(|_| chrono::Duration::zero()))
+    }
+
+    pub fn snapshot(&self, now: NaiveDateTime) -> TopSnapshot {
+        let since = |seconds: i64| now - chrono::Duration::seconds(seconds);
+
+        let mut running: Vec<RunningQuery> = self.running.values().map(|x| RunningQuery {
+            elapsed_ms: (now - x.begin).num_milliseconds(),
+            ..x.clone()
+        }).collect();
+        running.sort_by(|a, b| b.elapsed_ms.cmp(&a.elapsed_ms).then(a.session.cmp(&b.session)));
+
+        let mut slowest: Vec<SlowQuery> = self.slow.iter().filter(|x| x.logtime > since(SLOW_SECONDS)).cloned().collect();
+        slowest.sort_by(|a, b| b.total_time.cmp(&a.total_time).then(b.logtime.cmp(&a.logtime)));
+        slowest.truncate(SLOW_ROWS);
+
+        TopSnapshot {
+            now,
+            queries_per_s: count_since(&self.queries, since(RATE_SECONDS)) as f64 / RATE_SECONDS as f64,
+            calls_per_s: count_since(&self.calls, since(RATE_SECONDS)) as f64 / RATE_SECONDS as f64,
+            errors_per_min: count_since(&self.errors, since(MINUTE_SECONDS)),
+            warnings_per_min: count_since(&self.warnings, since(MINUTE_SECONDS)),
+            memory_failed: self.memory_failed_total,
+            memory_failed_per_min: count_since(&self.memory_failed, since(MINUTE_SECONDS)),
+            memory_punts: self.memory_punts_total,
+            memory_punts_per_min: count_since(&self.memory_punts, since(MINUTE_SECONDS)),
+            sessions: self.sessions.len(),
+            running,
+            slowest,
+        }
+    }
+}
+
+impl LogWriter for Top {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        let latest = match self.last {
+            Some((last, _)) if last > log.logtime => last,
+            _ => log.logtime,
+        };
+        self.last = Some((latest, Instant::now()));
+
+        // the input file or --hostname, so servers with the same pid are apart
+        let server = server_of(log).unwrap_or_default();
+        if !server.is_empty() {
+            match self.pids.insert(server.clone(), log.pid) {
+                Some(pid) if pid != log.pid => {
+                    self.running.retain(|x, _| x.0 != server || x.1 != pid);
+                    self.sessions.retain(|x, y| x.0 != server || y.0 != pid);
+                },
+                _ => (),
+            }
+        }
+
+        let event = log.event.clone().unwrap_or_default();
+        match log.severity {
+            Severity::FATAL | Severity::ERROR => self.errors.push_back(log.logtime),
+            Severity::WARNING => self.warnings.push_back(log.logtime),
+            _ => (),
+        }
+        if event == "memory_allocation_failed" {
+            self.memory_failed.push_back(log.logtime);
+            self.memory_failed_total += 1;
+        } else if event == "memory_punt_to_cpu" {
+            self.memory_punts.push_back(log.logtime);
+            self.memory_punts_total += 1;
+        }
+
+        if let Some(session) = &log.session {
+            let key = (server.clone(), session.to_string());
+            if event == "disconnect" || event == "session_invalidated" {
+                self.sessions.remove(&key);
+            } else {
+                self.sessions.insert(key, (log.pid, log.logtime));
+            }
+        }
+
+        if let Some(sequence) = log.sequence {
+            let value = |x: &Option<String>| x.clone().unwrap_or_default();
+            if let Some(name) = event.strip_suffix("_begin") {
+                self.running.insert((server, log.pid, sequence), RunningQuery {
+                    begin: log.logtime,
+                    elapsed_ms: 0,
+                    event: name.to_string(),
+                    session: value(&log.session),
+                    username: value(&log.username),
+                    dbname: value(&log.dbname),
+                    query: value(&log.query),
+                });
+            } else {
+                self.running.remove(&(server, log.pid, sequence));
+                self.calls.push_back(log.logtime);
+                if event == "sql_execute" {
+                    self.queries.push_back(log.logtime);
+                    self.slow.push_back(SlowQuery {
+                        logtime: log.logtime,
+                        total_time: log.total_time.or(log.dur_ms).unwrap_or(0),
+                        username: value(&log.username),
+                        dbname: value(&log.dbname),
+                        query: value(&log.query),
+                    });
+                }
+            }
+        }
+
+        // only the recent lines are needed
+        let since = latest - chrono::Duration::seconds(SLOW_SECONDS);
+        for times in [&mut self.queries, &mut self.calls, &mut self.errors, &mut self.warnings,
+                &mut self.memory_failed, &mut self.memory_punts] {
+            trim(times, since);
+        }
+        while let Some(query) = self.slow.front() {
+            if query.logtime >= since {
+                break
+            }
+            self.slow.pop_front();
+        }
+        // calls whose stdlog never came, like CallLines, and sessions that expired without a line
+        let latest = match self.expire.tick(log.logtime) {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let begun = latest - chrono::Duration::seconds(MAX_CALL_SECONDS);
+        self.running.retain(|_, x| x.begin >= begun);
+        let seen = latest - chrono::Duration::seconds(SESSION_IDLE_SECONDS);
+        self.sessions.retain(|_, x| x.1 >= seen);
+        Ok(())
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, snapshot: &TopSnapshot, message: &str) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Percentage(50), Constraint::Min(3), Constraint::Length(1)])
+        .split(f.size());
+
+    let summary = format!(
+        "queries/s {:.1}   calls/s {:.1}   sessions {}   running {}   errors/min {}   warnings/min {}\n\
+        gpu allocation failed {} ({}/min)   punt to cpu {} ({}/min)   log time {}",
+        snapshot.queries_per_s, snapshot.calls_per_s, snapshot.sessions, snapshot.running.len(),
+        snapshot.errors_per_min, snapshot.warnings_per_min,
+        snapshot.memory_failed, snapshot.memory_failed_per_min, snapshot.memory_punts, snapshot.memory_punts_per_min,
+        snapshot.now.format("%Y-%m-%d %H:%M:%S"));
+    f.render_widget(Paragraph::new(summary).block(Block::default().borders(Borders::BOTTOM)), chunks[0]);
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let one_line = |x: &str| x.replace('\n', " ");
+
+    let widths = [Constraint::Length(10), Constraint::Length(16), Constraint::Length(9), Constraint::Length(12),
+        Constraint::Length(12), Constraint::Percentage(100)];
+    let running = Table::new(snapshot.running.iter().map(|x| Row::new(vec![
+            Cell::from(format!("{:>9}", x.elapsed_ms)),
+            Cell::from(x.event.clone()),
+            Cell::from(x.session.clone()),
+            Cell::from(x.username.clone()),
+            Cell::from(x.dbname.clone()),
+            Cell::from(one_line(&x.query)),
+        ])))
+        .header(Row::new(vec!["elapsed_ms", "event", "session", "username", "dbname", "query"]).style(bold))
+        .block(Block::default().borders(Borders::BOTTOM).title("running"))
+        .widths(&widths);
+    f.render_widget(running, chunks[1]);
+
+    let widths = [Constraint::Length(10), Constraint::Length(9), Constraint::Length(12), Constraint::Length(12),
+        Constraint::Percentage(100)];
+    let slowest = Table::new(snapshot.slowest.iter().map(|x| Row::new(vec![
+            Cell::from(format!("{:>10}", x.total_time)),
+            Cell::from(x.logtime.format("%H:%M:%S").to_string()),
+            Cell::from(x.username.clone()),
+            Cell::from(x.dbname.clone()),
+            Cell::from(one_line(&x.query)),
+        ])))
+        .header(Row::new(vec!["total_time", "logtime", "username", "dbname", "query"]).style(bold))
+        .block(Block::default().title("slowest in 5 minutes"))
+        .widths(&widths);
+    f.render_widget(slowest, chunks[2]);
+
+    let status = if message.is_empty() { String::from("q quit") } else { format!("{} | q quit", message) };
+    f.render_widget(Paragraph::new(status).style(Style::default().add_modifier(Modifier::REVERSED)), chunks[3]);
+}
+
+// Live dashboard of the server, refreshed every second
+pub fn top_logs(
+        inputs: &[String],
+        filter: &LineFilter,
+        hostname: Option<&str>,
+        follow: bool,
+        templates: Option<&str>,
+        redactor: Option<&Redactor>,
+        ) -> SResult<()> {
+
+    let receiver = read_logs(inputs, filter, hostname, follow, templates, redactor);
+    let mut top = Top::new();
+    let mut message = String::from("");
+    with_terminal(|terminal| {
+        let mut drawn: Option<Instant> = None;
+        loop {
+            let mut lines = 0;
+            for line in receiver.try_iter().take(BATCH_SIZE) {
+                lines += 1;
+                match line {
+                    Ok(log) => top.write(&log)?,
+                    Err(e) => message = e,
+                }
+            }
+            let due = match drawn {
+                None => true,
+                Some(x) => x.elapsed() >= REFRESH,
+            };
+            if due {
+                if let Some(now) = top.now() {
+                    let snapshot = top.snapshot(now);
+                    terminal.draw(|f| draw(f, &snapshot, &message))?;
+                    drawn = Some(Instant::now());
+                }
+            }
+            // while catching up, only look for a key
+            let wait = if lines == BATCH_SIZE { Duration::from_millis(0) } else { Duration::from_millis(100) };
+            if event::poll(wait)? {
+                if let Event::Key(key) = event::read()? {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    })
+}
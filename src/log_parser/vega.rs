@@ -0,0 +1,270 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use lazy_static::lazy_static;
+
+use serde::Serialize;
+
+use super::calls::{server_of, CallLines, ExpireTick, MAX_CALL_SECONDS};
+use super::fingerprint::{fingerprint, fingerprint_hash};
+use super::lineparser::{LogLine, LogWriter, SResult};
+use super::report::{write_report, ReportOptions, TIME_FORMAT};
+
+
+// A data source of a vega spec, with the hash of its normalized sql
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct VegaData {
+    pub name: String,
+    pub sql: String,
+    pub fingerprint: String,
+}
+
+// The parts of a render_vega call that say what it rendered
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Vega {
+    pub widget_id: Option<i64>,
+    pub compression_level: Option<i64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    // the sources with sql, not the ones transforming another source
+    pub data: Vec<VegaData>,
+    // the type of each mark, like symbol, points or polys
+    pub marks: Vec<String>,
+}
+
+// The vega_json of a render_vega stdlog, with the widget_id and compression_level of its name_values:
+//   {"width":1002,"height":726,"data":[{"name":"pointmap","sql":"SELECT ..."}],"marks":[{"type":"symbol",...}]}
+pub fn parse_vega(json: &str, name_values: &Option<Vec<String>>) -> Option<Vega> {
+    let spec: serde_json::Value = serde_json::from_str(json).ok()?;
+    if !spec.is_object() {
+        return None
+    }
+    let data = spec["data"].as_array().map(|x| x.iter()
+        .filter_map(|x| x["sql"].as_str().map(|sql| VegaData {
+            name: x["name"].as_str().unwrap_or("").to_string(),
+            sql: sql.to_string(),
+            fingerprint: fingerprint_hash(&fingerprint(sql)),
+        }))
+        .collect())
+        .unwrap_or_default();
+    let marks = spec["marks"].as_array().map(|x| x.iter()
+        .filter_map(|x| x["type"].as_str().map(|x| x.to_string()))
+        .collect())
+        .unwrap_or_default();
+    // {"widget_id","0","compression_level","0"}
+    let value = |name: &str| name_values.as_ref()
+        .and_then(|x| x.chunks(2).find(|x| x.len() == 2 && x[0] == name))
+        .and_then(|x| x[1].parse().ok());
+    Some(Vega {
+        widget_id: value("widget_id"),
+        compression_level: value("compression_level"),
+        width: spec["width"].as_i64(),
+        height: spec["height"].as_i64(),
+        data,
+        marks,
+    })
+}
+
+// One row of the vega report per render_vega call, the slowest first,
+// with what the render server logged during the call.
+#[derive(Serialize, Debug, Clone)]
+pub struct VegaRow {
+    pub time: String,
+    pub hostname: String,
+    pub pid: i32,
+    pub sequence: Option<i32>,
+    pub session: String,
+    pub username: String,
+    pub dbname: String,
+    pub dashboardid: String,
+    pub chartid: String,
+    pub widget_id: Option<i64>,
+    pub compression_level: Option<i64>,
+    pub total_time: Option<i32>,
+    // Total Execution and Total Render of the RenderHandlerImpl.cpp line
+    pub execution_ms: Option<i64>,
+    pub render_ms: Option<i64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub marks: String,
+    // the Calcite.cpp lines of the sql it ran
+    pub executions: u64,
+    pub kernel_launches: u64,
+    // of the sql it ran, once each
+    pub fingerprints: String,
+    // of the data sources of the spec
+    pub sql: String,
+}
+
+enum Detail {
+    Completed(Option<i64>, Option<i64>),
+    Execution(String),
+    Launch,
+}
+
+// Builds the vega report from the render_vega stdlog calls,
+// and the lines of the same server and pid during a call, while a render_vega_begin is open,
+// except those of a thread in another call, like a concurrent sql_execute:
+//   RenderHandlerImpl.cpp:155 render_vega-COMPLETED nonce:... Total Execution: 259 (ms), Total Render: 550 (ms)
+//   Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT ...'
+//   Execute.cpp:2078 Launching 1 kernels for query.
+pub struct VegaWriter {
+    options: ReportOptions,
+    rows: Vec<VegaRow>,
+    // (server, pid) -> times of the render_vega_begin calls not ended yet
+    open: HashMap<(String, i32), Vec<NaiveDateTime>>,
+    // (server, pid, threadid) -> whether the call begun on the thread is a render_vega, and when
+    threads: HashMap<(String, i32, i32), (bool, NaiveDateTime)>,
+    // the lines not linked to a call yet, with their thread if it began the render
+    pending: CallLines<(Option<i32>, Detail)>,
+    expire: ExpireTick,
+}
+
+impl VegaWriter {
+    pub fn new(options: ReportOptions) -> VegaWriter {
+        VegaWriter {
+            options,
+            rows: vec![],
+            open: HashMap::new(),
+            threads: HashMap::new(),
+            pending: CallLines::new(),
+            expire: ExpireTick::default(),
+        }
+    }
+
+    pub fn rows(&self) -> Vec<VegaRow> {
+        let mut rows = self.rows.clone();
+        rows.sort_by(|a, b| b.total_time.cmp(&a.total_time).then(a.time.cmp(&b.time)));
+        rows
+    }
+
+    fn call(&mut self, log: &LogLine) {
+        let vega = log.vega.as_ref();
+        let mut row = VegaRow {
+            time: log.logtime.format(TIME_FORMAT).to_string(),
+            hostname: log.hostname.clone().unwrap_or_default(),
+            pid: log.pid,
+            sequence: log.sequence,
+            session: log.session.clone().unwrap_or_default(),
+            username: log.username.clone().unwrap_or_default(),
+            dbname: log.dbname.clone().unwrap_or_default(),
+            dashboardid: log.dashboardid.clone().unwrap_or_default(),
+            chartid: log.chartid.clone().unwrap_or_default(),
+            widget_id: vega.and_then(|x| x.widget_id),
+            compression_level: vega.and_then(|x| x.compression_level),
+            total_time: log.total_time.or(log.dur_ms),
+            execution_ms: None,
+            render_ms: None,
+            width: vega.and_then(|x| x.width),
+            height: vega.and_then(|x| x.height),
+            marks: vega.map(|x| x.marks.join(",")).unwrap_or_default(),
+            executions: 0,
+            kernel_launches: 0,
+            fingerprints: String::from(""),
+            sql: vega.map(|x| x.data.iter().map(|x| x.sql.as_str()).collect::<Vec<&str>>().join(";\n")).unwrap_or_default(),
+        };
+
+        let mut fingerprints: Vec<String> = vec![];
+        // the lines of a render worker, or of the thread of this call
+        let done = self.pending.take(log, |x| x.0.is_none() || x.0 == log.threadid);
+        for (_, detail) in done {
+            match detail {
+                Detail::Completed(execution, render) => {
+                    row.execution_ms = execution;
+                    row.render_ms = render;
+                },
+                Detail::Execution(hash) => {
+                    row.executions += 1;
+                    if !fingerprints.contains(&hash) {
+                        fingerprints.push(hash);
+                    }
+                },
+                Detail::Launch => row.kernel_launches += 1,
+            }
+        }
+        self.pending.expire();
+        row.fingerprints = fingerprints.join(",");
+        self.rows.push(row);
+
+        let key = (server_of(log).unwrap_or_default(), log.pid);
+        if let Some(open) = self.open.get_mut(&key) {
+            open.remove(0);
+            if open.is_empty() {
+                self.open.remove(&key);
+            }
+        }
+    }
+}
+
+impl LogWriter for VegaWriter {
+    fn write(&mut self, log: &LogLine) -> SResult<()> {
+        lazy_static! {
+            static ref RE_COMPLETED: regex::Regex = regex::Regex::new(
+                r"^render_vega-COMPLETED .*Total Execution: ([0-9]+) \(ms\), Total Render: ([0-9]+) \(ms\)").unwrap();
+            static ref RE_CALCITE: regex::Regex = regex::Regex::new(r"(?s)^User calcite catalog \S+ sql '(.*)'$").unwrap();
+        }
+        let key = (server_of(log).unwrap_or_default(), log.pid);
+        let thread = log.threadid.map(|x| (key.0.clone(), key.1, x));
+        // renders whose stdlog never came, like CallLines
+        if let Some(latest) = self.expire.tick(log.logtime) {
+            let begun = latest - chrono::Duration::seconds(MAX_CALL_SECONDS);
+            self.open.retain(|_, x| {
+                x.retain(|x| *x >= begun);
+                !x.is_empty()
+            });
+            self.threads.retain(|_, x| x.1 >= begun);
+        }
+        if let (Some(event), Some(_)) = (log.event.as_deref(), log.sequence) {
+            if let Some(thread) = &thread {
+                match event.strip_suffix("_begin") {
+                    Some(call) => self.threads.insert(thread.clone(), (call == "render_vega", log.logtime)),
+                    None => self.threads.remove(thread),
+                };
+            }
+        }
+        match (log.event.as_deref(), log.sequence) {
+            (Some("render_vega_begin"), Some(_)) => self.open.entry(key).or_default().push(log.logtime),
+            (Some("render_vega"), Some(_)) => self.call(log),
+            _ => if self.open.contains_key(&key) {
+                let render = match thread.and_then(|x| self.threads.get(&x)) {
+                    // a line of another call on the thread
+                    Some((false, _)) => return Ok(()),
+                    Some((true, _)) => log.threadid,
+                    None => None,
+                };
+                let msg = log.msg.trim();
+                let detail = if let Some(caps) = RE_COMPLETED.captures(msg) {
+                    Detail::Completed(caps[1].parse().ok(), caps[2].parse().ok())
+                } else if let Some(caps) = RE_CALCITE.captures(msg) {
+                    Detail::Execution(fingerprint_hash(&fingerprint(&caps[1])))
+                } else if msg.starts_with("Launching ") && msg.ends_with(" kernels for query.") {
+                    Detail::Launch
+                } else {
+                    return Ok(())
+                };
+                self.pending.push(log, (render, detail));
+            },
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> SResult<()> {
+        write_report(&self.rows(), &self.options)
+    }
+}
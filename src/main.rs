@@ -28,6 +28,45 @@ use pager::Pager;
 
 use colored;
 
+// --startup N or N,M
+fn parse_startups(value: &str) -> Result<Vec<i64>, String> {
+    value.split(',').map(|x| x.trim().parse().map_err(|_| format!("expected N or N,M, like -2,-1: '{}'", value))).collect()
+}
+
+// --bruteforce failures/minutes
+fn parse_brute_force(value: &str) -> Result<(usize, i64), String> {
+    let error = || format!("expected failures/minutes, like 5/10: '{}'", value);
+    let values: Vec<&str> = value.split('/').map(|x| x.trim()).collect();
+    match values[..] {
+        [failures, minutes] => Ok((failures.parse().map_err(|_| error())?, minutes.parse().map_err(|_| error())?)),
+        _ => Err(error()),
+    }
+}
+
+fn is_startups(value: String) -> Result<(), String> {
+    parse_startups(&value).map(|_| ())
+}
+
+fn is_brute_force(value: String) -> Result<(), String> {
+    parse_brute_force(&value).map(|_| ())
+}
+
+// a comma separated list of some of the names
+fn is_names(value: &str, names: &[&str]) -> Result<(), String> {
+    match value.split(',').map(|x| x.trim()).find(|x| !names.contains(x)) {
+        Some(x) => Err(format!("expected {} (comma separated): '{}'", names.join(", "), x)),
+        None => Ok(()),
+    }
+}
+
+fn is_dimensions(value: String) -> Result<(), String> {
+    is_names(&value, &["event", "operation", "dbname", "username", "client", "dashboardid", "hostname"])
+}
+
+fn is_redact_rules(value: String) -> Result<(), String> {
+    is_names(&value, &["credentials", "literals", "ids"])
+}
+
 fn main() -> log_parser::SResult<()> {
     let params = clap_app!(myapp =>
         (name: crate_name!())
@@ -38,6 +77,10 @@ fn main() -> log_parser::SResult<()> {
         // TODO implement more filter tags: vega, exec, ops, connect, version, failure, error, warning
         (@arg FILTER: -f --filter +takes_value "Filter logs: all, sql, select")
 
+        (@arg MINSEVERITY: --("min-severity") +takes_value "Drop lines below: DEBUG, INFO, WARNING, ERROR, FATAL")
+
+        (@arg MAXVERBOSITY: --("max-verbosity") +takes_value "Drop DEBUG lines above this level, 0 to 4, like log-severity = DEBUG4")
+
         // TODO select
         // (@arg SELECT: -s --select +takes_value "Select column sets: all, min, exec, ...")
 
@@ -45,8 +88,47 @@ fn main() -> log_parser::SResult<()> {
 
         // TODO arg file index selector: "-1", -5..-1", "..-1"
 
-        // TODO arg output format type: json, load_table, kafka
-        (@arg TYPE: -t --type +takes_value "Output format: csv, json, tsv, terminal, sql, execute, load (default: terminal)")
+        // TODO arg output format type: json, load_table
+        (@arg TYPE: -t --type +takes_value "Output format: csv, json, tsv, terminal, tui, top, metrics, otlp, chrome-trace, es-bulk, loki, syslog, kafka, timers, folded, qmd, plans, sql, execute, load (default: terminal)
+Or a report: digest, stats, errors, sessions, audit, config, hardware, memory, locks, tables, vega
+Or es-template, the index template for es-bulk")
+
+        (@arg FORMAT: --format +takes_value possible_values(&["terminal", "csv", "json", "markdown"]) "Report format: terminal, csv, json, markdown (default: terminal)")
+
+        (@arg GROUP: --group +takes_value {is_dimensions} "Group stats by: event, operation, dbname, username, client, dashboardid, hostname (default: event)")
+
+        (@arg BUCKET: --bucket +takes_value possible_values(&["minute", "hour", "day"]) "Group stats by time: minute, hour, day")
+
+        (@arg BRUTEFORCE: --bruteforce +takes_value {is_brute_force} "Failed logins/minutes that the audit reports as brute force (default: 5/10)")
+
+        (@arg TEMPLATES: --templates +takes_value "Mine msg templates into this file, msg_norm becomes the template")
+
+        (@arg BASELINE: --baseline +takes_value "Previous errors report, csv or json, to mark which errors are new")
+
+        (@arg SESSION: --session +takes_value "Session id, to list the activity of one session with -t sessions")
+
+        (@arg STARTUP: --startup +takes_value +allow_hyphen_values {is_startups} "Startup for -t config, N for its options or N,M to compare them, 1 is the first and -1 the last")
+
+        (@arg REDACT: --redact +takes_value {is_redact_rules} "Redact before output: credentials, literals, ids (comma separated)")
+
+        (@arg REDACTKEY: --redactkey +takes_value "Secret key for the hashes of ids, required by --redact ids")
+
+        (@arg MASK: --mask +takes_value +multiple number_of_values(1) "Regex to replace with *** in query and msg, can be repeated")
+
+        (@arg LISTEN: --listen +takes_value "Address for the /metrics endpoint of -t metrics (default: 127.0.0.1:9184)")
+
+        (@arg ENDPOINT: --endpoint +takes_value "URL to send to instead of OUTPUT, like http://localhost:4318/v1/traces for -t otlp
+For -t loki (default: http://localhost:3100/loki/api/v1/push)
+For -t syslog udp://host:port, tcp://host:port or unix:///path (default: udp://127.0.0.1:514)
+For -t kafka the brokers, comma separated (default: localhost:9092)")
+
+        (@arg INDEX: --index +takes_value "Index name for -t es-bulk, strftime of logtime (default: omnisci-logs-%Y.%m.%d)")
+
+        (@arg TOPIC: --topic +takes_value "Topic for -t kafka (default: omnisci-logs)")
+
+        (@arg CHECKPOINT: --checkpoint +takes_value "File of the last line -t kafka delivered from each input, to resume from")
+
+        (@arg KAFKACONFIG: --("kafka-config") +takes_value "File of librdkafka properties for -t kafka, a name=value per line, like security.protocol=SASL_SSL")
 
         (@arg OUTPUT: -o --output +takes_value "Ouput file, or if a dir, then output files as OUTPUT/INPUT.csv")
 
@@ -67,7 +149,32 @@ fn main() -> log_parser::SResult<()> {
         (after_help: "EXAMPLES:
     omnisci-log-scraper /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
     omnisci-log-scraper -t csv /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > log.csv
-    omnisci-log-scraper -f select -t sql /var/lib/omnisci/data/mapd_log/omnisci_server.INFO | omnisql")
+    omnisci-log-scraper -t csv --max-verbosity 1 /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > log.csv
+    omnisci-log-scraper -f select -t sql /var/lib/omnisci/data/mapd_log/omnisci_server.INFO | omnisql
+    omnisci-log-scraper -t tui --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t top --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t otlp --endpoint http://localhost:4318/v1/traces --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t chrome-trace -o trace.json /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t es-bulk --index omnisci-logs-%Y.%m /var/lib/omnisci/data/mapd_log/omnisci_server.INFO > bulk.ndjson
+    omnisci-log-scraper -t loki --endpoint http://loki:3100/loki/api/v1/push --hostname $(hostname) --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t syslog --endpoint tcp://syslog:601 --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t kafka --endpoint kafka1:9092,kafka2:9092 --topic omnisci-logs --checkpoint kafka.json --follow /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t folded /var/lib/omnisci/data/mapd_log/omnisci_server.INFO | flamegraph.pl > timers.svg
+    omnisci-log-scraper -t qmd /var/lib/omnisci/data/mapd_log/omnisci_server.INFO > qmd.csv
+    omnisci-log-scraper -t plans /var/lib/omnisci/data/mapd_log/omnisci_server.INFO > plans.csv
+    omnisci-log-scraper -t metrics --listen 0.0.0.0:9184 /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t digest --format csv /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > digest.csv
+    omnisci-log-scraper -t stats --group event,dbname --bucket hour /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t errors --baseline last_week.csv --format markdown /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t audit --bruteforce 10/5 --format json /var/lib/omnisci/data/mapd_log/omnisci_server.INFO > siem.json
+    omnisci-log-scraper -t csv --redact credentials,literals,ids --redactkey $KEY /var/lib/omnisci/data/mapd_log/omnisci_server.INFO > log.csv
+    omnisci-log-scraper -t sessions --session 409-as47 /var/lib/omnisci/data/mapd_log/omnisci_server.INFO
+    omnisci-log-scraper -t config --startup -2,-1 /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log
+    omnisci-log-scraper -t hardware --format csv --hostname $(hostname) /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > hardware.csv
+    omnisci-log-scraper -t memory --format csv --bucket day /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > memory.csv
+    omnisci-log-scraper -t locks --format csv /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > locks.csv
+    omnisci-log-scraper -t tables --format csv /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > tables.csv
+    omnisci-log-scraper -t vega --format csv /var/lib/omnisci/data/mapd_log/omnisci_server.INFO.*.log > vega.csv")
     ).get_matches();
 
     if params.is_present("DRYRUN") {
@@ -93,13 +200,40 @@ fn main() -> log_parser::SResult<()> {
 
     let output = params.value_of("OUTPUT");
     let db = params.value_of("DB");
+    let sink = log_parser::SinkOptions {
+        endpoint: params.value_of("ENDPOINT").map(|x| x.to_string()),
+        index: params.value_of("INDEX").map(|x| x.to_string()),
+        // without the kafka feature, -t kafka is an error and these are not needed
+        #[cfg(feature = "kafka")]
+        topic: params.value_of("TOPIC").map(|x| x.to_string()),
+        #[cfg(feature = "kafka")]
+        checkpoint: params.value_of("CHECKPOINT").map(|x| x.to_string()),
+        #[cfg(feature = "kafka")]
+        kafka_config: params.value_of("KAFKACONFIG").map(|x| x.to_string()),
+    };
     let hostname = params.value_of("HOSTNAME");
+    let templates = params.value_of("TEMPLATES");
 
-    let filter = match params.value_of("FILTER") {
-        None => "all",
-        Some(x) => x,
+    let redactor = match (params.value_of("REDACT"), params.values_of("MASK")) {
+        (None, None) => None,
+        (rules, masks) => {
+            let rules = match rules {
+                None => vec![],
+                Some(x) => x.split(",").map(|x| log_parser::redact::RedactRule::new(x.trim())).collect(),
+            };
+            let masks: Vec<&str> = match masks {
+                None => vec![],
+                Some(x) => x.collect(),
+            };
+            Some(log_parser::redact::Redactor::new(rules, &masks, params.value_of("REDACTKEY"))?)
+        },
     };
-    let filter: Vec<&str> = filter.split(",").map(|x| x.trim()).collect();
+
+    let filter = log_parser::LineFilter::new(
+        params.value_of("FILTER").unwrap_or("all"),
+        params.value_of("MINSEVERITY"),
+        params.value_of("MAXVERBOSITY"),
+    )?;
 
     let follow = params.is_present("follow");
 
@@ -108,6 +242,61 @@ fn main() -> log_parser::SResult<()> {
         Some(x) => log_parser::OutputType::new(x),
     };
 
+    if let log_parser::OutputType::Tui = output_type {
+        return log_parser::browser::browse_logs(&inputs, &filter, hostname, follow, templates, redactor.as_ref())
+    }
+
+    if let log_parser::OutputType::Top = output_type {
+        return log_parser::top::top_logs(&inputs, &filter, hostname, follow, templates, redactor.as_ref())
+    }
+
+    if let log_parser::OutputType::EsTemplate = output_type {
+        let index = sink.index.as_deref().unwrap_or(log_parser::es::DEFAULT_INDEX);
+        println!("{:#}", log_parser::es::index_template(index));
+        return Ok(())
+    }
+
+    if let log_parser::OutputType::Metrics = output_type {
+        let listen = params.value_of("LISTEN").unwrap_or(log_parser::metrics::DEFAULT_LISTEN);
+        return log_parser::metrics::serve_metrics(&inputs, &filter, hostname, listen)
+    }
+
+    if output_type.is_report() {
+        if follow {
+            return Err(format!("--follow is not supported by -t {}, a report is written once all inputs are read",
+                params.value_of("TYPE").unwrap_or("")).into())
+        }
+        let mut options = log_parser::ReportOptions {
+            format: match params.value_of("FORMAT") {
+                None => log_parser::ReportFormat::Terminal,
+                Some(x) => log_parser::ReportFormat::new(x),
+            },
+            output: output.map(|x| x.to_string()),
+            group_by: match params.value_of("GROUP") {
+                None => vec![],
+                Some(x) => x.split(",").map(|x| log_parser::Dimension::new(x.trim())).collect(),
+            },
+            bucket: params.value_of("BUCKET").map(log_parser::TimeBucket::new),
+            baseline: params.value_of("BASELINE").map(|x| x.to_string()),
+            session: params.value_of("SESSION").map(|x| x.to_string()),
+            startups: match params.value_of("STARTUP") {
+                None => vec![],
+                Some(x) => parse_startups(x)?,
+            },
+            ..Default::default()
+        };
+        if let Some(x) = params.value_of("BRUTEFORCE") {
+            let (failures, minutes) = parse_brute_force(x)?;
+            options.brute_force_failures = failures;
+            options.brute_force_minutes = minutes;
+        }
+        return log_parser::report_logs(&inputs, &filter, &output_type, hostname, templates, redactor.as_ref(), &options)
+    }
+
+    if let (log_parser::OutputType::ChromeTrace, None) = (&output_type, output) {
+        return log_parser::trace_logs(&inputs, &filter, hostname, follow, templates, redactor.as_ref())
+    }
+
     // TODO if OUTPUT file is set, disable terminal, default to csv
     match output_type {
         log_parser::OutputType::Terminal => if ! follow {
@@ -122,7 +311,7 @@ fn main() -> log_parser::SResult<()> {
     }
 
     for input in inputs {
-        match log_parser::transform_logs(&input, output, &filter, &output_type, db, hostname, follow) {
+        match log_parser::transform_logs(&input, output, &filter, &output_type, db, hostname, follow, templates, redactor.as_ref(), &sink) {
             Ok(_) => continue,
             Err(x) => return Err(x),
         };
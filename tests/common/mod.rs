@@ -0,0 +1,72 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// Helpers of the tests, each test uses some of them
+#![allow(dead_code)]
+
+use omnisci_log_scraper::log_parser as olog;
+
+use std::io::{BufReader, Write};
+use std::sync::{Arc, Mutex};
+
+pub fn pln(line: &str) -> olog::LogLine {
+    let mut rec = olog::LogLine::new(line.trim()).unwrap();
+    rec.parse_msg();
+    rec
+}
+
+// the lines of a log, with their continuation lines
+pub fn parse(log: &str) -> Vec<olog::LogLine> {
+    let mut reader = BufReader::new(log.as_bytes());
+    olog::ParsingLine::new(&mut reader, false).map(|x| x.unwrap()).collect()
+}
+
+// a line of an input file, as scrape_log sets its logfile
+pub fn input(line: &str, logfile: &str) -> olog::LogLine {
+    let mut rec = pln(line);
+    rec.logfile = Some(logfile.to_string());
+    rec
+}
+
+// the stdlog of a sql_execute on thread 3, with the sequence as its query
+pub fn sql_execute(time: &str, sequence: i32, dbname: &str, total_time: i32) -> olog::LogLine {
+    pln(&format!(r#"{} I 15 3 DBHandler.cpp:1058 stdlog sql_execute {} {} {} admin 409-as47 {{"query_str","client","nonce","execution_time_ms","total_time_ms"}} {{"select {};","tcp:172.17.0.1:53140","","{}","{}"}}"#,
+        time, sequence, total_time, dbname, sequence, total_time - 1, total_time))
+}
+
+// the output of a writer, shared to read it back
+#[derive(Clone)]
+pub struct Buffer(pub Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    pub fn new() -> Buffer {
+        Buffer(Arc::new(Mutex::new(vec![])))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
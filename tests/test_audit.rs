@@ -0,0 +1,146 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+
+mod common;
+use common::pln;
+
+// A failed connect, like the server logs it: neither the begin nor the failure line
+// have the user, only the stdlog at the end of the call has it in its name values, with the client.
+fn failed_login(writer: &mut dyn LogWriter, time: &str, sequence: i32, username: &str, client: &str) -> olog::SResult<()> {
+    writer.write(&pln(&format!("{} I 15 DBHandler.cpp:533 stdlog_begin connect {} 0     ", time, sequence)))?;
+    writer.write(&pln(&format!("{} E 15 7 DBHandler.cpp:640 Authentication failure", time)))?;
+    writer.write(&pln(&format!(r#"{} I 15 DBHandler.cpp:533 stdlog connect {} 0     {{"user","db","exception","client"}} {{"{}","omnisci","Authentication failure","{}"}}"#,
+        time, sequence, username, client)))
+}
+
+#[test]
+fn audit() -> olog::SResult<()> {
+    let mut audit = olog::audit::AuditWriter::new(olog::ReportOptions::default());
+    for minute in [0, 2, 4, 6, 8, 9, 40] {
+        failed_login(&mut audit, &format!("2021-08-05T21:{:02}:00.000000", minute), minute, "mallory", "tcp:10.0.0.66:4000")?;
+    }
+    failed_login(&mut audit, "2021-08-05T22:10:00.000000", 50, "admin", "tcp:10.0.0.1:4000")?;
+    for line in [
+        r#"2021-08-05T22:20:00.000000 I 15 8 DBHandler.cpp:1058 stdlog sql_execute 1 10 omnisci bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"GRANT SELECT ON TABLE flights TO analyst;","tcp:10.0.0.2:5000","","9","10"}"#,
+        r#"2021-08-05T22:21:00.000000 E 15 8 DBHandler.cpp:200 Unauthorized Access: user bob does not have SELECT privilege on table secrets"#,
+        r#"2021-08-05T22:22:00.000000 I 15 8 DBHandler.cpp:1058 stdlog sql_execute 2 10 omnisci bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"select 1;","tcp:10.0.0.2:5000","","9","10"}"#,
+        r#"2021-08-05T22:23:00.000000 I 15 8 DBHandler.cpp:1058 stdlog sql_execute 3 10 sales bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"DROP TABLE old;","tcp:10.0.0.2:5000","","9","10"}"#,
+    ] {
+        audit.write(&pln(line))?;
+    }
+
+    let rows = audit.rows();
+    let categories: Vec<&str> = rows.iter().map(|x| x.category.as_str()).collect();
+    assert_eq!(categories, vec![
+        "brute_force",
+        "failed_login", "failed_login",
+        "privilege_error", "ddl", "ddl",
+        "access", "access"]);
+
+    // 21:00 to 21:09 has 6 failures within 10 minutes, 21:40 is alone
+    let row = &rows[0];
+    assert_eq!(row.username, "mallory");
+    assert_eq!(row.client, "10.0.0.66");
+    assert_eq!(row.count, 6);
+    assert_eq!(row.time, "2021-08-05 21:00:00");
+    assert_eq!(row.end, "2021-08-05 21:09:00");
+
+    assert_eq!(rows[1].count, 7);
+    assert_eq!(rows[2].username, "admin");
+
+    assert_eq!(rows[3].username, "bob");
+    assert_eq!(rows[3].client, "10.0.0.2");
+    assert_eq!(rows[3].event, "unauthorized_access");
+    assert_eq!(rows[4].detail, "GRANT SELECT ON TABLE flights TO analyst;");
+    assert_eq!(rows[5].dbname, "sales");
+
+    assert_eq!(rows[6].dbname, "omnisci");
+    assert_eq!(rows[6].count, 2);
+    Ok(())
+}
+
+#[test]
+fn audit_brute_force_threshold() -> olog::SResult<()> {
+    let options = olog::ReportOptions {
+        brute_force_failures: 3,
+        brute_force_minutes: 1,
+        ..Default::default()
+    };
+    let mut audit = olog::audit::AuditWriter::new(options);
+    for second in [0, 10, 20, 50, 55] {
+        failed_login(&mut audit, &format!("2021-08-05T21:00:{:02}.000000", second), second, "mallory", "tcp:10.0.0.66:4000")?;
+    }
+    for second in [0, 30] {
+        failed_login(&mut audit, &format!("2021-08-05T21:05:{:02}.000000", second), 100 + second, "mallory", "tcp:10.0.0.66:4000")?;
+    }
+
+    let rows: Vec<olog::audit::AuditRow> = audit.rows().into_iter().filter(|x| x.category == "brute_force").collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].count, 5);
+    assert_eq!(rows[0].detail, "3 or more failed logins within 1 minutes");
+    Ok(())
+}
+
+#[test]
+fn failed_login_of_its_connect() -> olog::SResult<()> {
+    let mut audit = olog::audit::AuditWriter::new(olog::ReportOptions::default());
+    let host = |hostname: &str, line: &str| {
+        let mut log = pln(line);
+        log.hostname = Some(hostname.to_string());
+        log
+    };
+    for line in [
+        // an earlier call of another client on the same thread
+        r#"2021-08-05T21:00:00.000000 I 15 7 DBHandler.cpp:1058 stdlog sql_execute 1 10 omnisci bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"select 1;","tcp:10.0.0.2:5000","","9","10"}"#,
+        "2021-08-05T21:01:00.000000 I 15 7 DBHandler.cpp:533 stdlog_begin connect 2 0",
+        // a local login that fails, then the error of the call, is one failed login
+        "2021-08-05T21:01:00.001000 W 15 7 DBHandler.cpp:400 Local login failed",
+        "2021-08-05T21:01:00.001000 E 15 7 DBHandler.cpp:640 Authentication failure",
+        r#"2021-08-05T21:01:00.002000 I 15 7 DBHandler.cpp:533 stdlog connect 2 2     {"user","db","exception","client"} {"mallory","omnisci","Authentication failure","tcp:10.0.0.66:4000"}"#,
+    ] {
+        audit.write(&host("db1", line))?;
+    }
+    // a failure of thread 8, then the successful connect of thread 9 ends first
+    for line in [
+        "2021-08-05T21:01:30.000000 I 15 8 DBHandler.cpp:533 stdlog_begin connect 4 0",
+        "2021-08-05T21:01:30.000000 I 15 9 DBHandler.cpp:533 stdlog_begin connect 5 0",
+        "2021-08-05T21:01:30.001000 E 15 8 DBHandler.cpp:640 Authentication failure",
+        r#"2021-08-05T21:01:30.002000 I 15 9 DBHandler.cpp:533 stdlog connect 5 2 omnisci alice 456-AbCd {"client"} {"tcp:10.0.0.3:5000"}"#,
+        r#"2021-08-05T21:01:30.003000 I 15 8 DBHandler.cpp:533 stdlog connect 4 3     {"user","db","exception","client"} {"oscar","omnisci","Authentication failure","tcp:10.0.0.88:4000"}"#,
+    ] {
+        audit.write(&host("db1", line))?;
+    }
+    // the same pid on another server, whose connect is not logged yet
+    audit.write(&host("db2", "2021-08-05T21:02:00.000000 I 15 7 DBHandler.cpp:533 stdlog_begin connect 2 0"))?;
+    audit.write(&host("db2", "2021-08-05T21:02:00.001000 E 15 7 DBHandler.cpp:640 Authentication failure"))?;
+    audit.write(&host("db1", r#"2021-08-05T21:02:00.002000 I 15 7 DBHandler.cpp:533 stdlog connect 3 0     {"user","db","exception","client"} {"eve","omnisci","Authentication failure","tcp:10.0.0.99:4000"}"#))?;
+    audit.write(&host("db2", r#"2021-08-05T21:02:00.003000 I 15 7 DBHandler.cpp:533 stdlog connect 2 2     {"user","db","exception","client"} {"trudy","omnisci","Authentication failure","tcp:10.0.0.77:4000"}"#))?;
+
+    let rows: Vec<olog::audit::AuditRow> = audit.rows().into_iter().filter(|x| x.category == "failed_login").collect();
+    let logins: Vec<(&str, &str, &str, u64)> = rows.iter()
+        .map(|x| (x.hostname.as_str(), x.username.as_str(), x.client.as_str(), x.count))
+        .collect();
+    assert_eq!(logins, vec![
+        ("db1", "mallory", "10.0.0.66", 1),
+        ("db1", "oscar", "10.0.0.88", 1),
+        ("db2", "trudy", "10.0.0.77", 1),
+    ]);
+    Ok(())
+}
@@ -0,0 +1,151 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::browser::Browser;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+mod common;
+use common::pln;
+
+fn key(c: char) -> KeyEvent {
+    KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+}
+
+fn browser(follow: bool) -> Browser {
+    let mut browser = Browser::new(follow);
+    for line in [
+        r#"2020-07-01T00:00:00.000105 I 15 1 DBHandler.cpp:533 stdlog connect 0 233 omnisci admin 409-as47 {"client","roles"} {"tcp:172.17.0.1:53140","super"}"#,
+        r#"2020-07-01T00:00:02.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 1 0 omnisci admin 409-as47 {"query_str"} {"SELECT count(*) from omnisci_states where a = 'x and y' and b > 1;"}"#,
+        r#"2020-07-01T00:00:02.500000 I 15 3 Calcite.cpp:513 Time in Thrift 13 (ms), Time in Java Calcite server 532 (ms)"#,
+        r#"2020-07-01T00:00:02.600000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found"#,
+        r#"2020-07-01T00:00:03.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 1 906 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from omnisci_states where a = 'x and y' and b > 1;","tcp:172.17.0.1:53140","","905","906"}"#,
+    ] {
+        browser.push(pln(line));
+    }
+    browser
+}
+
+#[test]
+fn pretty_sql() {
+    assert_eq!(olog::browser::pretty_sql("SELECT a,  b FROM t JOIN u ON t.id = u.id WHERE a = 'x where y' AND b > 1 GROUP BY a ORDER BY b LIMIT 10"),
+        "SELECT a, b\nFROM t\nJOIN u\nON t.id = u.id\nWHERE a = 'x where y'\n  AND b > 1\nGROUP BY a\nORDER BY b\nLIMIT 10");
+}
+
+#[test]
+fn filter_and_search() -> olog::SResult<()> {
+    let mut browser = browser(false);
+    assert_eq!(browser.len(), 5);
+    assert_eq!(browser.selected(), 0);
+
+    browser.set_filter("event~sql dur_ms>100")?;
+    assert_eq!(browser.len(), 1);
+    assert_eq!(browser.selected_log().unwrap().dur_ms, Some(906));
+
+    browser.set_filter("severity=error")?;
+    assert_eq!(browser.len(), 1);
+    browser.set_filter("thrift")?;
+    assert_eq!(browser.selected_log().unwrap().fileline, "Calcite.cpp:513");
+    assert!(browser.set_filter("nosuchcolumn=1").is_err());
+
+    // clearing the filter keeps the selected line
+    browser.set_filter("")?;
+    assert_eq!(browser.len(), 5);
+    assert_eq!(browser.selected(), 2);
+
+    assert!(browser.search("OMNISCI_STATES", true, 3));
+    assert_eq!(browser.selected(), 4);
+    // wraps around
+    assert!(browser.search("omnisci_states", true, 5));
+    assert_eq!(browser.selected(), 1);
+    assert!(browser.search("omnisci_states", false, 0));
+    assert_eq!(browser.selected(), 4);
+    assert!(!browser.search("no such text", true, 0));
+    Ok(())
+}
+
+#[test]
+fn keys() {
+    let mut browser = browser(false);
+    // incremental search
+    for c in "/failed".chars() {
+        assert!(!browser.handle_key(key(c)));
+    }
+    browser.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert_eq!(browser.selected(), 3);
+
+    // begin and end of the same stdlog call
+    browser.handle_key(key('G'));
+    assert_eq!(browser.selected(), 4);
+    browser.handle_key(key('%'));
+    assert_eq!(browser.selected(), 1);
+    browser.handle_key(key('%'));
+    assert_eq!(browser.selected(), 4);
+
+    // hide the first column
+    assert!(browser.columns[0].visible);
+    browser.handle_key(key('c'));
+    browser.handle_key(key(' '));
+    browser.handle_key(key('+'));
+    browser.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+    assert!(!browser.columns[0].visible);
+    assert_eq!(browser.columns[0].width, 28);
+
+    browser.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    assert!(browser.detail);
+    let detail = browser.detail_lines();
+    assert_eq!(detail[1], "event sql_execute sequence 1 dur_ms 906 execution_time 905 total_time 906");
+    assert_eq!(detail[2], "session 409-as47 dbname omnisci username admin client tcp:172.17.0.1:53140");
+    assert!(detail.contains(&String::from("SELECT count(*)")));
+    assert_eq!(detail.last().unwrap(), "  and b > 1;");
+
+    assert!(browser.handle_key(key('q')));
+}
+
+#[test]
+fn follow_tails() {
+    let mut browser = browser(true);
+    assert_eq!(browser.selected(), 4);
+    browser.push(pln("2020-07-01T00:00:04.000000 I 15 3 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)"));
+    assert_eq!(browser.selected(), 5);
+
+    // unless the user scrolled up
+    browser.select(0);
+    browser.push(pln("2020-07-01T00:00:05.000000 I 15 3 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)"));
+    assert_eq!(browser.selected(), 0);
+}
+
+#[test]
+fn oldest_lines_dropped() -> olog::SResult<()> {
+    let mut browser = Browser::new(true).with_max_lines(3);
+    for second in 0..5 {
+        browser.push(pln(&format!("2020-07-01T00:00:0{}.000000 I 15 3 Calcite.cpp:513 Time in Thrift {} (ms), Time in Java Calcite server 2 (ms)", second, second)));
+    }
+    assert_eq!((browser.len(), browser.dropped()), (3, 2));
+    assert_eq!(browser.selected(), 2);
+    assert!(browser.selected_log().unwrap().msg.contains("Thrift 4 "));
+
+    // the filter only sees the lines kept
+    browser.set_filter("msg~thrift")?;
+    assert_eq!(browser.len(), 3);
+    browser.select(0);
+    assert!(browser.selected_log().unwrap().msg.contains("Thrift 2 "));
+    browser.push(pln("2020-07-01T00:00:05.000000 I 15 3 Calcite.cpp:513 Time in Thrift 5 (ms), Time in Java Calcite server 2 (ms)"));
+    assert!(browser.selected_log().unwrap().msg.contains("Thrift 3 "));
+    Ok(())
+}
@@ -0,0 +1,109 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::chrome_trace::ChromeTraceWriter;
+
+use serde_json::Value;
+
+mod common;
+use common::{pln, Buffer};
+
+fn trace(lines: &[&str]) -> (ChromeTraceWriter, Buffer) {
+    let buffer = Buffer::new();
+    let mut writer = ChromeTraceWriter::new(Box::new(buffer.clone()));
+    for line in lines {
+        writer.write(&pln(line)).unwrap();
+    }
+    (writer, buffer)
+}
+
+#[test]
+fn chrome_trace() -> olog::SResult<()> {
+    let (mut writer, buffer) = trace(&[
+        "2020-07-01T00:00:00.000000 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b",
+        r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 7 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#,
+        "2020-07-01T00:00:01.700000 I 15 3 Calcite.cpp:513 Time in Thrift 6 (ms), Time in Java Calcite server 692 (ms)",
+        "2020-07-01T00:00:01.800000 I 15 7 BufferMgr.cpp:303 ALLOCATION slab of 8388608 pages (4294967296B) created in 5 ms GPU_MGR:1",
+        "2020-07-01T00:00:01.900000 I 15 7 BufferMgr.cpp:303 ALLOCATION slab of 8388608 pages (4294967296B) created in 5 ms GPU_MGR:1",
+        "2020-07-01T00:00:01.950000 I 15 3 Execute.cpp:100 Query ran out of GPU memory, attempting punt to CPU",
+        "2020-07-01T00:00:02.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+        r#"2020-07-01T00:00:02.500000 I 15 9 DBHandler.cpp:1058 stdlog sql_execute 7 1500 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT 1;","tcp:172.17.0.1:53140","","1499","1500"}"#,
+    ]);
+    writer.close()?;
+    let events: Vec<Value> = serde_json::from_str(&buffer.text())?;
+    let find = |ph: &str, name: &str| events.iter().find(|x| x["ph"] == ph && x["name"] == name).unwrap().clone();
+
+    assert_eq!(find("M", "process_name")["args"]["name"], "omnisci_server 15");
+    let threads: Vec<i64> = events.iter().filter(|x| x["name"] == "thread_name").map(|x| x["tid"].as_i64().unwrap()).collect();
+    // counters are per process, and the stdlog is on the thread of its stdlog_begin
+    assert_eq!(threads, vec![0, 3]);
+
+    assert_eq!(find("i", "OmniSci Server 5.4.1-20200925-6728b9462b")["s"], "g");
+    let error = find("i", "Query execution failed with error Table 12 not found");
+    assert_eq!(error["s"], "t");
+    assert_eq!(error["tid"], 3);
+
+    // on the thread of the stdlog_begin
+    let call = find("X", "sql_execute");
+    assert_eq!(call["ts"], 1593561601000000i64);
+    assert_eq!(call["dur"], 1500000);
+    assert_eq!(call["tid"], 3);
+    assert_eq!(call["args"]["query"], "SELECT 1;");
+    assert_eq!(call["args"]["dur_ms"], 1500);
+
+    let parse = find("X", "sql_parse");
+    assert_eq!(parse["dur"], 692000);
+    assert_eq!(parse["ts"], 1593561601008000i64);
+
+    let slabs: Vec<&Value> = events.iter().filter(|x| x["name"] == "buffer pool slab bytes").collect();
+    assert_eq!(slabs[1]["args"]["GPU 1"], 8589934592i64);
+    assert_eq!(find("C", "gpu allocation failures")["args"]["punt_to_cpu"], 1);
+    Ok(())
+}
+
+#[test]
+fn chrome_trace_expired_begin() -> olog::SResult<()> {
+    let (mut writer, buffer) = trace(&[
+        r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 7 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#,
+        "2020-07-01T02:00:00.000000 I 15 3 DBHandler.cpp:100 checkpoint",
+        // the stdlog_begin expired, so the start is from dur_ms
+        r#"2020-07-01T02:00:01.000000 I 15 9 DBHandler.cpp:1058 stdlog sql_execute 7 1500 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#,
+    ]);
+    writer.close()?;
+    let events: Vec<Value> = serde_json::from_str(&buffer.text())?;
+    let call = events.iter().find(|x| x["ph"] == "X" && x["name"] == "sql_execute").unwrap();
+    assert_eq!(call["dur"], 1500000);
+    assert_eq!(call["tid"], 9);
+    Ok(())
+}
+
+#[test]
+fn chrome_trace_follow() -> olog::SResult<()> {
+    // before close, the array can be closed by the reader
+    let (mut writer, buffer) = trace(&["2020-07-01T00:00:00.000000 F 15 0 DBHandler.cpp:100 Interrupt signal (2) received."]);
+    let events: Vec<Value> = serde_json::from_str(&format!("{}]", buffer.text()))?;
+    assert_eq!(events.last().unwrap()["s"], "p");
+
+    writer.close()?;
+    let (mut empty, buffer) = trace(&[]);
+    empty.close()?;
+    let events: Vec<Value> = serde_json::from_str(&buffer.text())?;
+    assert!(events.is_empty());
+    Ok(())
+}
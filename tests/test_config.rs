@@ -0,0 +1,138 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::config::{parse_option, ConfigWriter};
+
+mod common;
+use common::{input, pln};
+
+// two starts of the same container, so the same pid
+const LINES: [&str; 9] = [
+    "2020-07-01T00:00:00.000001 I 15 0 CommandLineOptions.cpp:1328  Max CPU buffer pool slab size 4294967296",
+    "2020-07-01T00:00:00.000002 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to true",
+    "2020-07-01T00:00:00.000003 I 15 0 CommandLineOptions.cpp:1083  Maximum Idle session duration 60",
+    "2020-07-01T00:00:00.000004 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b",
+    "2020-07-02T00:00:00.000001 I 15 0 CommandLineOptions.cpp:1328  Max CPU buffer pool slab size 4294967296",
+    "2020-07-02T00:00:00.000002 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to false",
+    "2020-07-02T00:00:00.000003 I 15 0 CommandLineOptions.cpp:1337  Allow Local Auth Fallback: disabled",
+    "2020-07-02T00:00:00.000004 I 15 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2",
+    "2020-07-02T00:00:00.000005 I 15 0 DBHandler.cpp:338 Started in GPU mode",
+];
+
+#[test]
+fn options() {
+    let option = |line: &str| {
+        let log = pln(line);
+        parse_option(&log.fileline, &log.msg)
+    };
+    assert_eq!(option(LINES[0]), Some((String::from("Max CPU buffer pool slab size"), String::from("4294967296"))));
+    assert_eq!(option(LINES[1]), Some((String::from("Watchdog"), String::from("true"))));
+    assert_eq!(option(LINES[6]), Some((String::from("Allow Local Auth Fallback"), String::from("disabled"))));
+    assert_eq!(option(LINES[8]), Some((String::from("Started in"), String::from("GPU mode"))));
+    assert_eq!(option("2020-07-01T00:00:00.000013 I 15 0 CommandLineOptions.cpp:1061 OmniSci started with data directory at '/omnisci-storage/data'"),
+        Some((String::from("OmniSci started with data directory at"), String::from("/omnisci-storage/data"))));
+    assert_eq!(option(LINES[3]), None);
+    assert_eq!(option("2020-07-01T00:00:00.000010 I 15 0 CommandLineOptions.cpp:1334  Enable Calcite view optimize true"),
+        Some((String::from("Enable Calcite view optimize"), String::from("true"))));
+    // not an option, so a repeat of it is not a new startup
+    assert_eq!(option("2020-07-01T00:00:00.000020 W 15 0 CommandLineOptions.cpp:1200 The enable-runtime-udf flag is deprecated"), None);
+}
+
+#[test]
+fn snapshot_and_diff() -> olog::SResult<()> {
+    let mut writer = ConfigWriter::new(olog::ReportOptions::default());
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+
+    let rows = writer.rows(None)?;
+    assert_eq!(rows.len(), 7);
+    assert_eq!(rows[1].key, "Watchdog");
+    assert_eq!(rows[1].value, "true");
+    assert_eq!(rows[1].version, "5.4.1-20200925-6728b9462b");
+    let last = writer.rows(Some(-1))?;
+    assert_eq!(last.len(), 4);
+    assert_eq!(last[0].start, "2020-07-02 00:00:00.000001");
+    assert!(writer.rows(Some(3)).is_err());
+
+    let diff = writer.diff(1, 2)?;
+    let changes: Vec<(&str, &str, &str, &str)> = diff.iter()
+        .map(|x| (x.key.as_str(), x.change.as_str(), x.before.as_str(), x.after.as_str()))
+        .collect();
+    assert_eq!(changes, vec![
+        ("version", "changed", "5.4.1-20200925-6728b9462b", "5.5.0-20201201-1b17b5c4e2"),
+        ("Watchdog", "changed", "true", "false"),
+        ("Maximum Idle session duration", "removed", "60", ""),
+        ("Allow Local Auth Fallback", "added", "", "disabled"),
+        ("Started in", "added", "", "GPU mode"),
+    ]);
+    Ok(())
+}
+
+#[test]
+fn startups_by_version() -> olog::SResult<()> {
+    // an option logged twice in one start, and a restart with the same pid
+    let lines = [
+        "2020-07-01T00:00:00.000001 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to true",
+        "2020-07-01T00:00:00.000002 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to true",
+        "2020-07-01T00:00:00.000003 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b",
+        "2020-07-01T00:00:00.000004 I 15 0 DBHandler.cpp:338 Started in GPU mode",
+        "2020-07-02T00:00:00.000001 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to false",
+        "2020-07-02T00:00:00.000002 I 15 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2",
+    ];
+    let mut writer = ConfigWriter::new(olog::ReportOptions::default());
+    for line in lines.iter() {
+        writer.write(&pln(line))?;
+    }
+    let first = writer.rows(Some(1))?;
+    assert_eq!(first.len(), 3);
+    assert!(first.iter().all(|x| x.version == "5.4.1-20200925-6728b9462b"));
+    let last = writer.rows(Some(-1))?;
+    assert_eq!(last.len(), 1);
+    assert_eq!(last[0].version, "5.5.0-20201201-1b17b5c4e2");
+    assert!(writer.rows(Some(3)).is_err());
+
+    // without the version lines, an option logged again is a restart
+    let mut writer = ConfigWriter::new(olog::ReportOptions::default());
+    for line in lines.iter().filter(|x| !x.contains("OmniSci Server")) {
+        writer.write(&pln(line))?;
+    }
+    assert_eq!(writer.rows(Some(1))?.len(), 1);
+    assert_eq!(writer.rows(Some(2))?.len(), 2);
+    assert_eq!(writer.rows(Some(3))?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn inputs_with_the_same_pid() -> olog::SResult<()> {
+    // two servers without --hostname, starting at the same time in containers with the same pid
+    let mut writer = ConfigWriter::new(olog::ReportOptions::default());
+    for (logfile, line) in [
+        ("a/omnisci_server.INFO", "2020-07-01T00:00:00.000001 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to true"),
+        ("b/omnisci_server.INFO", "2020-07-01T00:00:00.000002 I 15 0 CommandLineOptions.cpp:1070  Watchdog is set to false"),
+        ("a/omnisci_server.INFO", "2020-07-01T00:00:00.000003 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b"),
+        ("b/omnisci_server.INFO", "2020-07-01T00:00:00.000004 I 15 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2"),
+    ].iter() {
+        writer.write(&input(line, logfile))?;
+    }
+    let rows = writer.rows(None)?;
+    let options: Vec<(&str, &str)> = rows.iter().map(|x| (x.value.as_str(), x.version.as_str())).collect();
+    assert_eq!(options, vec![("true", "5.4.1-20200925-6728b9462b"), ("false", "5.5.0-20201201-1b17b5c4e2")]);
+    Ok(())
+}
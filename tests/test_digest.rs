@@ -0,0 +1,79 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+
+mod common;
+use common::pln;
+
+#[test]
+fn fingerprint_literals() {
+    assert_eq!(
+        olog::fingerprint::fingerprint("SELECT * FROM t WHERE a = 'it''s' AND b IN (1, 2,3) AND c >= -1.5e3 LIMIT 10;"),
+        "select * from t where a = ? and b in (?+) and c >= ? limit ?");
+    assert_eq!(
+        olog::fingerprint::fingerprint("select count(*)\n    from omnisci_states -- states\n  as s"),
+        "select count(*) from omnisci_states as s");
+    assert_eq!(
+        olog::fingerprint::fingerprint("INSERT INTO t VALUES (1, 'a'), (2, 'b')"),
+        "insert into t values (?+)");
+    // digits inside names are not literals
+    assert_eq!(
+        olog::fingerprint::fingerprint("SELECT conv_4326_900913_x(x) FROM table_0_0"),
+        "select conv_4326_900913_x(x) from table_0_0");
+}
+
+#[test]
+fn fingerprint_column() {
+    let a = pln(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog sql_execute 1 1425 omnisci admin 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from omnisci_states WHERE id = 5;","tcp:172.17.0.1:52290","100/13","1418","1425"}
+"#);
+    let b = pln(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog sql_execute 2 10 omnisci admin 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"select COUNT(*)  from omnisci_states where id = 42","tcp:172.17.0.1:52290","100/13","8","10"}
+"#);
+    assert_eq!(a.fingerprint.unwrap(), b.fingerprint.unwrap());
+}
+
+#[test]
+fn digest() -> olog::SResult<()> {
+    let mut digest = olog::digest::DigestWriter::new(olog::ReportOptions::default());
+    for (user, ms) in [("admin", 100), ("admin", 300), ("mapd", 200)] {
+        digest.write(&pln(&format!(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog sql_execute 1 {} omnisci {} 455-NzOR {{"query_str","client","nonce","execution_time_ms","total_time_ms"}} {{"SELECT * FROM t WHERE id = {};","tcp:172.17.0.1:52290","100/13","{}","{}"}}
+"#, ms, user, ms, ms - 1, ms)))?;
+    }
+    // begin lines have no timing, and are not counted
+    digest.write(&pln(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog_begin sql_execute 1 0 omnisci admin 455-NzOR {"query_str"} {"SELECT * FROM t WHERE id = 1;"}
+"#))?;
+
+    let rows = digest.rows();
+    assert_eq!(rows.len(), 1);
+    let row = &rows[0];
+    assert_eq!(row.count, 3);
+    assert_eq!(row.total_sum, 600);
+    assert_eq!(row.total_avg, 200.0);
+    assert_eq!(row.total_p50, 200);
+    assert_eq!(row.total_max, 300);
+    assert_eq!(row.exec_max, 299);
+    assert_eq!(row.users, "admin,mapd");
+    assert_eq!(row.dashboards, "100");
+    assert_eq!(row.fingerprint, "select * from t where id = ?");
+    assert_eq!(row.sample, "SELECT * FROM t WHERE id = 300;");
+    Ok(())
+}
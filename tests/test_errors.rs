@@ -0,0 +1,82 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+
+mod common;
+use common::pln;
+
+fn write_lines(writer: &mut dyn LogWriter) -> olog::SResult<()> {
+    for line in [
+        r#"2021-08-05T21:43:32.324114 I 123 456 789 DBHandler.cpp:1476 stdlog_begin sql_execute 1373 0 omnisci admin 338-Ynob {"query_str"} {"SELECT COUNT(*) AS val FROM tab"}"#,
+        r#"2021-08-05T21:43:32.400000 E 123 456 789 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found"#,
+        r#"2021-08-05T21:43:33.000000 I 123 0 800 DBHandler.cpp:533 stdlog connect 5 233 omnisci mapd 409-as47 {"client","roles"} {"tcp:172.17.0.1:53140","super"}"#,
+        r#"2021-08-05T21:44:00.000000 E 123 0 800 RelAlgExecutor.cpp:100 Query execution failed with error Table 13 not found"#,
+        r#"2021-08-05T21:45:00.000000 W 123 0 801 DBHandler.cpp:400 Local login failed"#,
+        r#"2021-08-05T21:46:00.000000 I 123 0 801 DBHandler.cpp:300 nothing to see here"#,
+    ] {
+        writer.write(&pln(line))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn errors() -> olog::SResult<()> {
+    let mut errors = olog::errors::ErrorsWriter::new(olog::ReportOptions::default())?;
+    write_lines(&mut errors)?;
+
+    let rows = errors.rows();
+    assert_eq!(rows.len(), 2);
+    let row = &rows[0];
+    assert_eq!(row.new, None);
+    assert_eq!(row.severity, "ERROR");
+    assert_eq!(row.fileline, "RelAlgExecutor.cpp:100");
+    assert_eq!(row.count, 2);
+    assert_eq!(row.first_seen, "2021-08-05 21:43:32.400");
+    assert_eq!(row.last_seen, "2021-08-05 21:44:00");
+    // from the stdlog calls on the same threads
+    assert_eq!(row.users, "admin,mapd");
+    assert_eq!(row.sessions, "338-Ynob,409-as47");
+    assert_eq!(row.msg, "Query execution failed with error Table 12 not found");
+    assert_eq!(rows[1].severity, "AUTH");
+    assert_eq!(rows[1].event, "login_failure");
+    Ok(())
+}
+
+#[test]
+fn errors_baseline() -> olog::SResult<()> {
+    std::fs::create_dir_all("target/test")?;
+    let baseline = "target/test/errors_baseline.csv";
+    std::fs::write(baseline, "severity,fileline,msg_norm,count\nERROR,RelAlgExecutor.cpp:100,Query execution failed with error Table  not found,5\n")?;
+
+    let options = olog::ReportOptions {
+        baseline: Some(baseline.to_string()),
+        ..Default::default()
+    };
+    let mut errors = olog::errors::ErrorsWriter::new(options)?;
+    write_lines(&mut errors)?;
+
+    let rows = errors.rows();
+    assert_eq!(rows[0].new, Some(false));
+    assert_eq!(rows[1].new, Some(true));
+
+    let table = olog::format_markdown(&rows)?;
+    assert!(table.starts_with("| new | severity | fileline |"));
+    assert_eq!(table.lines().nth(1).unwrap(), "|---|---|---|---|---|---|---|---|---|---|---|");
+    Ok(())
+}
@@ -0,0 +1,90 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::es::{EsBulkWriter, doc_id, index_template};
+
+use serde_json::Value;
+
+mod common;
+use common::{pln, Buffer};
+
+const LINES: [&str; 3] = [
+    r#"2020-07-01T23:59:59.000105 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 1 906 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT 1;","tcp:172.17.0.1:53140","","905","906"}"#,
+    "2020-07-02T00:00:00.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+    "2020-07-02T00:00:00.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 13 not found",
+];
+
+#[test]
+fn es_bulk() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = EsBulkWriter::new(Box::new(buffer.clone()), "logs-%Y.%m.%d")?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+
+    let text = buffer.text();
+    let lines: Vec<Value> = text.lines().map(|x| serde_json::from_str(x).unwrap()).collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0]["index"]["_index"], "logs-2020.07.01");
+    assert_eq!(lines[2]["index"]["_index"], "logs-2020.07.02");
+    assert_eq!(lines[1]["logtime"], "2020-07-01T23:59:59.000105");
+    assert_eq!(lines[1]["event"], "sql_execute");
+    assert_eq!(lines[1]["total_time"], 906);
+
+    // ids are the same for the same line, and differ for other lines
+    assert_eq!(lines[0]["index"]["_id"], doc_id(&pln(LINES[0])));
+    assert!(lines[2]["index"]["_id"] != lines[4]["index"]["_id"]);
+    let mut other_host = pln(LINES[0]);
+    other_host.hostname = Some(String::from("h2"));
+    assert!(doc_id(&other_host) != doc_id(&pln(LINES[0])));
+
+    assert!(EsBulkWriter::new(Box::new(buffer.clone()), "logs-%Q").is_err());
+
+    // Elasticsearch rejects uppercase index names
+    buffer.0.lock().unwrap().clear();
+    let mut writer = EsBulkWriter::new(Box::new(buffer.clone()), "Logs-%b")?;
+    writer.write(&pln(LINES[0]))?;
+    writer.close()?;
+    let text = buffer.text();
+    let action: Value = serde_json::from_str(text.lines().next().unwrap())?;
+    assert_eq!(action["index"]["_index"], "logs-jul");
+    assert_eq!(index_template("Logs-%b")["index_patterns"][0], "logs-*");
+    Ok(())
+}
+
+#[test]
+fn es_template() {
+    let template = index_template("logs-%Y.%m.%d");
+    assert_eq!(template["index_patterns"][0], "logs-*");
+    let properties = &template["template"]["mappings"]["properties"];
+    assert_eq!(properties["logtime"]["type"], "date_nanos");
+    for name in ["severity", "event", "dbname"] {
+        assert_eq!(properties[name]["type"], "keyword");
+    }
+    assert_eq!(properties["msg"]["type"], "text");
+    assert_eq!(properties["query"]["type"], "text");
+    assert_eq!(properties["dur_ms"]["type"], "integer");
+
+    // every column of a line is mapped
+    let doc = serde_json::to_value(pln(LINES[0])).unwrap();
+    for name in doc.as_object().unwrap().keys() {
+        assert!(properties[name].is_object(), "{} is not mapped", name);
+    }
+}
@@ -0,0 +1,196 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::loki::LokiWriter;
+use olog::syslog::SyslogWriter;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+mod common;
+
+fn pln(line: &str) -> olog::LogLine {
+    let mut rec = common::pln(line);
+    rec.hostname = Some(String::from("h1"));
+    rec
+}
+
+const LINES: [&str; 3] = [
+    r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 7 1500 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from flights;","tcp:172.17.0.1:53140","","1499","1500"}"#,
+    "2020-07-01T00:00:02.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+    r#"2020-07-01T00:00:03.000000 I 15 5 DBHandler.cpp:616 stdlog disconnect 8 10 omnisci admin 409-as47 {"client"} {"tcp:172.17.0.1:53140"}"#,
+];
+
+// a Loki that answers each push with the status, and returns the bodies
+fn loki(statuses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/loki/api/v1/push", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut bodies = vec![];
+        for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break
+                }
+                if let Some(x) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = x.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+            write!(reader.into_inner(), "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+#[test]
+fn loki_push_with_retry() -> olog::SResult<()> {
+    let (url, loki) = loki(vec!["500 Internal Server Error", "204 No Content"]);
+    let mut writer = LokiWriter::new(&url);
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    // its msg is cleared, the version is in msg_norm
+    writer.write(&pln("2020-07-01T00:00:04.000000 I 16 1 DBHandler.cpp:238 OmniSci Server 5.4.0-20200904-1b17b5c4e2"))?;
+    writer.close()?;
+
+    let bodies = loki.join().unwrap();
+    assert_eq!(bodies[0], bodies[1]);
+    let request: Value = serde_json::from_str(&bodies[1])?;
+    let streams = request["streams"].as_array().unwrap();
+    assert_eq!(streams.len(), 4);
+    let stdlog = streams.iter().find(|x| x["stream"]["event"] == "sql_execute").unwrap();
+    assert_eq!(stdlog["stream"]["job"], "omnisci");
+    assert_eq!(stdlog["stream"]["hostname"], "h1");
+    assert_eq!(stdlog["stream"]["dbname"], "omnisci");
+    assert_eq!(stdlog["stream"]["severity"], "INFO");
+    assert_eq!(stdlog["values"][0][0], "1593561601000000000");
+    assert_eq!(stdlog["values"][0][1], "SELECT count(*) from flights;");
+    let error = streams.iter().find(|x| x["stream"]["severity"] == "ERROR").unwrap();
+    assert!(error["stream"].get("event").is_none());
+    assert_eq!(error["values"][0][1], "Query execution failed with error Table 12 not found");
+    let version = streams.iter().find(|x| x["stream"]["event"] == "version").unwrap();
+    assert_eq!(version["values"][0][1], "5.4.0-20200904-1b17b5c4e2");
+    let disconnect = streams.iter().find(|x| x["stream"]["event"] == "disconnect").unwrap();
+    assert_eq!(disconnect["values"][0][1], "disconnect");
+    Ok(())
+}
+
+#[test]
+fn loki_retries_through_an_outage() -> olog::SResult<()> {
+    // more failures in a row than a batch was once tried
+    let mut statuses = vec!["503 Service Unavailable"; 5];
+    statuses.push("204 No Content");
+    let (url, loki) = loki(statuses);
+    let mut writer = LokiWriter::new(&url);
+    writer.write(&pln(LINES[1]))?;
+    writer.close()?;
+    assert_eq!(loki.join().unwrap().len(), 6);
+    Ok(())
+}
+
+#[test]
+fn loki_drops_old_lines() -> olog::SResult<()> {
+    // the first batch is too old for Loki, the second is pushed
+    let (url, loki) = loki(vec!["400 Bad Request", "204 No Content"]);
+    let mut writer = LokiWriter::new(&url);
+    for _ in 0..1001 {
+        writer.write(&pln(LINES[1]))?;
+    }
+    writer.close()?;
+    let bodies = loki.join().unwrap();
+    assert_eq!(bodies.len(), 2);
+    let request: Value = serde_json::from_str(&bodies[1])?;
+    assert_eq!(request["streams"][0]["values"].as_array().unwrap().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn loki_rejected() -> olog::SResult<()> {
+    let (url, loki) = loki(vec!["401 Unauthorized"]);
+    let mut writer = LokiWriter::new(&url);
+    writer.write(&pln(LINES[1]))?;
+    // not sent again, the error stops the run
+    assert!(writer.close().is_err());
+    assert_eq!(loki.join().unwrap().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn syslog_over_udp() -> olog::SResult<()> {
+    let socket = UdpSocket::bind("127.0.0.1:0")?;
+    socket.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut writer = SyslogWriter::new(&format!("udp://{}", socket.local_addr()?))?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+
+    let mut messages = vec![];
+    let mut buf = [0; 4096];
+    for _ in LINES.iter() {
+        let n = socket.recv(&mut buf)?;
+        messages.push(String::from_utf8(buf[..n].to_vec())?);
+    }
+    assert_eq!(messages[0], concat!(r#"<134>1 2020-07-01T00:00:01.000000Z h1 omnisci_server 15 sql_execute "#,
+        r#"[omnisci@32473 severity="INFO" hostname="h1" event="sql_execute" dbname="omnisci"] SELECT count(*) from flights;"#));
+    assert_eq!(messages[1], concat!(r#"<131>1 2020-07-01T00:00:02.000000Z h1 omnisci_server 15 - "#,
+        r#"[omnisci@32473 severity="ERROR" hostname="h1"] Query execution failed with error Table 12 not found"#));
+    Ok(())
+}
+
+#[test]
+fn syslog_over_tcp() -> olog::SResult<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let mut writer = SyslogWriter::new(&format!("tcp://{}", listener.local_addr()?))?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+
+    // octet counted frames, one connection for the batch
+    let (mut stream, _) = listener.accept()?;
+    let mut text = String::new();
+    stream.read_to_string(&mut text)?;
+    let mut messages = vec![];
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let space = rest.find(' ').unwrap();
+        let length: usize = rest[..space].parse()?;
+        messages.push(&rest[space + 1..space + 1 + length]);
+        rest = &rest[space + 1 + length..];
+    }
+    assert_eq!(messages.len(), 3);
+    // disconnects are AUTH, a notice
+    assert_eq!(messages[2], concat!(r#"<133>1 2020-07-01T00:00:03.000000Z h1 omnisci_server 15 disconnect "#,
+        r#"[omnisci@32473 severity="AUTH" hostname="h1" event="disconnect" dbname="omnisci"] "#));
+    Ok(())
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::hardware::HardwareWriter;
+
+mod common;
+use common::{input, pln};
+
+const STARTUP: [&str; 20] = [
+    "2020-07-01T00:00:00.000021 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b",
+    "2020-07-01T00:00:00.000023 I 15 0 CudaMgr.cpp:369 Using 2 Gpus.",
+    "2020-07-01T00:00:00.000024 1 15 0 CudaMgr.cpp:371 Device: 1",
+    "2020-07-01T00:00:00.000025 1 15 0 CudaMgr.cpp:372 UUID: 7ff02ae8-e62b-d9aa-c814-aec3a8240cc7",
+    "2020-07-01T00:00:00.000027 1 15 0 CudaMgr.cpp:374 Compute Major: 7",
+    "2020-07-01T00:00:00.000028 1 15 0 CudaMgr.cpp:375 Compute Minor: 0",
+    "2020-07-01T00:00:00.000031 1 15 0 CudaMgr.cpp:378 Per device global memory: 11.9205 GB",
+    "2020-07-01T00:00:00.000033 1 15 0 CudaMgr.cpp:381 Memory bandwidth: 144.192 GB/sec",
+    "2020-07-01T00:00:00.000043 1 15 0 CudaMgr.cpp:371 Device: 0",
+    "2020-07-01T00:00:00.000044 1 15 0 CudaMgr.cpp:372 UUID: 3e09368e-489d-bce6-5ef8-d6e0b290643a",
+    "2020-07-01T00:00:00.000066 1 15 0 DataMgr.cpp:176 Detected 386774M of total system memory.",
+    "2020-07-01T00:00:00.000067 I 15 0 DataMgr.cpp:185 Min CPU Slab Size is 256MB",
+    "2020-07-01T00:00:00.000069 I 15 0 DataMgr.cpp:187 Max memory pool size for CPU is 309419MB",
+    "2020-07-01T00:00:00.000070 I 15 0 DataMgr.cpp:190 Reserved GPU memory is 1337.67MB includes render buffer allocation",
+    "2020-07-01T00:00:00.000072 I 15 0 DataMgr.cpp:215 Max GPU Slab size for GPU 0 is 4096MB",
+    "2020-07-01T00:00:00.000073 I 15 0 DataMgr.cpp:217 Max memory pool size for GPU 1 is 10869MB",
+    "2020-07-01T00:00:00.000084 I 15 0 DBHandler.cpp:338 Started in GPU mode",
+    "2020-07-01T00:00:00.000085 I 15 0 EglPlatform.cpp:257 EGL Version: 1.5",
+    "2020-07-01T00:00:00.000086 I 15 0 OpenGLDriver.cpp:66 OpenGL Version: 4.6",
+    "2020-07-01T00:00:00.000094 I 15 0 EglPlatform.cpp:328 Cuda ID: 0  UUID: 3e09368e-489d-bce6-5ef8-d6e0b290643a",
+];
+
+#[test]
+fn inventory() -> olog::SResult<()> {
+    let mut writer = HardwareWriter::new(olog::ReportOptions::default());
+    for line in STARTUP.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    assert_eq!(rows.len(), 3);
+
+    let host = &rows[0];
+    assert_eq!(host.device, "host");
+    assert_eq!(host.start, "2020-07-01 00:00:00.000021");
+    assert_eq!(host.version, "5.4.1-20200925-6728b9462b");
+    assert_eq!(host.mode, "GPU");
+    assert_eq!(host.gpus, Some(2));
+    assert_eq!(host.system_memory_mb, Some(386774));
+    assert_eq!(host.min_slab_mb, Some(256));
+    assert_eq!(host.memory_pool_mb, Some(309419));
+    assert_eq!(host.reserved_gpu_memory_mb, Some(1337.67));
+    assert_eq!(host.egl_version, "1.5");
+    assert_eq!(host.opengl_version, "4.6");
+
+    // sorted by id
+    let (gpu0, gpu1) = (&rows[1], &rows[2]);
+    assert_eq!(gpu0.id, Some(0));
+    assert_eq!(gpu0.uuid, "3e09368e-489d-bce6-5ef8-d6e0b290643a");
+    assert_eq!(gpu0.max_slab_mb, Some(4096));
+    assert_eq!(gpu0.render, Some(true));
+    assert_eq!(gpu0.version, "5.4.1-20200925-6728b9462b");
+    assert_eq!(gpu1.id, Some(1));
+    assert_eq!(gpu1.compute_capability, "7.0");
+    assert_eq!(gpu1.global_memory_gb, Some(11.9205));
+    assert_eq!(gpu1.memory_bandwidth_gbs, Some(144.192));
+    assert_eq!(gpu1.memory_pool_mb, Some(10869));
+    assert_eq!(gpu1.render, None);
+    Ok(())
+}
+
+#[test]
+fn restarts() -> olog::SResult<()> {
+    let mut writer = HardwareWriter::new(olog::ReportOptions::default());
+    for line in STARTUP.iter() {
+        writer.write(&pln(line))?;
+    }
+    // the same container restarted, now with one GPU, and another server
+    for line in [
+        "2020-07-02T00:00:00.000001 I 15 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2",
+        "2020-07-02T00:00:00.000002 I 15 0 CudaMgr.cpp:369 Using 1 Gpus.",
+        "2020-07-02T00:00:00.000003 1 15 0 CudaMgr.cpp:371 Device: 0",
+        "2020-07-02T00:00:00.000004 I 16 0 DBHandler.cpp:338 Started in CPU mode",
+        "2020-07-02T00:00:00.000005 1 16 0 DataMgr.cpp:176 Detected 1024M of total system memory.",
+        // nothing of the hardware
+        "2020-07-02T00:00:00.000006 I 17 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2",
+    ].iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    let summary: Vec<(i32, &str, &str, Option<i32>)> = rows.iter()
+        .map(|x| (x.pid, x.version.as_str(), x.device.as_str(), x.id))
+        .collect();
+    assert_eq!(summary, vec![
+        (15, "5.4.1-20200925-6728b9462b", "host", None),
+        (15, "5.4.1-20200925-6728b9462b", "gpu", Some(0)),
+        (15, "5.4.1-20200925-6728b9462b", "gpu", Some(1)),
+        (15, "5.5.0-20201201-1b17b5c4e2", "host", None),
+        (15, "5.5.0-20201201-1b17b5c4e2", "gpu", Some(0)),
+        (16, "", "host", None),
+    ]);
+    assert_eq!(rows[3].gpus, Some(1));
+    assert_eq!(rows[5].mode, "CPU");
+    assert_eq!(rows[5].system_memory_mb, Some(1024));
+    Ok(())
+}
+
+#[test]
+fn inputs_with_the_same_pid() -> olog::SResult<()> {
+    // two servers without --hostname, starting at the same time in containers with the same pid
+    let mut writer = HardwareWriter::new(olog::ReportOptions::default());
+    for (logfile, line) in [
+        ("a/omnisci_server.INFO", "2020-07-01T00:00:00.000001 I 15 0 CudaMgr.cpp:369 Using 2 Gpus."),
+        ("b/omnisci_server.INFO", "2020-07-01T00:00:00.000002 I 15 0 CudaMgr.cpp:369 Using 1 Gpus."),
+        ("a/omnisci_server.INFO", "2020-07-01T00:00:00.000003 1 15 0 CudaMgr.cpp:371 Device: 0"),
+        ("b/omnisci_server.INFO", "2020-07-01T00:00:00.000004 1 15 0 CudaMgr.cpp:371 Device: 0"),
+        ("a/omnisci_server.INFO", "2020-07-01T00:00:00.000005 1 15 0 CudaMgr.cpp:371 Device: 1"),
+    ].iter() {
+        writer.write(&input(line, logfile))?;
+    }
+    let rows = writer.rows();
+    let summary: Vec<(&str, Option<i32>, Option<i32>)> = rows.iter().map(|x| (x.device.as_str(), x.gpus, x.id)).collect();
+    assert_eq!(summary, vec![
+        ("host", Some(2), None),
+        ("gpu", None, Some(0)),
+        ("gpu", None, Some(1)),
+        ("host", Some(1), None),
+        ("gpu", None, Some(0)),
+    ]);
+    Ok(())
+}
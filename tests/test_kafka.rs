@@ -0,0 +1,239 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// cargo test --features kafka
+#![cfg(feature = "kafka")]
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::kafka::{read_checkpoints, read_properties, record_key, write_checkpoint, Checkpoint, KafkaWriter};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::mocking::MockCluster;
+use rdkafka::types::{RDKafkaApiKey, RDKafkaRespErr};
+use rdkafka::{Message, Offset, Timestamp, TopicPartitionList};
+
+mod common;
+
+fn pln(line: &str) -> olog::LogLine {
+    let mut rec = common::pln(line);
+    rec.hostname = Some(String::from("h1"));
+    rec
+}
+
+const LINES: [&str; 4] = [
+    r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 7 1500 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from flights;","tcp:172.17.0.1:53140","","1499","1500"}"#,
+    r#"2020-07-01T00:00:02.000000 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 8 10 omnisci admin 510-bd88 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT 1;","tcp:172.17.0.1:53141","","9","10"}"#,
+    r#"2020-07-01T00:00:03.000000 I 15 3 DBHandler.cpp:616 stdlog disconnect 9 10 omnisci admin 409-as47 {"client"} {"tcp:172.17.0.1:53140"}"#,
+    "2020-07-01T00:00:04.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+];
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("test_kafka_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// a port nothing listens on
+fn no_broker() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+// the messages of partition 0 of the topic, from the start
+fn consume(brokers: &str, topic: &str, count: usize) -> olog::SResult<Vec<(String, serde_json::Value, Timestamp)>> {
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", brokers)
+        .set("group.id", "test_kafka")
+        .create()?;
+    let mut partitions = TopicPartitionList::new();
+    partitions.add_partition_offset(topic, 0, Offset::Beginning)?;
+    consumer.assign(&partitions)?;
+    let mut messages = vec![];
+    while messages.len() < count {
+        let message = match consumer.poll(Duration::from_secs(10)) {
+            Some(x) => x?,
+            None => return Err(format!("{} of {} messages consumed", messages.len(), count).into()),
+        };
+        messages.push((
+            String::from_utf8(message.key().unwrap_or_default().to_vec())?,
+            serde_json::from_slice(message.payload().unwrap_or_default())?,
+            message.timestamp(),
+        ));
+    }
+    Ok(messages)
+}
+
+#[test]
+fn keys_and_properties() -> olog::SResult<()> {
+    assert_eq!(record_key(&pln(LINES[0])), "h1/409-as47");
+    assert_eq!(record_key(&pln(LINES[2])), "h1/409-as47");
+    assert_eq!(record_key(&pln(LINES[3])), "h1/");
+
+    let dir = temp_dir("properties");
+    let path = dir.join("kafka.properties");
+    let path = path.to_str().unwrap();
+    fs::write(path, "# brokers of the cloud\nsecurity.protocol=SASL_SSL\n\nsasl.password = a=b\n")?;
+    assert_eq!(read_properties(path)?, vec![
+        (String::from("security.protocol"), String::from("SASL_SSL")),
+        (String::from("sasl.password"), String::from("a=b")),
+    ]);
+    fs::write(path, "security.protocol SASL_SSL\n")?;
+    assert!(read_properties(path).is_err());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn resume_from_checkpoint() -> olog::SResult<()> {
+    let dir = temp_dir("checkpoint");
+    let checkpoint = dir.join("checkpoint.json");
+    let checkpoint = checkpoint.to_str().unwrap();
+    let config = dir.join("kafka.properties");
+    let config = config.to_str().unwrap();
+    // fail fast without a broker
+    fs::write(config, "message.timeout.ms=100\n")?;
+    let broker = no_broker();
+
+    // every line was sent before, so nothing is sent
+    let mut checkpoints = BTreeMap::new();
+    checkpoints.insert("omnisci_server.INFO", Checkpoint {
+        logtime: String::from("2020-07-01T00:00:04"),
+        id: olog::es::doc_id(&pln(LINES[3])),
+        partition: 0,
+        offset: 3,
+    });
+    fs::write(checkpoint, serde_json::to_string(&checkpoints)?)?;
+    let mut writer = KafkaWriter::new(&broker, "logs", Some(config), Some(checkpoint), "omnisci_server.INFO")?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+    let saved = read_checkpoints(checkpoint)?;
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved["omnisci_server.INFO"].offset, 3);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn delivered() -> olog::SResult<()> {
+    let cluster = MockCluster::new(1)?;
+    cluster.create_topic("logs", 1, 1)?;
+    let brokers = cluster.bootstrap_servers();
+    let dir = temp_dir("delivered");
+    let checkpoint = dir.join("checkpoint.json");
+    let checkpoint = checkpoint.to_str().unwrap();
+    let config = dir.join("kafka.properties");
+    let config = config.to_str().unwrap();
+    // a line not acknowledged fails its batch sooner
+    fs::write(config, "message.timeout.ms=2000\n")?;
+
+    let mut writer = KafkaWriter::new(&brokers, "logs", Some(config), Some(checkpoint), "omnisci_server.INFO")?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+    let saved = read_checkpoints(checkpoint)?;
+    assert_eq!(saved["omnisci_server.INFO"].offset, 3);
+    assert_eq!(saved["omnisci_server.INFO"].id, olog::es::doc_id(&pln(LINES[3])));
+
+    let messages = consume(&brokers, "logs", 4)?;
+    let keys: Vec<&str> = messages.iter().map(|x| x.0.as_str()).collect();
+    assert_eq!(keys, vec!["h1/409-as47", "h1/510-bd88", "h1/409-as47", "h1/"]);
+    let (_, payload, timestamp) = &messages[1];
+    assert_eq!(payload["hostname"], "h1");
+    assert_eq!(payload["query"], "SELECT 1;");
+    assert_eq!(payload["total_time"], 10);
+    assert_eq!(*timestamp, Timestamp::CreateTime(1593561602000));
+
+    // while the broker is down, the batch is sent again, and the checkpoint waits for it
+    cluster.broker_down(1)?;
+    let mut writer = KafkaWriter::new(&brokers, "logs", Some(config), Some(checkpoint), "omnisci_server.WARNING")?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    thread::sleep(Duration::from_secs(4));
+    assert!(!read_checkpoints(checkpoint)?.contains_key("omnisci_server.WARNING"));
+    cluster.broker_up(1)?;
+    writer.close()?;
+    let saved = read_checkpoints(checkpoint)?;
+    assert_eq!(saved["omnisci_server.INFO"].offset, 3);
+    assert!(saved["omnisci_server.WARNING"].offset >= 7);
+    let messages = consume(&brokers, "logs", 8)?;
+    assert_eq!(messages[7].0, "h1/");
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn rejected() -> olog::SResult<()> {
+    let cluster = MockCluster::new(1)?;
+    cluster.create_topic("logs", 1, 1)?;
+    cluster.request_errors(RDKafkaApiKey::Produce, &[RDKafkaRespErr::RD_KAFKA_RESP_ERR_TOPIC_AUTHORIZATION_FAILED; 10]);
+    let brokers = cluster.bootstrap_servers();
+    let dir = temp_dir("rejected");
+    let checkpoint = dir.join("checkpoint.json");
+    let checkpoint = checkpoint.to_str().unwrap();
+
+    // a topic the client may not write to stops the run instead of sending the batch again
+    let mut writer = KafkaWriter::new(&brokers, "logs", None, Some(checkpoint), "omnisci_server.INFO")?;
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    let e = writer.close().unwrap_err();
+    assert!(e.to_string().contains("Kafka rejected the lines"), "{}", e);
+    assert!(read_checkpoints(checkpoint)?.is_empty());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn checkpoints_of_two_inputs() -> olog::SResult<()> {
+    let dir = temp_dir("two_inputs");
+    let checkpoint = dir.join("checkpoint.json").to_str().unwrap().to_string();
+    // each input writes its checkpoint from its own thread, and keeps the other's
+    let threads: Vec<thread::JoinHandle<()>> = ["omnisci_server.INFO", "omnisci_server.WARNING"].iter().map(|input| {
+        let checkpoint = checkpoint.clone();
+        thread::spawn(move || {
+            for offset in 0..200 {
+                write_checkpoint(&checkpoint, input, Checkpoint {
+                    logtime: String::from("2020-07-01T00:00:04"),
+                    id: String::from("x"),
+                    partition: 0,
+                    offset,
+                }).unwrap();
+            }
+        })
+    }).collect();
+    for x in threads {
+        x.join().unwrap();
+    }
+    let saved = read_checkpoints(&checkpoint)?;
+    assert_eq!(saved.len(), 2);
+    assert!(saved.values().all(|x| x.offset == 199));
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -120,3 +120,50 @@ fn v570() {
     SELECT COUNT(*) AS val FROM tab
     "#.trim());
 }
+
+#[test]
+fn debug_verbosity() {
+    let rec = pln("2020-07-01T00:00:00.000157 3 15 6 RelAlgExecutor.cpp:1070 used_inputs.size() = 0");
+    assert_eq!(rec.severity.to_string(), "DEBUG");
+    assert_eq!(rec.verbosity, Some(3));
+    let rec = pln("2020-07-01T00:00:00.000158 I 15 6 RelAlgExecutor.cpp:496 Executing query step 0");
+    assert_eq!(rec.verbosity, None);
+    let rec = pln("2020-07-01T00:00:00.000159 7 15 6 RelAlgExecutor.cpp:496 Executing query step 0");
+    assert_eq!(rec.severity.to_string(), "OTHER");
+}
+
+#[test]
+fn min_severity_max_verbosity() -> olog::SResult<()> {
+    let dir = std::env::temp_dir().join(format!("test_lines_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let input = dir.join("omnisci_server.INFO");
+    let output = dir.join("out.csv");
+    std::fs::write(&input, r#"2020-07-01T00:00:01.000000 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b
+2020-07-01T00:00:02.000000 1 15 0 MapDServer.cpp:316 heartbeat thread starting
+2020-07-01T00:00:03.000000 2 15 0 EglPlatform.cpp:100 EGL version 1.4
+2020-07-01T00:00:04.000000 4 15 0 FileInfo.cpp:100 open file
+2020-07-01T00:00:05.000000 W 15 0 DBHandler.cpp:100 Local login failed
+2020-07-01T00:00:06.000000 I 15 1 DBHandler.cpp:533 stdlog connect 0 233 omnisci admin 409-as47 {"client","roles"} {"tcp:172.17.0.1:53140","super"}
+2020-07-01T00:00:07.000000 E 15 1 DBHandler.cpp:200 Unauthorized Access: user bob does not have SELECT privilege on table secrets
+2020-07-01T00:00:08.000000 E 15 1 DBHandler.cpp:200 Syntax error at: FROM
+"#)?;
+    let count = |min_severity: Option<&str>, max_verbosity: Option<&str>| -> olog::SResult<usize> {
+        let filter = olog::LineFilter::new("all", min_severity, max_verbosity)?;
+        olog::transform_logs(input.to_str().unwrap(), output.to_str(), &filter, &olog::OutputType::CSV,
+            None, None, false, None, None, &Default::default())?;
+        Ok(std::fs::read_to_string(&output)?.lines().count() - 1)
+    };
+    assert_eq!(count(None, None)?, 8);
+    assert_eq!(count(None, Some("2"))?, 7);
+    assert_eq!(count(None, Some("0"))?, 5);
+    // by the glog level, so the INFO connect is dropped even though it is AUTH,
+    // and the ERROR lines that became AUTH and INPUT are kept
+    assert_eq!(count(Some("warning"), None)?, 3);
+    assert_eq!(count(Some("ERROR"), None)?, 2);
+    std::fs::remove_dir_all(&dir)?;
+
+    assert!(olog::LineFilter::new("all", Some("loud"), None).is_err());
+    assert!(olog::LineFilter::new("all", None, Some("5")).is_err());
+    assert!(olog::LineFilter::new("all", None, Some("x")).is_err());
+    Ok(())
+}
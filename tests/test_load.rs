@@ -37,11 +37,14 @@ fn test_load() -> olog::SResult<()> {
     olog::transform_logs(
         "tests/gold/omnisci_server.INFO",
         None,
-        &vec!(),
+        &olog::LineFilter::default(),
         &olog::OutputType::Load,
         Some(DB_URL),
         Some("test_load"),
         false,
+        None,
+        None,
+        &olog::SinkOptions::default(),
     )?;
 
     let res = olog::QueryResult::new(con.sql_execute(String::from("select count(*) count_ from omnisci_log_scraper where hostname = 'test_load'"), true, nonce.to_string())?);
@@ -76,11 +79,14 @@ fn test_copy_from() -> olog::SResult<()> {
     olog::transform_logs(
         "tests/gold/omnisci_server.INFO",
         Some("tests/gold/omnisci_server.INFO.csv"),
-        &vec!(),
+        &olog::LineFilter::default(),
         &olog::OutputType::CSV,
         None,
         Some("db"),
         false,
+        None,
+        None,
+        &olog::SinkOptions::default(),
     )?;
 
     let res = con.sql_execute(String::from("delete from omnisci_log_scraper where hostname = 'db'"), false, nonce.to_string())?;
@@ -0,0 +1,116 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::locks::{parse_lock, LockWriter};
+
+mod common;
+use common::pln;
+
+// an import on thread 3 with a write lock, while a dashboard query on thread 4 waits to read
+const LINES: [&str; 10] = [
+    "2020-07-01T10:00:00.000000 1 15 3 LockMgr.h:207 Acquiring Table Data Write Lock for table: flights",
+    "2020-07-01T10:00:01.000000 1 15 4 LockMgr.h:113 Acquiring Table Schema Read Lock for table: flights",
+    "2020-07-01T10:00:01.001000 1 15 4 LockMgr.h:207 Acquiring Table Data Read Lock for table: flights",
+    "2020-07-01T10:00:02.000000 1 15 9 LockMgr.h:113 Acquiring Table Schema Read Lock for table: airports",
+    r#"2020-07-01T10:00:05.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 20 5200 omnisci importer 410-imp1 {"query_str","execution_time_ms","total_time_ms"} {"COPY flights FROM '/data/flights.csv';","5190","5200"}"#,
+    "2020-07-01T10:00:05.010000 I 15 4 QueryDispatchQueue.h:49 Dispatching query with 0 queries in the queue.",
+    "2020-07-01T10:00:05.011000 I 15 6 QueryDispatchQueue.h:80 Running query and returning control. There are now 0 queries in the queue.",
+    "2020-07-01T10:00:05.300000 1 15 6 Execute.cpp:2078 Launching 1 kernels for query.",
+    r#"2020-07-01T10:00:05.400000 I 15 DBHandler.cpp:1058 stdlog sql_execute 21 4400 omnisci dash 411-dsh1 {"query_str","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM flights;","4390","4400"}"#,
+    "2020-07-01T10:00:05.500000 1 15 6 Execute.cpp:2078 Launching 1 kernels for query.",
+];
+
+#[test]
+fn lock_lines() {
+    let lock = parse_lock("Acquiring Table Data Write Lock for table: flights").unwrap();
+    assert_eq!((lock.lock_type.as_str(), lock.mode.as_str(), lock.table.as_str()), ("Data", "Write", "flights"));
+    assert!(parse_lock("Acquiring Table Data Lock for table: flights").is_none());
+
+    let log = pln(LINES[1]);
+    assert_eq!(log.event.as_deref(), Some("table_lock"));
+    assert_eq!(log.sequence, None);
+    assert_eq!(log.msg_norm.as_deref(), Some("Acquiring Table Schema Read Lock"));
+    assert_eq!(log.name_values, Some(vec![
+        String::from("lock_type"), String::from("Schema"),
+        String::from("lock_mode"), String::from("Read"),
+        String::from("table"), String::from("flights"),
+    ]));
+    assert!(pln(LINES[5]).name_values.is_none());
+}
+
+#[test]
+fn blocked_and_waits() -> olog::SResult<()> {
+    let mut writer = LockWriter::new(olog::ReportOptions::default());
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    let summary: Vec<(&str, &str, Option<i64>)> = rows.iter()
+        .map(|x| (x.category.as_str(), x.table.as_str(), x.wait_ms))
+        .collect();
+    assert_eq!(summary, vec![
+        ("table", "flights", None),
+        ("table", "airports", None),
+        ("blocked", "flights", Some(4000)),
+        ("blocked", "flights", Some(3999)),
+        ("wait", "flights", Some(4299)),
+    ]);
+    assert_eq!((rows[0].count, rows[0].reads, rows[0].writes), (Some(3), Some(2), Some(1)));
+
+    // the dashboard query, blocked by the import
+    let blocked = &rows[3];
+    assert_eq!(blocked.lock, "Data Read");
+    assert_eq!(blocked.threadid, Some(4));
+    assert_eq!(blocked.sequence, Some(21));
+    assert_eq!(blocked.username, "dash");
+    assert_eq!(blocked.writer_sequence, Some(20));
+    assert_eq!(blocked.writer_username, "importer");
+    assert_eq!(blocked.writer_query, "COPY flights FROM '/data/flights.csv';");
+
+    // launched by the executor thread it was dispatched to
+    let wait = &rows[4];
+    assert_eq!(wait.time, "2020-07-01 10:00:01.001");
+    assert_eq!(wait.end, "2020-07-01 10:00:05.300");
+    assert_eq!(wait.query, "SELECT count(*) FROM flights;");
+    Ok(())
+}
+
+#[test]
+fn locks_without_a_call() -> olog::SResult<()> {
+    let lines = [
+        // no stdlog ends within a window of its lock
+        "2020-07-01T10:00:00.000000 1 15 7 LockMgr.h:207 Acquiring Table Data Read Lock for table: t",
+        r#"2020-07-01T10:00:10.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 1 100 omnisci admin 409-as47 {"query_str","execution_time_ms","total_time_ms"} {"SELECT 1;","90","100"}"#,
+        "2020-07-01T10:00:20.000000 1 15 8 LockMgr.h:207 Acquiring Table Data Write Lock for table: t",
+        "2020-07-01T10:00:20.500000 1 15 7 LockMgr.h:207 Acquiring Table Data Read Lock for table: t",
+        r#"2020-07-01T10:00:21.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 2 1100 omnisci admin 409-as47 {"query_str","execution_time_ms","total_time_ms"} {"DELETE FROM t;","1090","1100"}"#,
+        r#"2020-07-01T10:00:22.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 3 1600 omnisci admin 409-as47 {"query_str","execution_time_ms","total_time_ms"} {"SELECT * FROM t;","1590","1600"}"#,
+    ];
+    let mut writer = LockWriter::new(olog::ReportOptions::default());
+    for line in lines.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    assert_eq!(rows.len(), 2);
+    assert_eq!((rows[0].category.as_str(), rows[0].count), ("table", Some(3)));
+    // the later lock of thread 7 is of its own call, not of the one that never ended
+    assert_eq!(rows[1].category, "blocked");
+    assert_eq!((rows[1].sequence, rows[1].writer_sequence, rows[1].wait_ms), (Some(3), Some(2), Some(500)));
+    Ok(())
+}
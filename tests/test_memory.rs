@@ -0,0 +1,104 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::memory::MemoryWriter;
+
+mod common;
+use common::pln;
+
+const LINES: [&str; 10] = [
+    "2020-07-01T10:00:00.000000 I 15 7 BufferMgr.cpp:303 ALLOCATION slab of 8388608 pages (4294967296B) created in 5 ms GPU_MGR:1",
+    "2020-07-01T10:00:01.000000 I 15 9 BufferMgr.cpp:303 ALLOCATION slab of 8388608 pages (4294967296B) created in 0 ms CPU_MGR:0",
+    "2020-07-01T10:30:00.000000 I 15 7 BufferMgr.cpp:303 ALLOCATION slab of 4194304 pages (2147483648B) created in 3 ms GPU_MGR:1",
+    "2020-07-01T11:00:00.000000 I 15 7 BufferMgr.cpp:296 ALLOCATION Attempted slab of 8388608 pages (4294967296B) failed GPU_MGR:1",
+    "2020-07-01T11:00:00.100000 I 15 7 BufferMgr.cpp:626 ALLOCATION failed to find 16777216B free. Forcing Eviction. Eviction start 0 Number pages requested 32768 Best Eviction Start Slab 0 GPU_MGR:1",
+    "2020-07-01T11:00:00.200000 I 15 7 RelAlgExecutor.cpp:2892 Query ran out of GPU memory, attempting punt to CPU",
+    r#"2020-07-01T11:00:01.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 11 1500 omnisci admin 409-as47 {"query_str","execution_time_ms","total_time_ms"} {"SELECT * FROM big;","1400","1500"}"#,
+    r#"2020-07-01T11:05:00.000000 I 15 3 DBHandler.cpp:2340 stdlog clear_gpu_memory 12 10 omnisci admin 409-as47 {"client"} {"tcp:localhost:52066"}"#,
+    "2020-07-01T11:06:00.000000 I 15 7 BufferMgr.cpp:303 ALLOCATION slab of 4194304 pages (2147483648B) created in 3 ms GPU_MGR:1",
+    "2020-07-01T11:07:00.000000 I 15 7 RelAlgExecutor.cpp:2892 Query ran out of GPU memory, attempting punt to CPU",
+];
+
+#[test]
+fn allocation_column() {
+    let log = pln(LINES[0]);
+    assert_eq!(log.event.as_deref(), Some("memory_slab"));
+    let memory = log.memory.as_ref().unwrap();
+    assert_eq!(memory.device, "GPU");
+    assert_eq!(memory.device_id, Some(1));
+    assert_eq!(memory.bytes, Some(4294967296));
+    assert_eq!(memory.pages, Some(8388608));
+    assert_eq!(memory.ms, Some(5));
+
+    let log = pln(LINES[3]);
+    assert_eq!(log.event.as_deref(), Some("memory_allocation"));
+    assert_eq!(log.severity.to_string(), "WARNING");
+    assert_eq!(log.memory.as_ref().unwrap().ms, None);
+    let memory = pln(LINES[4]).memory.unwrap();
+    assert_eq!((memory.bytes, memory.pages, memory.device_id), (Some(16777216), Some(32768), Some(1)));
+    let log = pln(LINES[5]);
+    assert_eq!(log.event.as_deref(), Some("memory_punt_to_cpu"));
+    assert_eq!(log.memory.unwrap().device_name(), "GPU");
+
+    assert!(pln(LINES[6]).memory.is_none());
+    let json = serde_json::to_value(pln(LINES[1])).unwrap();
+    let column: serde_json::Value = serde_json::from_str(json["memory"].as_str().unwrap()).unwrap();
+    assert_eq!(column["device"], "CPU");
+    assert_eq!(column["bytes"], 4294967296i64);
+}
+
+#[test]
+fn timeline_punts_and_peaks() -> olog::SResult<()> {
+    let mut writer = MemoryWriter::new(olog::ReportOptions::default());
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    let summary: Vec<(&str, &str, Option<i64>)> = rows.iter()
+        .map(|x| (x.category.as_str(), x.device.as_str(), x.allocated_bytes))
+        .collect();
+    assert_eq!(summary, vec![
+        ("allocation", "GPU 1", Some(4294967296)),
+        ("allocation", "CPU 0", Some(4294967296)),
+        ("allocation", "GPU 1", Some(6442450944)),
+        ("failure", "GPU 1", Some(6442450944)),
+        ("failure", "GPU 1", Some(6442450944)),
+        ("punt", "GPU", None),
+        // after clear_gpu_memory
+        ("allocation", "GPU 1", Some(2147483648)),
+        ("punt", "GPU", None),
+        ("peak", "CPU 0", Some(4294967296)),
+        ("peak", "GPU 1", Some(6442450944)),
+        ("peak", "GPU 1", Some(2147483648)),
+    ]);
+
+    // the punt during the sql_execute, and one without a call
+    assert_eq!(rows[5].sequence, Some(11));
+    assert_eq!(rows[5].session, "409-as47");
+    assert_eq!(rows[5].execution_time, Some(1400));
+    assert_eq!(rows[5].query, "SELECT * FROM big;");
+    assert_eq!(rows[7].sequence, None);
+
+    assert_eq!(rows[9].time, "2020-07-01 10:00");
+    assert_eq!((rows[9].peak_bytes, rows[9].allocations, rows[9].failures), (Some(6442450944), Some(2), Some(0)));
+    // the bytes from 10:00 count in the peak of 11:00
+    assert_eq!(rows[10].time, "2020-07-01 11:00");
+    assert_eq!((rows[10].peak_bytes, rows[10].allocations, rows[10].failures), (Some(6442450944), Some(1), Some(2)));
+    Ok(())
+}
@@ -0,0 +1,103 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::metrics::Metrics;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+mod common;
+use common::{pln, input};
+
+fn metrics() -> olog::SResult<Metrics> {
+    let mut metrics = Metrics::new();
+    for line in [
+        r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 3 0 omnisci admin 409-as47 {"query_str"} {"SELECT count(*) from flights;"}"#,
+        r#"2020-07-01T00:00:02.000000 I 15 4 DBHandler.cpp:1476 stdlog_begin sql_execute 4 0 omnisci bob 455-NzOR {"query_str"} {"SELECT 2;"}"#,
+        r#"2020-07-01T00:00:03.000000 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 4 1000 omnisci bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT 2;","tcp:172.17.0.2:53140","","999","1000"}"#,
+        "2020-07-01T00:00:04.000000 I 15 3 Execute.cpp:100 Query ran out of GPU memory, attempting punt to CPU",
+        "2020-07-01T00:00:05.000000 I 15 3 BufferMgr.cpp:100 ALLOCATION failed to find 1048576B free. Forcing Eviction.",
+    ] {
+        metrics.write(&input(line, "a/omnisci_server.INFO"))?;
+    }
+    Ok(metrics)
+}
+
+#[test]
+fn render() -> olog::SResult<()> {
+    let mut metrics = metrics()?;
+    let text = metrics.render();
+    assert!(text.contains("# TYPE omnisci_log_lines_total counter\n"));
+    assert!(text.contains(r#"omnisci_log_lines_total{event="sql_execute",severity="INFO",operation="SELECT",dbname="omnisci"} 1"#));
+    assert!(text.contains(r#"omnisci_log_lines_total{event="sql_execute_begin",severity="INFO",operation="SELECT",dbname="omnisci"} 2"#));
+    assert!(text.contains("omnisci_execution_time_seconds_bucket{le=\"0.5\"} 0\n"));
+    assert!(text.contains("omnisci_execution_time_seconds_bucket{le=\"1\"} 1\n"));
+    assert!(text.contains("omnisci_total_time_seconds_bucket{le=\"+Inf\"} 1\n"));
+    assert!(text.contains("omnisci_total_time_seconds_sum 1\n"));
+    assert!(text.contains("omnisci_memory_allocation_failed_total 1\n"));
+    assert!(text.contains("omnisci_memory_punt_to_cpu_total 1\n"));
+    assert!(text.contains("omnisci_in_flight_calls 1\n"));
+
+    // another server without a --hostname, with the same pid and sequence, is not a restart
+    metrics.write(&input(r#"2020-07-01T00:00:30.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 3 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#, "b/omnisci_server.INFO"))?;
+    metrics.write(&pln("2020-07-01T00:00:40.000000 I 17 1 DBHandler.cpp:100 OmniSci Server 5.6.0"))?;
+    assert_eq!(metrics.in_flight(), 2);
+    // a restarted server never ends the calls of the old one, and the other keeps its own
+    metrics.write(&input("2020-07-01T00:01:00.000000 I 16 1 DBHandler.cpp:100 OmniSci Server 5.6.0", "a/omnisci_server.INFO"))?;
+    assert_eq!(metrics.in_flight(), 1);
+
+    // nor does a call whose stdlog does not come within an hour
+    let mut metrics = self::metrics()?;
+    metrics.write(&input("2020-07-01T01:00:01.000000 I 15 3 DBHandler.cpp:100 OmniSci Server 5.6.0", "a/omnisci_server.INFO"))?;
+    assert_eq!(metrics.in_flight(), 1);
+    metrics.write(&input("2020-07-01T01:01:02.000000 I 15 3 DBHandler.cpp:100 OmniSci Server 5.6.0", "a/omnisci_server.INFO"))?;
+    assert_eq!(metrics.in_flight(), 0);
+    Ok(())
+}
+
+fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serve() -> olog::SResult<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?.to_string();
+    let metrics = Arc::new(Mutex::new(metrics()?));
+    {
+        let metrics = metrics.clone();
+        thread::spawn(move || olog::metrics::serve(listener, metrics));
+    }
+
+    // a client that never sends its request does not block the others
+    let _idle = TcpStream::connect(&address)?;
+    let response = get(&address, "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+    assert!(response.ends_with("omnisci_in_flight_calls 1\n"));
+
+    assert!(get(&address, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    Ok(())
+}
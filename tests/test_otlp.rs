@@ -0,0 +1,175 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::otlp::{OtlpSink, OtlpWriter};
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+use serde_json::Value;
+
+mod common;
+use common::{pln, Buffer};
+
+const LINES: [&str; 6] = [
+    r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 7 0 omnisci admin 409-as47 {"query_str"} {"SELECT count(*) from flights;"}"#,
+    "2020-07-01T00:00:01.700000 I 15 3 Calcite.cpp:513 Time in Thrift 6 (ms), Time in Java Calcite server 692 (ms)",
+    "2020-07-01T00:00:01.800000 I 15 4 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)",
+    "2020-07-01T00:00:02.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+    r#"2020-07-01T00:00:02.500000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 7 1500 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from flights;","tcp:172.17.0.1:53140","{""chartId"":""3"",""dashboardId"":100}","1499","1500"}"#,
+    r#"2020-07-01T00:00:03.000000 I 15 5 DBHandler.cpp:616 stdlog disconnect 8 10 omnisci admin 409-as47 {"client"} {"tcp:172.17.0.1:53140"}"#,
+];
+
+// the spans written, of all requests
+fn written_spans(buffer: &Buffer) -> olog::SResult<Vec<Value>> {
+    let text = buffer.text();
+    let mut spans = vec![];
+    for line in text.lines() {
+        let request: Value = serde_json::from_str(line)?;
+        for resource in request["resourceSpans"].as_array().unwrap() {
+            spans.extend(resource["scopeSpans"][0]["spans"].as_array().unwrap().iter().cloned());
+        }
+    }
+    Ok(spans)
+}
+
+fn attribute<'a>(span: &'a Value, key: &str) -> &'a Value {
+    let attributes = span["attributes"].as_array().unwrap();
+    &attributes.iter().find(|x| x["key"] == key).unwrap()["value"]
+}
+
+#[test]
+fn spans() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = OtlpWriter::new(OtlpSink::File(Box::new(buffer.clone())));
+    for line in LINES.iter() {
+        let mut log = pln(line);
+        log.hostname = Some(String::from("h1"));
+        writer.write(&log)?;
+    }
+    writer.close()?;
+
+    let text = buffer.text();
+    let request: Value = serde_json::from_str(text.lines().next().unwrap())?;
+    let resource = &request["resourceSpans"][0];
+    assert_eq!(resource["resource"]["attributes"][1]["value"]["stringValue"], "h1");
+    let spans = written_spans(&buffer)?;
+    let names: Vec<&str> = spans.iter().map(|x| x["name"].as_str().unwrap()).collect();
+    // the sql_parse of thread 4 is not part of the call
+    assert_eq!(names, vec!["sql_execute", "sql_parse", "disconnect"]);
+
+    let call = &spans[0];
+    assert_eq!(call["startTimeUnixNano"], "1593561601000000000");
+    assert_eq!(call["endTimeUnixNano"], "1593561602500000000");
+    assert_eq!(call["status"]["code"], 2);
+    assert_eq!(call["status"]["message"], "Query execution failed with error Table 12 not found");
+    assert_eq!(attribute(call, "db.name")["stringValue"], "omnisci");
+    assert_eq!(attribute(call, "db.user")["stringValue"], "admin");
+    assert_eq!(attribute(call, "omnisci.client")["stringValue"], "tcp:172.17.0.1:53140");
+    assert_eq!(attribute(call, "omnisci.dashboardid")["stringValue"], "100");
+    assert_eq!(attribute(call, "omnisci.chartid")["stringValue"], "3");
+    assert_eq!(attribute(call, "omnisci.total_time_ms")["intValue"], "1500");
+
+    let child = &spans[1];
+    assert_eq!(child["traceId"], call["traceId"]);
+    assert_eq!(child["parentSpanId"], call["spanId"]);
+    assert_eq!(child["startTimeUnixNano"], "1593561601008000000");
+    assert!(child["traceId"].as_str().unwrap().len() == 32 && child["spanId"].as_str().unwrap().len() == 16);
+
+    // without a stdlog_begin, the start is from dur_ms
+    assert_eq!(spans[2]["startTimeUnixNano"], "1593561602990000000");
+    assert!(spans[2]["traceId"] != call["traceId"]);
+    Ok(())
+}
+
+#[test]
+fn spans_of_query() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = OtlpWriter::new(OtlpSink::File(Box::new(buffer.clone())));
+    for line in [
+        r#"2020-07-01T00:00:01.000000 I 15 2 3 DBHandler.cpp:1476 stdlog_begin sql_execute 7 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#,
+        // another thread of the query
+        "2020-07-01T00:00:01.700000 I 15 2 6 Calcite.cpp:513 Time in Thrift 6 (ms), Time in Java Calcite server 692 (ms)",
+        // another query on the thread of the first call, after it
+        "2020-07-01T00:00:01.800000 I 15 9 6 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)",
+        r#"2020-07-01T00:00:02.000000 I 15 2 3 DBHandler.cpp:1058 stdlog sql_execute 7 1000 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#,
+        // a call begun long ago is dropped, its stdlog starts from dur_ms
+        r#"2020-07-01T00:00:03.000000 I 15 4 5 DBHandler.cpp:1476 stdlog_begin sql_execute 8 0 omnisci admin 409-as47 {"query_str"} {"SELECT 2;"}"#,
+        "2020-07-01T02:00:00.000000 I 15 4 5 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)",
+        r#"2020-07-01T02:00:01.000000 I 15 4 5 DBHandler.cpp:1058 stdlog sql_execute 8 1000 omnisci admin 409-as47 {"query_str"} {"SELECT 2;"}"#,
+    ].iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+
+    let spans = written_spans(&buffer)?;
+    let names: Vec<&str> = spans.iter().map(|x| x["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["sql_execute", "sql_parse", "sql_execute"]);
+    assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+    assert_eq!(spans[1]["startTimeUnixNano"], "1593561601008000000");
+    assert_eq!(spans[2]["startTimeUnixNano"], "1593568800000000000");
+    Ok(())
+}
+
+// a collector that answers each request with the status, and returns the bodies
+fn collector(statuses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut bodies = vec![];
+        for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break
+                }
+                if let Some(x) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = x.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+            write!(reader.into_inner(), "HTTP/1.1 {}\r\nContent-Length: 4\r\n\r\nnope", status).unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
+
+#[test]
+fn spans_over_http() -> olog::SResult<()> {
+    // a failed request is retried
+    let (url, collector) = collector(vec!["503 Service Unavailable", "200 OK"]);
+    let mut writer = OtlpWriter::new(OtlpSink::Http(url));
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    writer.close()?;
+    let bodies = collector.join().unwrap();
+    assert_eq!(bodies[0], bodies[1]);
+    let request: Value = serde_json::from_str(&bodies[1])?;
+    assert_eq!(request["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap().len(), 3);
+    Ok(())
+}
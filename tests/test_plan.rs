@@ -0,0 +1,104 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::plan::{parse_plan, PlanWriter};
+
+mod common;
+use common::{parse, Buffer};
+
+// the same query on 5.4 and 5.5, the second plan without the project
+const LOG: &str = r#"2020-07-01T00:00:00.000000 I 15 0 DBHandler.cpp:238 OmniSci Server 5.4.1-20200925-6728b9462b
+2020-07-01T00:00:01.000000 2 15 6 RelAlgDagBuilder.cpp:2582 Parsing query RA JSON: {
+  "rels": [
+    {"id": "0", "relOp": "LogicalTableScan", "fieldNames": ["a", "b"], "table": ["omnisci", "t"], "inputs": []},
+    {"id": "1", "relOp": "LogicalFilter", "condition": {"op": ">", "operands": [{"input": 1}, {"literal": 10, "type": "DECIMAL"}]}},
+    {"id": "2", "relOp": "LogicalProject", "fields": ["a"], "exprs": [{"input": 0}]},
+    {"id": "3", "relOp": "LogicalAggregate", "fields": ["a", "EXPR$1"], "group": [0], "aggs": [{"agg": "COUNT", "distinct": false, "operands": []}]}
+  ]
+}
+2020-07-01T00:00:01.100000 3 15 6 RelAlgExecutor.cpp:979 ra_node=(RelCompound<140689908460656>(null (RexAgg 4 0 BIGINT NONE) groups: [ ] sources: [ (RexInput 0 140687846984800) ])) input_col_descs_unique.size()=1 source_used_inputs.size()=1
+2020-07-01T00:00:01.200000 3 15 6 RelAlgExecutor.cpp:979 ra_node=(RelCompound<140689908460656>(null (RexAgg 4 0 BIGINT NONE) groups: [ ] sources: [ (RexInput 0 140687846984800) ])) input_col_descs_unique.size()=1 source_used_inputs.size()=1
+2020-07-01T00:00:01.500000 I 15 DBHandler.cpp:1058 stdlog sql_execute 7 600 omnisci admin 409-as47 {"query_str","execution_time_ms","total_time_ms"} {"SELECT a, count(*) FROM t WHERE b > 10 GROUP BY a;","590","600"}
+2020-07-01T00:00:02.000000 I 16 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2
+2020-07-01T00:00:03.000000 2 16 6 RelAlgDagBuilder.cpp:2582 Parsing query RA JSON: {
+  "rels": [
+    {"id": "0", "relOp": "LogicalTableScan", "fieldNames": ["a", "b"], "table": ["omnisci", "t"], "inputs": []},
+    {"id": "1", "relOp": "LogicalFilter", "condition": {"op": ">", "operands": [{"input": 1}, {"literal": 20, "type": "DECIMAL"}]}},
+    {"id": "2", "relOp": "LogicalAggregate", "fields": ["a", "EXPR$1"], "group": [0], "aggs": [{"agg": "COUNT", "distinct": false, "operands": []}]}
+  ]
+}
+2020-07-01T00:00:03.500000 I 16 DBHandler.cpp:1058 stdlog sql_execute 2 600 omnisci admin 510-bd88 {"query_str","execution_time_ms","total_time_ms"} {"SELECT a, count(*) FROM t WHERE b > 20 GROUP BY a;","590","600"}
+"#;
+
+#[test]
+fn plan_tree() {
+    let logs = parse(LOG);
+    assert_eq!(logs.len(), 8);
+    let log = &logs[1];
+    assert_eq!(log.event.as_deref(), Some("query_plan"));
+    assert_eq!(log.sequence, None);
+    let plan = log.plan.as_ref().unwrap();
+    assert_eq!(plan.root.op, "LogicalAggregate");
+    // without inputs, the input is the previous rel
+    assert_eq!(plan.root.inputs[0].inputs[0].op, "LogicalFilter");
+    assert_eq!(plan.explain, "Aggregate group=[0] COUNT()
+  Project a=$0
+    Filter ($1 > 10)
+      TableScan omnisci.t");
+
+    // literals are not part of the shape
+    let other = parse_plan(&logs[1].msg.replace("\"literal\": 10", "\"literal\": 30")).unwrap();
+    assert_eq!(other.fingerprint, plan.fingerprint);
+    assert_ne!(logs[6].plan.as_ref().unwrap().fingerprint, plan.fingerprint);
+
+    let json = serde_json::to_value(log).unwrap();
+    let column: serde_json::Value = serde_json::from_str(json["plan"].as_str().unwrap()).unwrap();
+    assert_eq!(column["root"]["inputs"][0]["detail"], "a=$0");
+    assert!(parse_plan("Parsing query RA JSON: {").is_none());
+}
+
+#[test]
+fn plans_by_version() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = PlanWriter::new(Box::new(buffer.clone()));
+    let logs = parse(LOG);
+    for log in &logs {
+        writer.write(log)?;
+    }
+    writer.close()?;
+
+    let text = buffer.text();
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let rows: Vec<csv::StringRecord> = reader.records().collect::<Result<_, _>>()?;
+    assert_eq!(rows.len(), 2);
+    let headers = reader.headers()?.clone();
+    let get = |row: &csv::StringRecord, name: &str| row[headers.iter().position(|x| x == name).unwrap()].to_string();
+    assert_eq!(get(&rows[0], "version"), "5.4.1-20200925-6728b9462b");
+    assert_eq!(get(&rows[0], "sequence"), "7");
+    assert_eq!(get(&rows[0], "event"), "sql_execute");
+    // the distinct dumps, without addresses
+    assert_eq!(get(&rows[0], "ra_nodes"), "(RelCompound(null (RexAgg 4 0 BIGINT NONE) groups: [ ] sources: [ (RexInput 0) ]))");
+    assert_eq!(get(&rows[1], "version"), "5.5.0-20201201-1b17b5c4e2");
+    assert_eq!(get(&rows[1], "sequence"), "2");
+
+    // the same query with another plan after the upgrade
+    assert_eq!(get(&rows[0], "fingerprint"), get(&rows[1], "fingerprint"));
+    assert_ne!(get(&rows[0], "plan_fingerprint"), get(&rows[1], "plan_fingerprint"));
+    Ok(())
+}
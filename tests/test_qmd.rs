@@ -0,0 +1,122 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::qmd::QmdWriter;
+
+use serde_json::json;
+
+mod common;
+use common::{parse, Buffer};
+
+// pid 15, queryid 3 and 4, the stdlog of queryid 3 on thread 2
+const LOG: &str = "2020-07-01T00:00:01.000000 1 15 3 8 Execute.cpp:1984 Query Memory Descriptor State
+\tQuery Type: Baseline Hash
+\tKeyless Hash: False
+\tEffective key width: 8
+\tCol Slot Context State
+\tN | P , L
+\t0 | 8 , 8
+\t1 | 8 , 4
+\tEntry Count: 2048
+\tMin Val (perfect hash only): 0
+\tOutput Columnar: True
+2020-07-01T00:00:01.100000 1 15 4 9 Execute.cpp:1984 Query Memory Descriptor State
+\tQuery Type: Projection
+\tOutput Columnar: False
+2020-07-01T00:00:01.500000 I 15 3 2 DBHandler.cpp:1058 stdlog sql_execute 7 600 omnisci admin 409-as47 {\"query_str\",\"execution_time_ms\",\"total_time_ms\"} {\"SELECT a, count(*) FROM t GROUP BY a;\",\"590\",\"600\"}
+";
+
+#[test]
+fn query_memory_descriptor() {
+    let logs = parse(LOG);
+    assert_eq!(logs.len(), 3);
+    let log = &logs[0];
+    assert_eq!(log.event.as_deref(), Some("query_memory_descriptor"));
+    assert_eq!(log.msg_norm.as_deref(), Some("Query Memory Descriptor State"));
+    assert_eq!((log.queryid, log.threadid), (Some(3), Some(8)));
+    let qmd = log.qmd.as_ref().unwrap();
+    assert_eq!(qmd["query_type"], "Baseline Hash");
+    assert_eq!(qmd["keyless_hash"], false);
+    assert_eq!(qmd["output_columnar"], true);
+    assert_eq!(qmd["entry_count"], 2048);
+    assert_eq!(qmd["min_val_perfect_hash_only"], 0);
+    assert_eq!(qmd["col_slot_context"], json!([
+        {"slot": 0, "padded": 8, "logical": 8},
+        {"slot": 1, "padded": 8, "logical": 4},
+    ]));
+    // the heading of the slots is not a value
+    assert!(qmd.get("col_slot_context_state").is_none());
+    assert!(logs[2].qmd.is_none());
+}
+
+#[test]
+fn qmd_with_its_query() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = QmdWriter::new(Box::new(buffer.clone()));
+    for log in parse(LOG) {
+        writer.write(&log)?;
+    }
+    writer.close()?;
+
+    let text = buffer.text();
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let rows: Vec<csv::StringRecord> = reader.records().map(|x| x.unwrap()).collect();
+    let headers = reader.headers()?.clone();
+    let get = |row: &csv::StringRecord, name: &str| row[headers.iter().position(|x| x == name).unwrap()].to_string();
+    assert_eq!(rows.len(), 2);
+
+    // queryid 3 is in the sql_execute
+    assert_eq!(get(&rows[0], "queryid"), "3");
+    assert_eq!(get(&rows[0], "query_type"), "Baseline Hash");
+    assert_eq!(get(&rows[0], "output_columnar"), "true");
+    assert_eq!(get(&rows[0], "sequence"), "7");
+    assert_eq!(get(&rows[0], "execution_time"), "590");
+    assert_eq!(get(&rows[0], "query"), "SELECT a, count(*) FROM t GROUP BY a;");
+    let qmd: serde_json::Value = serde_json::from_str(&get(&rows[0], "qmd"))?;
+    assert_eq!(qmd["effective_key_width"], 8);
+
+    // queryid 4 has no stdlog, so it is written at the end without one
+    assert_eq!(get(&rows[1], "queryid"), "4");
+    assert_eq!(get(&rows[1], "sequence"), "");
+    Ok(())
+}
+
+#[test]
+fn qmd_without_a_call_expires() -> olog::SResult<()> {
+    let buffer = Buffer::new();
+    let mut writer = QmdWriter::new(Box::new(buffer.clone()));
+    let lines = "2020-07-01T00:00:01.000000 1 15 5 8 Execute.cpp:1984 Query Memory Descriptor State
+\tQuery Type: Projection
+2020-07-01T02:00:00.000000 I 15 6 2 DBHandler.cpp:1058 stdlog sql_execute 8 10 omnisci admin 409-as47 {\"query_str\",\"execution_time_ms\",\"total_time_ms\"} {\"SELECT 1;\",\"9\",\"10\"}
+2020-07-01T02:00:01.000000 I 15 5 2 DBHandler.cpp:1058 stdlog sql_execute 9 7201000 omnisci admin 409-as47 {\"query_str\",\"execution_time_ms\",\"total_time_ms\"} {\"SELECT 2;\",\"7200990\",\"7201000\"}
+";
+    for log in parse(lines) {
+        writer.write(&log)?;
+    }
+    writer.close()?;
+
+    // an hour after it, the descriptor is no longer waiting for its call
+    let text = buffer.text();
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers = reader.headers()?.clone();
+    let rows: Vec<csv::StringRecord> = reader.records().map(|x| x.unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(&rows[0][headers.iter().position(|x| x == "sequence").unwrap()], "");
+    Ok(())
+}
@@ -0,0 +1,96 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+
+mod common;
+use common::pln;
+
+fn write_lines(writer: &mut dyn LogWriter) -> olog::SResult<()> {
+    for line in [
+        r#"2020-07-01T00:00:00.000105 I 15 1 DBHandler.cpp:533 stdlog connect 0 233 omnisci admin 409-as47 {"client","roles"} {"tcp:172.17.0.1:53140","super"}"#,
+        r#"2020-07-01T00:00:01.000000 I 15 2 DBHandler.cpp:2154 stdlog get_tables 2 0 omnisci calcite 409-pVPx {"client"} {"tcp:localhost:46548"}"#,
+        r#"2020-07-01T00:00:02.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 1 0 omnisci admin 409-as47 {"query_str"} {"SELECT count(*) from omnisci_states;"}"#,
+        r#"2020-07-01T00:00:02.500000 I 15 3 Calcite.cpp:513 Time in Thrift 13 (ms), Time in Java Calcite server 532 (ms)"#,
+        r#"2020-07-01T00:00:03.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 1 906 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) from omnisci_states;","tcp:172.17.0.1:53140","","905","906"}"#,
+        r#"2020-07-01T00:00:03.100000 I 15 3 Calcite.cpp:513 Time in Thrift 1 (ms), Time in Java Calcite server 2 (ms)"#,
+        r#"2020-07-01T00:00:04.000000 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 4 17 omnisci admin 409-as47 {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"select 1;","tcp:172.17.0.1:53140","","16","17"}"#,
+        r#"2020-07-01T00:00:05.000000 I 15 5 DBHandler.cpp:533 stdlog connect 7 100 omnisci mapd 148-6778 {"client","roles"} {"tcp:10.0.0.2:40000","public"}"#,
+        r#"2020-07-01T00:00:10.000000 I 15 4 DBHandler.cpp:616 stdlog disconnect 6 0 omnisci admin 409-as47 {"client"} {"tcp:172.17.0.1:53140"}"#,
+        r#"2020-07-01T02:00:05.000000 I 15 6 DBHandler.cpp:183 Session 148-6778 idle duration 7200 seconds exceeds maximum idle duration 7200 seconds. Invalidating session."#,
+    ] {
+        writer.write(&pln(line))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn sessions() -> olog::SResult<()> {
+    let mut sessions = olog::sessions::SessionsWriter::new(olog::ReportOptions::default());
+    write_lines(&mut sessions)?;
+
+    let rows = sessions.rows();
+    assert_eq!(rows.len(), 3);
+    let row = &rows[0];
+    assert_eq!(row.session, "409-as47");
+    assert_eq!(row.username, "admin");
+    assert_eq!(row.client, "172.17.0.1");
+    assert_eq!(row.dbname, "omnisci");
+    assert_eq!(row.start_event, "connect");
+    assert_eq!(row.end_event, "disconnect");
+    assert_eq!(row.duration_s, 9.999);
+    assert_eq!(row.calls, 4);
+    assert_eq!(row.queries, 2);
+    assert_eq!(row.query_time_ms, 923);
+
+    // already open when the log starts
+    assert_eq!(rows[1].session, "409-pVPx");
+    assert_eq!(rows[1].start_event, "");
+    assert_eq!(rows[1].end, "");
+
+    assert_eq!(rows[2].session, "148-6778");
+    assert_eq!(rows[2].client, "10.0.0.2");
+    assert_eq!(rows[2].end_event, "session_invalidated");
+    assert_eq!(rows[2].duration_s, 7200.0);
+    Ok(())
+}
+
+#[test]
+fn session_activity() -> olog::SResult<()> {
+    let options = olog::ReportOptions {
+        session: Some(String::from("409-as47")),
+        ..Default::default()
+    };
+    let mut sessions = olog::sessions::SessionsWriter::new(options);
+    write_lines(&mut sessions)?;
+
+    let activity = sessions.activity();
+    let events: Vec<&str> = activity.iter().map(|x| x.event.as_str()).collect();
+    // the Calcite line after the sql_execute call ended is not part of the session
+    assert_eq!(events, vec!["connect", "sql_execute_begin", "sql_parse", "sql_execute", "sql_execute", "disconnect"]);
+    assert_eq!(activity[2].detail, "Time in Thrift 13 (ms), Time in Java Calcite server 532 (ms)");
+    assert_eq!(activity[3].dur_ms, Some(906));
+    Ok(())
+}
+
+#[test]
+fn client_ip() {
+    assert_eq!(olog::sessions::client_ip("tcp:172.17.0.1:53140"), "172.17.0.1");
+    assert_eq!(olog::sessions::client_ip("http:localhost"), "localhost");
+    assert_eq!(olog::sessions::client_ip("10.0.0.2"), "10.0.0.2");
+}
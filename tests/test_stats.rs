@@ -0,0 +1,139 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+
+mod common;
+use common::{pln, sql_execute};
+
+#[test]
+fn stats_by_event() -> olog::SResult<()> {
+    let mut stats = olog::stats::StatsWriter::new(olog::ReportOptions::default());
+    for ms in 1..=100 {
+        stats.write(&sql_execute("2020-07-01T00:00:00.000106", 1, "omnisci", ms))?;
+    }
+    // begin lines do not count toward dur_ms
+    stats.write(&pln(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog_begin sql_execute 1 0 omnisci admin 455-NzOR {"query_str"} {"SELECT 1;"}
+"#))?;
+
+    let rows = stats.rows();
+    let metrics: Vec<&str> = rows.iter().map(|x| x.metric.as_str()).collect();
+    assert_eq!(metrics, vec!["dur_ms", "execution_time", "total_time"]);
+    let row = &rows[0];
+    assert_eq!(row.event, Some(String::from("sql_execute")));
+    assert_eq!(row.dbname, None);
+    assert_eq!(row.count, 100);
+    assert_eq!(row.sum, 5050);
+    assert_eq!(row.avg, 50.5);
+    assert_eq!(row.p50, 50);
+    assert_eq!(row.p99, 99);
+    assert_eq!(row.max, 100);
+    Ok(())
+}
+
+#[test]
+fn stats_by_dbname_per_hour() -> olog::SResult<()> {
+    let options = olog::ReportOptions {
+        group_by: vec![olog::Dimension::new("dbname")],
+        bucket: Some(olog::TimeBucket::new("hour")),
+        ..Default::default()
+    };
+    let mut stats = olog::stats::StatsWriter::new(options);
+    stats.write(&sql_execute("2020-07-01T00:10:00.000000", 1, "omnisci", 10))?;
+    stats.write(&sql_execute("2020-07-01T00:50:00.000000", 1, "omnisci", 30))?;
+    stats.write(&sql_execute("2020-07-01T01:10:00.000000", 1, "omnisci", 20))?;
+    stats.write(&sql_execute("2020-07-01T01:20:00.000000", 1, "other", 40))?;
+
+    let rows: Vec<olog::stats::StatsRow> = stats.rows().into_iter().filter(|x| x.metric == "total_time").collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].bucket, Some(String::from("2020-07-01 00:00")));
+    assert_eq!(rows[0].dbname, Some(String::from("omnisci")));
+    assert_eq!(rows[0].event, None);
+    assert_eq!(rows[0].count, 2);
+    assert_eq!(rows[0].max, 30);
+    assert_eq!(rows[1].bucket, Some(String::from("2020-07-01 01:00")));
+    assert_eq!(rows[1].sum, 20);
+    assert_eq!(rows[2].dbname, Some(String::from("other")));
+
+    let table = olog::format_table(&rows)?;
+    assert!(table.lines().next().unwrap().contains("bucket"));
+    assert!(!table.contains("event"));
+    Ok(())
+}
+
+#[test]
+fn stats_groups_bounded() -> olog::SResult<()> {
+    let options = olog::ReportOptions {
+        group_by: vec![olog::Dimension::new("client")],
+        ..Default::default()
+    };
+    let mut stats = olog::stats::StatsWriter::new(options);
+    // each connection has its own port
+    for port in 50000..52000 {
+        stats.write(&pln(&format!(r#"
+2020-07-01T00:00:00.000106 I 15 DBHandler.cpp:1058 stdlog sql_execute 1 10 omnisci admin 455-NzOR {{"query_str","client"}} {{"SELECT 1;","tcp:172.17.0.1:{}"}}
+"#, port)))?;
+    }
+    let rows = stats.rows();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].client, Some(String::from("172.17.0.1")));
+    assert_eq!(rows[0].count, 2000);
+
+    let options = olog::ReportOptions {
+        group_by: vec![olog::Dimension::new("dbname")],
+        ..Default::default()
+    };
+    let mut stats = olog::stats::StatsWriter::new(options);
+    for i in 0..1100 {
+        stats.write(&sql_execute("2020-07-01T00:00:00.000106", 1, &format!("db{}", i), 10))?;
+    }
+    let rows: Vec<olog::stats::StatsRow> = stats.rows().into_iter().filter(|x| x.metric == "total_time").collect();
+    assert_eq!(rows.len(), 1001);
+    let other: Vec<&olog::stats::StatsRow> = rows.iter().filter(|x| x.dbname.as_deref() == Some("(other)")).collect();
+    assert_eq!(other.len(), 1);
+    assert_eq!(other[0].count, 100);
+    Ok(())
+}
+
+#[test]
+fn stats_groups_bounded_per_bucket() -> olog::SResult<()> {
+    let options = olog::ReportOptions {
+        group_by: vec![olog::Dimension::new("dbname")],
+        bucket: Some(olog::TimeBucket::new("hour")),
+        ..Default::default()
+    };
+    let mut stats = olog::stats::StatsWriter::new(options);
+    // the same 1000 dbs every hour for three days, and one more db in the last hour
+    for hour in 0..72 {
+        let time = format!("2020-07-{:02}T{:02}:00:00.000000", 1 + hour / 24, hour % 24);
+        for i in 0..1000 {
+            stats.write(&sql_execute(&time, 1, &format!("db{}", i), 10))?;
+        }
+        if hour == 71 {
+            stats.write(&sql_execute(&time, 1, "db1000", 10))?;
+        }
+    }
+    let rows: Vec<olog::stats::StatsRow> = stats.rows().into_iter().filter(|x| x.metric == "total_time").collect();
+    assert_eq!(rows.len(), 72 * 1000 + 1);
+    let last: Vec<&olog::stats::StatsRow> = rows.iter().filter(|x| x.bucket.as_deref() == Some("2020-07-03 23:00")).collect();
+    assert_eq!(last.len(), 1001);
+    assert!(last.iter().any(|x| x.dbname.as_deref() == Some("db999") && x.count == 1));
+    assert!(last.iter().any(|x| x.dbname.as_deref() == Some("(other)") && x.count == 1));
+    Ok(())
+}
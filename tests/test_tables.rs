@@ -0,0 +1,131 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::tables::{query_tables, TableWriter};
+
+mod common;
+use common::pln;
+
+fn tables(query: &str) -> Vec<(String, bool)> {
+    query_tables(query).into_iter().map(|x| (x.table, x.write)).collect()
+}
+
+fn read(table: &str) -> (String, bool) {
+    (table.to_string(), false)
+}
+
+fn write(table: &str) -> (String, bool) {
+    (table.to_string(), true)
+}
+
+#[test]
+fn tables_of_queries() {
+    assert_eq!(tables("INSERT INTO t2 SELECT a FROM t1 JOIN t3 ON t1.a = t3.a;"), vec![write("t2"), read("t1"), read("t3")]);
+    assert_eq!(tables("SELECT * FROM Flights f, \"Airports\" AS a, carriers WHERE f.origin = a.code"),
+        vec![read("flights"), read("airports"), read("carriers")]);
+    // the WITH query and function arguments are not tables, nor the FROM of a literal
+    assert_eq!(tables("WITH recent AS (SELECT * FROM flights WHERE dep_time > '2020-01-01 FROM x') \
+        SELECT EXTRACT(YEAR FROM dep_time), COUNT(*) FROM recent LEFT JOIN (SELECT * FROM airports) ON TRUE"),
+        vec![read("flights"), read("airports")]);
+    assert_eq!(tables("select * from t where a is distinct from b"), vec![read("t")]);
+    assert_eq!(tables("SELECT * FROM t WHERE a IN (SELECT b FROM u)"), vec![read("t"), read("u")]);
+    assert_eq!(tables("COPY flights FROM '/data/flights.csv' WITH (header='true');"), vec![write("flights")]);
+    assert_eq!(tables("COPY (SELECT * FROM flights) TO '/tmp/out.csv';"), vec![read("flights")]);
+    assert_eq!(tables("DROP TABLE IF EXISTS old_flights;"), vec![write("old_flights")]);
+    assert_eq!(tables("ALTER TABLE flights RENAME TO flights_2019;"), vec![write("flights")]);
+    assert_eq!(tables("CREATE TABLE IF NOT EXISTS t (a INT);"), vec![write("t")]);
+    assert_eq!(tables("DELETE FROM t WHERE a = 1;"), vec![write("t")]);
+    assert_eq!(tables("UPDATE t SET a = 1;"), vec![write("t")]);
+    assert_eq!(tables("CREATE USER bob (password = 'x');"), vec![]);
+    assert_eq!(tables("SELECT 1;"), vec![]);
+}
+
+#[test]
+fn reads_writes_and_never_queried() -> olog::SResult<()> {
+    let lines = [
+        r#"2020-07-01T10:00:00.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 20 5200 omnisci importer 410-imp1 {"query_str","execution_time_ms","total_time_ms"} {"COPY flights FROM '/data/flights.csv';","5190","5200"}"#,
+        r#"2020-07-01T10:00:01.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 21 40 omnisci dash 411-dsh1 {"query_str","nonce","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM flights;","7/3","30","40"}"#,
+        "2020-07-01T10:00:02.000000 1 15 2 LockMgr.h:113 Acquiring Table Schema Read Lock for table: staging",
+        "2020-07-01T10:00:02.000001 I 15 2 FileMgr.cpp:205 Completed Reading table's file metadata, Elapsed time : 11ms Epoch: 1 files read: 2 table location: '/omnisci-storage/data/mapd_data/table_1_5'",
+        "2020-07-01T10:00:02.000002 I 15 2 Catalog.cpp:1414 Instantiating Fragmenter for table staging took 12ms",
+        r#"2020-07-02T09:00:00.000000 I 15 DBHandler.cpp:1058 stdlog sql_execute 30 20 omnisci alice 412-ali1 {"query_str","execution_time_ms","total_time_ms"} {"select origin from FLIGHTS limit 1;","10","20"}"#,
+    ];
+    let mut writer = TableWriter::new(olog::ReportOptions::default());
+    for line in lines.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    let summary: Vec<(&str, &str, &str, u64, u64, u64)> = rows.iter()
+        .map(|x| (x.category.as_str(), x.time.as_str(), x.table.as_str(), x.reads, x.writes, x.storage))
+        .collect();
+    assert_eq!(summary, vec![
+        ("table", "", "staging", 0, 0, 2),
+        ("table", "", "flights", 2, 1, 0),
+        ("bucket", "2020-07-01", "staging", 0, 0, 2),
+        ("bucket", "2020-07-01", "flights", 1, 1, 0),
+        ("bucket", "2020-07-02", "flights", 1, 0, 0),
+    ]);
+
+    let staging = &rows[0];
+    // no call tells its db
+    assert_eq!(staging.dbname, "");
+    assert!(staging.never_queried);
+    assert_eq!(staging.location, "/omnisci-storage/data/mapd_data/table_1_5");
+    assert_eq!(staging.last_access, "2020-07-01 10:00:02.000002");
+    let flights = &rows[1];
+    assert!(!flights.never_queried);
+    assert_eq!(flights.last_read, "2020-07-02 09:00:00");
+    assert_eq!(flights.users, "alice,dash,importer");
+    assert_eq!(flights.dashboards, "7");
+    assert_eq!(flights.dbname, "omnisci");
+    Ok(())
+}
+
+#[test]
+fn tables_of_each_db() -> olog::SResult<()> {
+    let lines = [
+        r#"2020-07-01T10:00:00.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 20 40 db1 alice 410-ali1 {"query_str","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM flights;","30","40"}"#,
+        r#"2020-07-01T10:00:01.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 21 40 db2 bob 411-bob1 {"query_str","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM flights;","30","40"}"#,
+        // a lock of the call on its thread
+        r#"2020-07-01T10:00:02.000000 I 15 4 DBHandler.cpp:1476 stdlog_begin sql_execute 22 0 db2 bob 411-bob1 {"query_str"} {"SELECT count(*) FROM flights;"}"#,
+        "2020-07-01T10:00:02.000001 1 15 4 LockMgr.h:113 Acquiring Table Schema Read Lock for table: flights",
+        r#"2020-07-01T10:00:02.000002 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 22 10 db2 bob 411-bob1 {"query_str","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM flights;","5","10"}"#,
+        // after the call, the db of the thread is not known, and flights is in two
+        "2020-07-01T10:00:03.000000 1 15 4 LockMgr.h:113 Acquiring Table Schema Read Lock for table: flights",
+        r#"2020-07-01T10:00:04.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 23 40 db1 alice 410-ali1 {"query_str","execution_time_ms","total_time_ms"} {"SELECT count(*) FROM carriers;","30","40"}"#,
+        // carriers is only in db1
+        "2020-07-01T10:00:05.000000 1 15 5 LockMgr.h:113 Acquiring Table Schema Read Lock for table: carriers",
+    ];
+    let mut writer = TableWriter::new(olog::ReportOptions::default());
+    for line in lines.iter() {
+        writer.write(&pln(line))?;
+    }
+    let mut summary: Vec<(String, String, u64, u64, String)> = writer.rows().into_iter()
+        .filter(|x| x.category == "table")
+        .map(|x| (x.dbname, x.table, x.reads, x.storage, x.users))
+        .collect();
+    summary.sort();
+    assert_eq!(summary, vec![
+        (String::from(""), String::from("flights"), 0, 1, String::from("")),
+        (String::from("db1"), String::from("carriers"), 1, 1, String::from("alice")),
+        (String::from("db1"), String::from("flights"), 1, 0, String::from("alice")),
+        (String::from("db2"), String::from("flights"), 2, 1, String::from("bob")),
+    ]);
+    Ok(())
+}
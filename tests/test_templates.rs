@@ -0,0 +1,75 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+
+mod common;
+use common::pln;
+
+#[test]
+fn mine_templates() {
+    let mut miner = olog::templates::TemplateMiner::new();
+    let (id1, template, params) = miner.mine("LockMgr.h:113", "Acquiring Table Schema Read Lock for table: flights");
+    assert_eq!(template, "Acquiring Table Schema Read Lock for table: flights");
+    assert!(params.is_empty());
+
+    // the differing token becomes a wildcard of the same template
+    let (id2, template, params) = miner.mine("LockMgr.h:113", "Acquiring Table Schema Read Lock for table: omnisci_states");
+    assert_eq!(id1, id2);
+    assert_eq!(template, "Acquiring Table Schema Read Lock for table: <*>");
+    assert_eq!(params, vec!["omnisci_states"]);
+
+    // tokens with digits are always parameters
+    let (_, template, params) = miner.mine("FileInfo.cpp:127", "FId.PSz: 1.2097152 Chunk key: 1,5,1,0 Page id: 0 Epoch: 2");
+    assert_eq!(template, "FId.PSz: <*> Chunk key: <*> Page id: <*> Epoch: <*>");
+    assert_eq!(params, vec!["1.2097152", "1,5,1,0", "0", "2"]);
+
+    // same words from another fileline is another template
+    let (id3, _, _) = miner.mine("LockMgr.h:207", "Acquiring Table Schema Read Lock for table: flights");
+    assert!(id3 != id1);
+
+    // not similar enough
+    let (id4, _, _) = miner.mine("LockMgr.h:113", "Releasing Table Data Write Lock after table: flights");
+    assert!(id4 != id1);
+}
+
+#[test]
+fn templates_file() -> olog::SResult<()> {
+    std::fs::create_dir_all("target/test")?;
+    let path = "target/test/templates.json";
+    let _ = std::fs::remove_file(path);
+
+    let mut miner = olog::templates::TemplateMiner::load(path)?;
+    miner.mine("Calcite.cpp:513", "Time in Thrift 13 (ms), Time in Java Calcite server 1532 (ms)");
+    let (id, _, _) = miner.mine("DBHandler.cpp:581", "User admin connected to database omnisci");
+    miner.save(path)?;
+    assert!(!std::path::Path::new("target/test/templates.json.tmp").exists());
+
+    // ids are kept when the templates are loaded again
+    let mut miner = olog::templates::TemplateMiner::load(path)?;
+    let (id2, _, _) = miner.mine("DBHandler.cpp:581", "User mapd connected to database omnisci");
+    assert_eq!(id, id2);
+    let (id3, _, _) = miner.mine("DBHandler.cpp:581", "Something else entirely");
+    assert_eq!(id3, 3);
+
+    let mut log = pln("2020-07-01T00:00:00.000104 I 15 DBHandler.cpp:581 User admin connected to database mapd");
+    miner.apply(&mut log);
+    assert_eq!(log.template_id, Some(id));
+    assert_eq!(log.msg_norm.unwrap(), "User <*> connected to database <*>");
+    assert_eq!(log.template_params.unwrap(), vec!["admin", "mapd"]);
+    Ok(())
+}
@@ -0,0 +1,98 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::timers::{stacks, FoldedWriter, Timer, TimersWriter};
+
+mod common;
+use common::{parse, Buffer};
+
+const LOG: &str = "2020-07-01T00:00:00.000178 1 15 DBHandler.cpp:1058 stacked_times sql_execute 1 total time 906 ms
+  parse_to_ra 140690399721216 - total time 711 ms
+    processImpl 140690399721216 - total time 698 ms
+  execute_rel_alg 140691342599936 - total time 194 ms
+    convert_rows 140691342599936 - total time 0 ms
+2020-07-01T00:00:00.000207 1 15 DBHandler.cpp:1058 stacked_times sql_execute 4 total time 17 ms
+  parse_to_ra 140690399721216 - total time 14 ms
+    processImpl 140690399721216 - total time 14 ms
+  execute_rel_alg 140691342599936 - total time 3 ms
+    convert_rows 140691342599936 - total time 0 ms
+2020-07-01T00:00:00.000208 I 15 DBHandler.cpp:1058 stdlog sql_execute 4 17 omnisci admin 409-as47 {\"query_str\"} {\"SELECT 1;\"}
+";
+
+#[test]
+fn stacked_times() {
+    let logs = parse(LOG);
+    assert_eq!(logs.len(), 3);
+    let log = &logs[0];
+    assert_eq!(log.event.as_deref(), Some("stacked_times"));
+    assert_eq!(log.total_time, Some(906));
+    // not a stdlog, the sequence is in the timers
+    assert_eq!(log.sequence, None);
+    let stacked = log.timers.as_ref().unwrap();
+    assert_eq!(stacked.sequence, 1);
+    assert_eq!(stacked.timers.len(), 5);
+    assert_eq!(stacked.timers[2], Timer {
+        name: String::from("processImpl"),
+        thread: Some(140690399721216),
+        total_ms: 698,
+        depth: 2,
+    });
+    assert_eq!(stacked.timers[3].depth, 1);
+    assert!(logs[2].timers.is_none());
+
+    let json = serde_json::to_value(log).unwrap();
+    let column: serde_json::Value = serde_json::from_str(json["timers"].as_str().unwrap()).unwrap();
+    assert_eq!(column["timers"][1]["name"], "parse_to_ra");
+
+    let stacks = stacks(&stacked.timers);
+    assert_eq!(stacks[0], (String::from("sql_execute"), 1));
+    assert_eq!(stacks[1], (String::from("sql_execute;parse_to_ra"), 13));
+    assert_eq!(stacks[4], (String::from("sql_execute;execute_rel_alg;convert_rows"), 0));
+}
+
+#[test]
+fn timers_and_folded() -> olog::SResult<()> {
+    let table = Buffer::new();
+    let folded = Buffer::new();
+    let mut writers: Vec<Box<dyn LogWriter>> = vec![
+        Box::new(TimersWriter::new(Box::new(table.clone()))),
+        Box::new(FoldedWriter::new(Box::new(folded.clone()))),
+    ];
+    for writer in writers.iter_mut() {
+        for log in parse(LOG) {
+            writer.write(&log)?;
+        }
+        writer.close()?;
+    }
+
+    let table = table.text();
+    let rows: Vec<&str> = table.lines().collect();
+    assert_eq!(rows.len(), 11);
+    assert_eq!(rows[0], "logtime,hostname,pid,sequence,depth,name,thread,total_ms,self_ms,stack");
+    assert_eq!(rows[7], "2020-07-01 00:00:00.000207,,15,4,1,parse_to_ra,140690399721216,14,0,sql_execute;parse_to_ra");
+
+    // summed over both calls, without the stacks of no time
+    let folded = folded.text();
+    assert_eq!(folded, "sql_execute 1
+sql_execute;execute_rel_alg 197
+sql_execute;parse_to_ra 13
+sql_execute;parse_to_ra;processImpl 712
+");
+    Ok(())
+}
@@ -0,0 +1,158 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::top::Top;
+
+use chrono::NaiveDateTime;
+
+mod common;
+use common::{input, pln, sql_execute};
+
+fn time(text: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").unwrap()
+}
+
+fn query(top: &mut Top, second: u32, sequence: i32, total_time: i32) -> olog::SResult<()> {
+    top.write(&sql_execute(&format!("2020-07-01T00:00:{:02}.000000", second), sequence, "omnisci", total_time))
+}
+
+#[test]
+fn top_rates() -> olog::SResult<()> {
+    let mut top = Top::new();
+    for i in 0..20 {
+        query(&mut top, 40 + i as u32 / 2, i, 100 + i)?;
+    }
+    for line in [
+        "2020-07-01T00:00:45.000000 E 15 3 RelAlgExecutor.cpp:100 Query execution failed with error Table 12 not found",
+        "2020-07-01T00:00:46.000000 W 15 3 RelAlgExecutor.cpp:100 Falling back to CPU",
+        "2020-07-01T00:00:47.000000 I 15 3 BufferMgr.cpp:100 ALLOCATION failed to find 1048576B free. Forcing Eviction.",
+        "2020-07-01T00:00:48.000000 I 15 3 Execute.cpp:100 Query ran out of GPU memory, attempting punt to CPU",
+    ] {
+        top.write(&pln(line))?;
+    }
+
+    let snapshot = top.snapshot(time("2020-07-01T00:00:49.900000"));
+    // 20 queries from 40 to 49 seconds
+    assert_eq!(snapshot.queries_per_s, 2.0);
+    assert_eq!(snapshot.calls_per_s, 2.0);
+    assert_eq!(snapshot.errors_per_min, 1);
+    // the allocation failure and the punt are warnings too
+    assert_eq!(snapshot.warnings_per_min, 3);
+    assert_eq!(snapshot.memory_failed, 1);
+    assert_eq!(snapshot.memory_punts_per_min, 1);
+    assert_eq!(snapshot.sessions, 1);
+    assert_eq!(snapshot.slowest.len(), 10);
+    assert_eq!(snapshot.slowest[0].total_time, 119);
+    assert_eq!(snapshot.slowest[0].query, "select 19;");
+
+    // a minute later the rates are back to 0, the slowest are kept for 5 minutes
+    let snapshot = top.snapshot(time("2020-07-01T00:01:50.000000"));
+    assert_eq!(snapshot.queries_per_s, 0.0);
+    assert_eq!(snapshot.errors_per_min, 0);
+    assert_eq!(snapshot.memory_punts_per_min, 0);
+    assert_eq!(snapshot.memory_punts, 1);
+    assert_eq!(snapshot.slowest.len(), 10);
+    let snapshot = top.snapshot(time("2020-07-01T00:06:00.000000"));
+    assert_eq!(snapshot.slowest.len(), 0);
+    Ok(())
+}
+
+#[test]
+fn top_running_and_sessions() -> olog::SResult<()> {
+    let mut top = Top::new();
+    for line in [
+        r#"2020-07-01T00:00:00.000000 I 15 1 DBHandler.cpp:533 stdlog connect 1 233 omnisci admin 409-as47 {"client","roles"} {"tcp:172.17.0.1:53140","super"}"#,
+        r#"2020-07-01T00:00:00.500000 I 15 2 DBHandler.cpp:533 stdlog connect 2 233 omnisci bob 455-NzOR {"client","roles"} {"tcp:172.17.0.2:53140",""}"#,
+        r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 3 0 omnisci admin 409-as47 {"query_str"} {"SELECT count(*) from flights;"}"#,
+        r#"2020-07-01T00:00:02.000000 I 15 4 DBHandler.cpp:1476 stdlog_begin sql_execute 4 0 omnisci bob 455-NzOR {"query_str"} {"SELECT 2;"}"#,
+        r#"2020-07-01T00:00:03.000000 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 4 1000 omnisci bob 455-NzOR {"query_str","client","nonce","execution_time_ms","total_time_ms"} {"SELECT 2;","tcp:172.17.0.2:53140","","999","1000"}"#,
+        r#"2020-07-01T00:00:04.000000 I 15 5 DBHandler.cpp:533 stdlog disconnect 5 0 omnisci bob 455-NzOR {"client"} {"tcp:172.17.0.2:53140"}"#,
+    ] {
+        top.write(&pln(line))?;
+    }
+
+    let snapshot = top.snapshot(time("2020-07-01T00:00:10.000000"));
+    assert_eq!(snapshot.sessions, 1);
+    assert_eq!(snapshot.running.len(), 1);
+    let running = &snapshot.running[0];
+    assert_eq!(running.event, "sql_execute");
+    assert_eq!(running.elapsed_ms, 9000);
+    assert_eq!(running.username, "admin");
+    assert_eq!(running.query, "SELECT count(*) from flights;");
+    assert!(top.now().unwrap() >= time("2020-07-01T00:00:04.000000"));
+    Ok(())
+}
+
+#[test]
+fn top_restart_and_expiry() -> olog::SResult<()> {
+    let mut top = Top::new();
+    let begin = r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 3 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#;
+    // the same pid and sequence on two servers
+    for hostname in ["db1", "db2"] {
+        let mut log = pln(begin);
+        log.hostname = Some(hostname.to_string());
+        top.write(&log)?;
+    }
+    let snapshot = top.snapshot(time("2020-07-01T00:00:02.000000"));
+    assert_eq!(snapshot.running.len(), 2);
+    assert_eq!(snapshot.sessions, 2);
+
+    // db1 restarted
+    let mut log = pln("2020-07-01T00:00:03.000000 I 16 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2");
+    log.hostname = Some(String::from("db1"));
+    top.write(&log)?;
+    let snapshot = top.snapshot(time("2020-07-01T00:00:04.000000"));
+    assert_eq!(snapshot.running.len(), 1);
+    assert_eq!(snapshot.sessions, 1);
+
+    // an hour later the call never ended and the session is idle
+    let mut log = pln("2020-07-01T01:00:02.000000 I 15 0 DBHandler.cpp:100 checkpoint");
+    log.hostname = Some(String::from("db2"));
+    top.write(&log)?;
+    let snapshot = top.snapshot(time("2020-07-01T01:00:02.000000"));
+    assert_eq!(snapshot.running.len(), 0);
+    assert_eq!(snapshot.sessions, 0);
+    Ok(())
+}
+
+#[test]
+fn top_inputs_without_hostname() -> olog::SResult<()> {
+    let mut top = Top::new();
+    let write = |top: &mut Top, line: &str, logfile: &str| top.write(&input(line, logfile));
+    // two docker servers with the same pid, sequence and session, logging at once
+    let begin = r#"2020-07-01T00:00:01.000000 I 15 3 DBHandler.cpp:1476 stdlog_begin sql_execute 7 0 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#;
+    for logfile in ["a/omnisci_server.INFO", "b/omnisci_server.INFO", "a/omnisci_server.INFO"] {
+        write(&mut top, begin, logfile)?;
+    }
+    let snapshot = top.snapshot(time("2020-07-01T00:00:02.000000"));
+    assert_eq!(snapshot.running.len(), 2);
+    assert_eq!(snapshot.sessions, 2);
+
+    // b restarted, a keeps its call and session
+    write(&mut top, "2020-07-01T00:00:03.000000 I 16 0 DBHandler.cpp:238 OmniSci Server 5.5.0-20201201-1b17b5c4e2", "b/omnisci_server.INFO")?;
+    let snapshot = top.snapshot(time("2020-07-01T00:00:04.000000"));
+    assert_eq!(snapshot.running.len(), 1);
+    assert_eq!(snapshot.sessions, 1);
+
+    // the stdlog of a ends only its call
+    write(&mut top, r#"2020-07-01T00:00:05.000000 I 15 3 DBHandler.cpp:1058 stdlog sql_execute 7 4000 omnisci admin 409-as47 {"query_str"} {"SELECT 1;"}"#, "a/omnisci_server.INFO")?;
+    let snapshot = top.snapshot(time("2020-07-01T00:00:06.000000"));
+    assert_eq!(snapshot.running.len(), 0);
+    Ok(())
+}
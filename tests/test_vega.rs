@@ -0,0 +1,140 @@
+/*
+ * Copyright 2021 OmniSci, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+extern crate omnisci_log_scraper;
+use omnisci_log_scraper::log_parser as olog;
+use olog::LogWriter;
+use olog::vega::VegaWriter;
+
+mod common;
+use common::pln;
+
+const LINES: [&str; 11] = [
+    r#"2020-07-01T10:00:00.000000 I 15 DBHandler.cpp:3358 stdlog_begin render_vega 5 0 omnisci admin 409-as47 {"widget_id","compression_level","vega_json","nonce"} {"2","3","{""width"":1002,""height"":726,""data"":[{""name"":""pointmap"",""sql"":""SELECT x, y FROM flights WHERE dep_delay > 10 LIMIT 2000000""},{""name"":""stats"",""source"":""pointmap"",""transform"":[]}],""marks"":[{""type"":""symbol"",""from"":{""data"":""pointmap""}}]}","7/3"}"#,
+    "2020-07-01T10:00:00.100000 I 15 8 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT x, y FROM flights WHERE dep_delay > 10 LIMIT 2000000'",
+    "2020-07-01T10:00:00.200000 1 15 8 Execute.cpp:2078 Launching 1 kernels for query.",
+    "2020-07-01T10:00:00.300000 1 15 8 Execute.cpp:2078 Launching 2 kernels for query.",
+    r#"2020-07-01T10:00:01.000000 I 15 2 RenderHandlerImpl.cpp:155 render_vega-COMPLETED nonce:7/3 Total Execution: 300 (ms), Total Render: 700 (ms)"#,
+    r#"2020-07-01T10:00:01.000100 I 15 DBHandler.cpp:3358 stdlog render_vega 5 1000 omnisci admin 409-as47 {"widget_id","compression_level","vega_json","nonce","client"} {"2","3","{""width"":1002,""height"":726,""data"":[{""name"":""pointmap"",""sql"":""SELECT x, y FROM flights WHERE dep_delay > 10 LIMIT 2000000""},{""name"":""stats"",""source"":""pointmap"",""transform"":[]}],""marks"":[{""type"":""symbol"",""from"":{""data"":""pointmap""}}]}","7/3","tcp:172.17.0.1:53140"}"#,
+    // not during a render
+    "2020-07-01T10:00:02.000000 I 15 8 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT COUNT(*) FROM flights'",
+    r#"2020-07-01T10:00:03.000000 I 15 DBHandler.cpp:3358 stdlog_begin render_vega 6 0 omnisci admin 409-as47 {"widget_id","compression_level","vega_json","nonce"} {"4","0","{""width"":400,""height"":300,""data"":[{""name"":""polys"",""sql"":""SELECT rowid, name FROM states""}],""marks"":[{""type"":""polys""},{""type"":""legend""}]}","7/4"}"#,
+    "2020-07-01T10:00:03.010000 I 15 8 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT rowid, name FROM states'",
+    r#"2020-07-01T10:00:03.050000 I 15 2 RenderHandlerImpl.cpp:155 render_vega-COMPLETED nonce:7/4 Total Execution: 20 (ms), Total Render: 30 (ms)"#,
+    r#"2020-07-01T10:00:03.050100 I 15 DBHandler.cpp:3358 stdlog render_vega 6 50 omnisci admin 409-as47 {"widget_id","compression_level","vega_json","nonce","client"} {"4","0","{""width"":400,""height"":300,""data"":[{""name"":""polys"",""sql"":""SELECT rowid, name FROM states""}],""marks"":[{""type"":""polys""},{""type"":""legend""}]}","7/4","tcp:172.17.0.1:53140"}"#,
+];
+
+#[test]
+fn vega_column() {
+    let log = pln(LINES[5]);
+    assert_eq!(log.event.as_deref(), Some("render_vega"));
+    assert_eq!((log.dashboardid.as_deref(), log.chartid.as_deref()), (Some("7"), Some("3")));
+    // vega_json is not sql
+    assert_eq!(log.fingerprint, None);
+    let vega = log.vega.as_ref().unwrap();
+    assert_eq!((vega.widget_id, vega.compression_level), (Some(2), Some(3)));
+    assert_eq!((vega.width, vega.height), (Some(1002), Some(726)));
+    assert_eq!(vega.marks, vec!["symbol"]);
+    // the transform of pointmap has no sql
+    assert_eq!(vega.data.len(), 1);
+    assert_eq!(vega.data[0].name, "pointmap");
+    assert_eq!(vega.data[0].sql, "SELECT x, y FROM flights WHERE dep_delay > 10 LIMIT 2000000");
+    assert_eq!(vega.data[0].fingerprint.len(), 16);
+
+    assert!(pln(LINES[0]).vega.is_some());
+    assert!(pln(LINES[1]).vega.is_none());
+    let json = serde_json::to_value(pln(LINES[10])).unwrap();
+    let column: serde_json::Value = serde_json::from_str(json["vega"].as_str().unwrap()).unwrap();
+    assert_eq!(column["marks"], serde_json::json!(["polys", "legend"]));
+    assert_eq!(column["data"][0]["sql"], "SELECT rowid, name FROM states");
+}
+
+#[test]
+fn slowest_renders_and_their_sql() -> olog::SResult<()> {
+    let mut writer = VegaWriter::new(olog::ReportOptions::default());
+    for line in LINES.iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    assert_eq!(rows.len(), 2);
+
+    let row = &rows[0];
+    assert_eq!((row.sequence, row.total_time), (Some(5), Some(1000)));
+    assert_eq!((row.dashboardid.as_str(), row.chartid.as_str()), ("7", "3"));
+    assert_eq!((row.widget_id, row.compression_level), (Some(2), Some(3)));
+    assert_eq!((row.execution_ms, row.render_ms), (Some(300), Some(700)));
+    assert_eq!((row.width, row.height), (Some(1002), Some(726)));
+    assert_eq!(row.marks, "symbol");
+    assert_eq!((row.executions, row.kernel_launches), (1, 2));
+    assert_eq!(row.fingerprints, pln(LINES[5]).vega.unwrap().data[0].fingerprint);
+    assert_eq!(row.sql, "SELECT x, y FROM flights WHERE dep_delay > 10 LIMIT 2000000");
+
+    // the COUNT(*) between the renders is not linked to either
+    let row = &rows[1];
+    assert_eq!((row.sequence, row.total_time), (Some(6), Some(50)));
+    assert_eq!(row.chartid, "4");
+    assert_eq!((row.execution_ms, row.render_ms), (Some(20), Some(30)));
+    assert_eq!(row.marks, "polys,legend");
+    assert_eq!((row.executions, row.kernel_launches), (1, 0));
+    assert_eq!(row.sql, "SELECT rowid, name FROM states");
+    Ok(())
+}
+
+#[test]
+fn lines_of_a_concurrent_call() -> olog::SResult<()> {
+    let render = |time: &str, event: &str, total: i32| format!(
+        r#"{} I 15 3 DBHandler.cpp:3358 {} render_vega 5 {} omnisci admin 409-as47 {{"widget_id","compression_level","vega_json","nonce"}} {{"1","0","{{""data"":[{{""name"":""t"",""sql"":""SELECT x FROM t""}}]}}","7/1"}}"#,
+        time, event, total);
+    let mut writer = VegaWriter::new(olog::ReportOptions::default());
+    for line in [
+        render("2020-07-01T10:00:00.000000", "stdlog_begin", 0),
+        // a sql_execute on thread 4 during the render
+        r#"2020-07-01T10:00:00.100000 I 15 4 DBHandler.cpp:1058 stdlog_begin sql_execute 6 0 omnisci bob 510-bd88 {"query_str"} {"SELECT y FROM u"}"#.to_string(),
+        "2020-07-01T10:00:00.200000 I 15 4 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT y FROM u'".to_string(),
+        "2020-07-01T10:00:00.300000 1 15 4 Execute.cpp:2078 Launching 4 kernels for query.".to_string(),
+        r#"2020-07-01T10:00:00.400000 I 15 4 DBHandler.cpp:1058 stdlog sql_execute 6 300 omnisci bob 510-bd88 {"query_str"} {"SELECT y FROM u"}"#.to_string(),
+        // the render's own sql, on its thread
+        "2020-07-01T10:00:00.500000 I 15 3 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT x FROM t'".to_string(),
+        "2020-07-01T10:00:00.600000 1 15 3 Execute.cpp:2078 Launching 1 kernels for query.".to_string(),
+        render("2020-07-01T10:00:01.000000", "stdlog", 1000),
+    ].iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    assert_eq!(rows.len(), 1);
+    assert_eq!((rows[0].executions, rows[0].kernel_launches), (1, 1));
+    assert_eq!(rows[0].fingerprints, pln(&render("2020-07-01T10:00:01.000000", "stdlog", 1000)).vega.unwrap().data[0].fingerprint);
+    Ok(())
+}
+
+#[test]
+fn open_renders_expire() -> olog::SResult<()> {
+    let mut writer = VegaWriter::new(olog::ReportOptions::default());
+    for line in [
+        // a render whose stdlog never came
+        LINES[0],
+        // hours later, a query not during a render
+        "2020-07-01T13:00:00.000000 I 15 8 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT COUNT(*) FROM flights'",
+        "2020-07-01T13:00:01.000000 I 15 8 Calcite.cpp:486 User calcite catalog omnisci sql 'SELECT COUNT(*) FROM flights'",
+        r#"2020-07-01T13:00:02.000000 I 15 DBHandler.cpp:3358 stdlog render_vega 6 5000 omnisci admin 409-as47 {"widget_id","compression_level","vega_json","nonce"} {"4","0","{}","7/4"}"#,
+    ].iter() {
+        writer.write(&pln(line))?;
+    }
+    let rows = writer.rows();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].executions, 0);
+    Ok(())
+}